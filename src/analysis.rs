@@ -0,0 +1,87 @@
+// post-game analysis
+//
+// a background job goes over games once they've ended, has the engine judge every ply,
+// and saves what it found as annotations, for the replay page
+// games waiting to be analyzed are found in the database, so none are missed across restarts
+// (games on boards with a tablebase get an exact analysis, see engine/analysis.rs)
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use sea_orm::DatabaseConnection;
+use uuid::Uuid;
+
+use crate::engine::analysis::{analyze, analyze_exact, ANALYSIS_DEPTH, EXACT_ANALYSIS_BUDGET};
+use crate::engine::tablebase::Tablebases;
+use crate::entity;
+
+const SCAN_INTERVAL: Duration = Duration::from_secs(10);
+// how many games are analyzed per scan
+const BATCH_SIZE: u64 = 5;
+
+pub async fn run(conn: DatabaseConnection, tablebases: Arc<Tablebases>) {
+    let mut interval = tokio::time::interval(SCAN_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(err) = analyze_ended(&conn, &tablebases).await {
+            tracing::error!("could not analyze ended games: {}", err);
+        }
+    }
+}
+
+async fn analyze_ended(
+    conn: &DatabaseConnection,
+    tablebases: &Arc<Tablebases>,
+) -> Result<(), String> {
+    let games = entity::game::find_unanalyzed(BATCH_SIZE, conn)
+        .await
+        .map_err(|err| format!("Database error: {}", err))?;
+
+    for game in games {
+        // a game that can't be analyzed is marked as analyzed anyway, with nothing to show,
+        // so it doesn't hold up the games after it on every scan
+        if let Err(err) = analyze_game(game.uuid, conn, tablebases).await {
+            tracing::error!("could not analyze game {}: {}", game.uuid, err);
+            entity::annotation::save_analysis(game.uuid, vec![], conn)
+                .await
+                .map_err(|err| format!("Database error: {}", err))?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn analyze_game(
+    game_id: Uuid,
+    conn: &DatabaseConnection,
+    tablebases: &Arc<Tablebases>,
+) -> Result<(), String> {
+    let boards = entity::board::find_for_game(game_id, conn)
+        .await
+        .map_err(|err| format!("Database error: {}", err))?
+        .into_iter()
+        .map(|board| serde_json::from_value(board.state))
+        .collect::<Result<Vec<Vec<Vec<u8>>>, _>>()
+        .map_err(|err| format!("could not deserialize game board: {}", err))?;
+
+    // searching every position takes a while, so it's kept off the async workers
+    let tablebases = tablebases.clone();
+    let plies = tokio::task::spawn_blocking(move || {
+        let (rows, cols) = boards.first().map_or((0, 0), |board| {
+            (board.len(), board.first().map_or(0, Vec::len))
+        });
+        // the solver may not get through a long game in time, so looking ahead is kept to fall back on
+        tablebases
+            .find(rows, cols)
+            .and_then(|tablebase| analyze_exact(&boards, tablebase, EXACT_ANALYSIS_BUDGET))
+            .unwrap_or_else(|| analyze(&boards, ANALYSIS_DEPTH))
+    })
+    .await
+    .map_err(|err| format!("Engine error: {}", err))?;
+
+    entity::annotation::save_analysis(game_id, plies, conn)
+        .await
+        .map_err(|err| format!("Database error: {}", err))
+}
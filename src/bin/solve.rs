@@ -0,0 +1,120 @@
+// the offline solver
+//
+//   cargo run --release --bin solve -- <rows>x<cols> <tablebase file> [plies]
+//
+// finds every position on the board up to so many plies in, works out their values,
+// and saves them as a tablebase for the server to load (see engine/tablebase.rs)
+// the deepest positions are solved first, so the ones before them only need searching a ply deep
+//
+// it's a binary of its own, built from the engine modules in the library
+// so the server doesn't carry the solver (or its big transposition table) around
+
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+use std::time::Instant;
+
+use stacky_sides::engine::solver::{Geometry, Outcome, Solver, Stones, Value};
+use stacky_sides::engine::tablebase::Tablebase;
+
+// how many plies in a tablebase covers, unless told otherwise
+const DEFAULT_PLIES: usize = 4;
+// how many bits of the position key pick a transposition table slot
+// (2^24 slots take 256MB)
+const TABLE_BITS: u32 = 24;
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if let Err(err) = run(&args) {
+        eprintln!("{}", err);
+        ::std::process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let usage = "usage: solve <rows>x<cols> <tablebase file> [plies]";
+    let (size, path) = match args {
+        [size, path, ..] => (size, Path::new(path)),
+        _ => return Err(usage.to_string()),
+    };
+    let geometry = size
+        .split_once('x')
+        .and_then(|(rows, cols)| Some((rows.parse().ok()?, cols.parse().ok()?)))
+        .and_then(|(rows, cols)| Geometry::new(rows, cols))
+        .ok_or_else(|| format!("board size not supported: {}", size))?;
+    let plies = match args.get(2) {
+        Some(plies) => plies
+            .parse::<usize>()
+            .map_err(|_| usage.to_string())?
+            .min(geometry.cells()),
+        None => DEFAULT_PLIES.min(geometry.cells()),
+    };
+    let (rows, cols) = (geometry.rows, geometry.cols);
+
+    let levels = find_positions(&geometry, plies);
+    let mut positions = Vec::<(u64, Value)>::new();
+
+    for (ply, level) in levels.iter().enumerate().rev() {
+        let started_at = Instant::now();
+        let tablebase = Tablebase::new(rows, cols, plies, positions.clone());
+        let mut solver =
+            Solver::new(Geometry::new(rows, cols).unwrap(), TABLE_BITS).with_tablebase(&tablebase);
+
+        // (with no deadline, every search runs to the end)
+        for (&key, stones) in level {
+            positions.push((key, solver.solve(stones).unwrap()));
+        }
+
+        println!(
+            "ply {}: {} positions, {} searched, in {:.1?}",
+            ply,
+            level.len(),
+            solver.nodes,
+            started_at.elapsed()
+        );
+    }
+
+    let tablebase = Tablebase::new(rows, cols, plies, positions);
+    tablebase
+        .save(path)
+        .map_err(|err| format!("could not save tablebase: {}", err))?;
+
+    let value = tablebase.get(0, 0).unwrap_or(0);
+    let outcome = match geometry.outcome(value, 0) {
+        Outcome::Win(plies) => format!("player 1 wins in {} plies", plies),
+        Outcome::Loss(plies) => format!("player 2 wins in {} plies", plies),
+        Outcome::Draw => String::from("a draw"),
+    };
+    println!(
+        "{}x{} is {} with perfect play ({} positions saved to {})",
+        rows,
+        cols,
+        outcome,
+        tablebase.len(),
+        path.display()
+    );
+
+    Ok(())
+}
+
+fn find_positions(geometry: &Geometry, plies: usize) -> Vec<HashMap<u64, Stones>> {
+    // every position up to plies in, a ply at a time (mirror images only once)
+    // leaving out the ones where the game's already over
+    let mut levels = vec![HashMap::from([(0, Stones::default())])];
+
+    while levels.len() <= plies {
+        let mut next = HashMap::new();
+        for stones in levels.last().unwrap().values() {
+            for cell in geometry.plays(stones) {
+                let after = stones.play(cell);
+                if geometry.is_winning_play(stones, cell) || after.plies == geometry.cells() {
+                    continue;
+                }
+                next.entry(geometry.key(&after)).or_insert(after);
+            }
+        }
+        levels.push(next);
+    }
+
+    levels
+}
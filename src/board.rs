@@ -1,4 +1,8 @@
-pub fn is_winning_move(row: usize, col: usize, game_board: &Vec<Vec<u8>>) -> bool {
+// the rules of the board
+//
+// shared by the game handlers, which referee plays, and the engines, which choose them
+
+pub fn is_winning_move(row: usize, col: usize, game_board: &[Vec<u8>]) -> bool {
     // line: |
     let (row_top, _col_top) = seek_top(row, col, game_board);
     let (row_btm, _col_btm) = seek_bottom(row, col, game_board);
@@ -27,10 +31,64 @@ pub fn is_winning_move(row: usize, col: usize, game_board: &Vec<Vec<u8>>) -> boo
         return true;
     }
 
-    return false;
+    false
+}
+
+pub fn is_valid_move(row: usize, col: usize, game_board: &[Vec<u8>]) -> bool {
+    // balls are stacked from either side of a row
+    // so a free cell can only be played if it's on either end of its row
+    // or if the cell beside it on either side has already been played
+    if row >= game_board.len() || col >= game_board[row].len() {
+        return false;
+    }
+
+    if game_board[row][col] != 0 {
+        return false;
+    }
+
+    let last_col = game_board[row].len() - 1;
+    col == 0 || col == last_col || game_board[row][col - 1] != 0 || game_board[row][col + 1] != 0
+}
+
+pub fn is_board_full(game_board: &[Vec<u8>]) -> bool {
+    game_board
+        .iter()
+        .all(|row| row.iter().all(|&cell| cell != 0))
+}
+
+pub fn get_next_player_num(game_board: &[Vec<u8>]) -> u8 {
+    // player 1 always plays first
+    // so it's player 2's turn whenever player 1 has played more
+    let mut counts = [0, 0, 0];
+    for row in game_board {
+        for &cell in row {
+            counts[cell as usize] += 1;
+        }
+    }
+
+    if counts[1] > counts[2] {
+        2
+    } else {
+        1
+    }
 }
 
-fn seek_top(row: usize, col: usize, game_board: &Vec<Vec<u8>>) -> (usize, usize) {
+pub fn get_opponent_num(player_num: u8) -> u8 {
+    match player_num {
+        1 => 2,
+        _ => 1,
+    }
+}
+
+pub fn count_plies(game_board: &[Vec<u8>]) -> usize {
+    game_board
+        .iter()
+        .flatten()
+        .filter(|&&cell| cell != 0)
+        .count()
+}
+
+fn seek_top(row: usize, col: usize, game_board: &[Vec<u8>]) -> (usize, usize) {
     let item = game_board[row][col];
     let mut row = row;
 
@@ -45,7 +103,7 @@ fn seek_top(row: usize, col: usize, game_board: &Vec<Vec<u8>>) -> (usize, usize)
     (row, col)
 }
 
-fn seek_bottom(row: usize, col: usize, game_board: &Vec<Vec<u8>>) -> (usize, usize) {
+fn seek_bottom(row: usize, col: usize, game_board: &[Vec<u8>]) -> (usize, usize) {
     let item = game_board[row][col];
     let mut row = row;
 
@@ -60,7 +118,7 @@ fn seek_bottom(row: usize, col: usize, game_board: &Vec<Vec<u8>>) -> (usize, usi
     (row, col)
 }
 
-fn seek_right(row: usize, col: usize, game_board: &Vec<Vec<u8>>) -> (usize, usize) {
+fn seek_right(row: usize, col: usize, game_board: &[Vec<u8>]) -> (usize, usize) {
     let item = game_board[row][col];
     let mut col = col;
 
@@ -75,7 +133,7 @@ fn seek_right(row: usize, col: usize, game_board: &Vec<Vec<u8>>) -> (usize, usiz
     (row, col)
 }
 
-fn seek_left(row: usize, col: usize, game_board: &Vec<Vec<u8>>) -> (usize, usize) {
+fn seek_left(row: usize, col: usize, game_board: &[Vec<u8>]) -> (usize, usize) {
     let item = game_board[row][col];
     let mut col = col;
 
@@ -90,7 +148,7 @@ fn seek_left(row: usize, col: usize, game_board: &Vec<Vec<u8>>) -> (usize, usize
     (row, col)
 }
 
-fn seek_top_right(row: usize, col: usize, game_board: &Vec<Vec<u8>>) -> (usize, usize) {
+fn seek_top_right(row: usize, col: usize, game_board: &[Vec<u8>]) -> (usize, usize) {
     let item = game_board[row][col];
     let (mut row, mut col) = (row, col);
 
@@ -106,7 +164,7 @@ fn seek_top_right(row: usize, col: usize, game_board: &Vec<Vec<u8>>) -> (usize,
     (row, col)
 }
 
-fn seek_top_left(row: usize, col: usize, game_board: &Vec<Vec<u8>>) -> (usize, usize) {
+fn seek_top_left(row: usize, col: usize, game_board: &[Vec<u8>]) -> (usize, usize) {
     let item = game_board[row][col];
     let (mut row, mut col) = (row, col);
 
@@ -122,7 +180,7 @@ fn seek_top_left(row: usize, col: usize, game_board: &Vec<Vec<u8>>) -> (usize, u
     (row, col)
 }
 
-fn seek_bottom_right(row: usize, col: usize, game_board: &Vec<Vec<u8>>) -> (usize, usize) {
+fn seek_bottom_right(row: usize, col: usize, game_board: &[Vec<u8>]) -> (usize, usize) {
     let item = game_board[row][col];
     let (mut row, mut col) = (row, col);
 
@@ -138,7 +196,7 @@ fn seek_bottom_right(row: usize, col: usize, game_board: &Vec<Vec<u8>>) -> (usiz
     (row, col)
 }
 
-fn seek_bottom_left(row: usize, col: usize, game_board: &Vec<Vec<u8>>) -> (usize, usize) {
+fn seek_bottom_left(row: usize, col: usize, game_board: &[Vec<u8>]) -> (usize, usize) {
     let item = game_board[row][col];
     let (mut row, mut col) = (row, col);
 
@@ -178,9 +236,35 @@ mod test {
         ]
     }
 
+    #[test]
+    fn valid_moves_stack_from_the_sides() {
+        let board = &get_extra_board();
+
+        assert!(is_valid_move(1, 0, board));
+        assert!(is_valid_move(1, 4, board));
+        assert!(is_valid_move(1, 1, board));
+        assert!(is_valid_move(3, 4, board));
+        assert!(!is_valid_move(3, 2, board));
+        assert!(!is_valid_move(0, 0, board));
+        assert!(!is_valid_move(5, 0, board));
+    }
+
+    #[test]
+    fn full_board() {
+        assert!(!is_board_full(&get_board()));
+        assert!(is_board_full(&[vec![1, 2], vec![2, 1]]));
+    }
+
+    #[test]
+    fn next_player_by_count() {
+        assert_eq!(get_next_player_num(&[vec![0, 0], vec![0, 0]]), 1);
+        assert_eq!(get_next_player_num(&[vec![1, 0], vec![0, 0]]), 2);
+        assert_eq!(get_next_player_num(&[vec![1, 0], vec![0, 2]]), 1);
+    }
+
     #[test]
     fn top_right_end_of_chain() {
-        let ref board = get_board();
+        let board = &get_board();
 
         assert_eq!(seek_top_right(1, 1, board), (1, 1));
         assert_eq!(seek_top_right(3, 1, board), (0, 4));
@@ -189,7 +273,7 @@ mod test {
 
     #[test]
     fn top_left_end_of_chain() {
-        let ref board = get_board();
+        let board = &get_board();
 
         assert_eq!(seek_top_left(1, 1, board), (1, 1));
         assert_eq!(seek_top_left(3, 3, board), (1, 1));
@@ -198,7 +282,7 @@ mod test {
 
     #[test]
     fn bottom_right_end_of_chain() {
-        let ref board = get_board();
+        let board = &get_board();
 
         assert_eq!(seek_bottom_right(0, 4, board), (0, 4));
         assert_eq!(seek_bottom_right(1, 1, board), (3, 3));
@@ -207,7 +291,7 @@ mod test {
 
     #[test]
     fn bottom_left_end_of_chain() {
-        let ref board = get_board();
+        let board = &get_board();
 
         assert_eq!(seek_bottom_left(0, 4, board), (3, 1));
         assert_eq!(seek_bottom_left(1, 1, board), (1, 1));
@@ -216,7 +300,7 @@ mod test {
 
     #[test]
     fn top_of_chain() {
-        let ref board = get_extra_board();
+        let board = &get_extra_board();
 
         assert_eq!(seek_top(0, 0, board), (0, 0));
         assert_eq!(seek_top(2, 2, board), (0, 2));
@@ -225,7 +309,7 @@ mod test {
 
     #[test]
     fn bottom_of_chain() {
-        let ref board = get_extra_board();
+        let board = &get_extra_board();
 
         assert_eq!(seek_bottom(0, 0, board), (0, 0));
         assert_eq!(seek_bottom(0, 2, board), (2, 2));
@@ -234,7 +318,7 @@ mod test {
 
     #[test]
     fn right_end_of_chain() {
-        let ref board = get_extra_board();
+        let board = &get_extra_board();
 
         assert_eq!(seek_right(2, 0, board), (2, 0));
         assert_eq!(seek_right(2, 3, board), (2, 4));
@@ -243,7 +327,7 @@ mod test {
 
     #[test]
     fn left_end_of_chain() {
-        let ref board = get_extra_board();
+        let board = &get_extra_board();
 
         assert_eq!(seek_left(2, 0, board), (2, 0));
         assert_eq!(seek_left(2, 3, board), (2, 2));
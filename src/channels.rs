@@ -4,16 +4,68 @@ use std::sync::{Arc, Mutex};
 use tokio::sync::broadcast;
 use uuid::Uuid;
 
+use crate::clock::Clock;
+
 type GameID = Uuid;
 
+// what the players in a game have offered each other
+// and are waiting on an answer for
+// (each holds the number of the player who made the offer)
+#[derive(Debug, Default)]
+pub struct Offers {
+    pub rematch: Option<u8>,
+    pub draw: Option<u8>,
+    pub takeback: Option<u8>,
+}
+
+// who is connected to the game, per seat
+// (indexed by player number - 1)
+#[derive(Debug, Default)]
+pub struct Presence {
+    pub connections: [usize; 2],
+    // bumped whenever a grace timer is started for the seat, or its player comes back
+    // so an earlier grace timer knows to stand down
+    pub grace_timers: [u64; 2],
+    // the player who left during their turn and didn't come back in time
+    pub abandoned_by: Option<u8>,
+}
+
+pub struct GameRoom {
+    pub channel: broadcast::Sender<String>,
+    pub offers: Mutex<Offers>,
+    // None until first needed, and for untimed games
+    pub clock: Mutex<Option<Clock>>,
+    pub presence: Mutex<Presence>,
+    // whether the computer is working on a move
+    // (so it never makes two at once)
+    pub is_computer_playing: Mutex<bool>,
+}
+
+impl GameRoom {
+    fn new() -> Self {
+        let (channel_tx, _channel_rx) = broadcast::channel(100);
+        Self {
+            channel: channel_tx,
+            offers: Mutex::new(Offers::default()),
+            clock: Mutex::new(None),
+            presence: Mutex::new(Presence::default()),
+            is_computer_playing: Mutex::new(false),
+        }
+    }
+}
+
 pub struct GameChannels {
-    channels: Mutex<HashMap<GameID, broadcast::Sender<String>>>,
+    rooms: Mutex<HashMap<GameID, Arc<GameRoom>>>,
+    // public games being opened and closed, for everyone in the lobby
+    lobby_channel: broadcast::Sender<String>,
 }
 
 impl GameChannels {
     fn new() -> Self {
+        let (lobby_tx, _lobby_rx) = broadcast::channel(100);
         Self {
-            channels: Mutex::new(HashMap::new()),
+            rooms: Mutex::new(HashMap::new()),
+            lobby_channel: lobby_tx,
         }
     }
 
@@ -22,25 +74,27 @@ impl GameChannels {
     }
 
     pub fn insert_channel(&self, game_id: GameID) -> Option<broadcast::Sender<String>> {
-        let (channel_tx, _channel_rx) = broadcast::channel(100);
-        self.channels.lock().unwrap().insert(game_id, channel_tx)
+        self.rooms
+            .lock()
+            .unwrap()
+            .insert(game_id, Arc::new(GameRoom::new()))
+            .map(|room| room.channel.clone())
     }
 
-    pub fn query_channel(&self, game_id: &GameID) -> Option<broadcast::Sender<String>> {
-        if let Some(channel_tx) = self.channels.lock().unwrap().get(game_id) {
-            Some(channel_tx.clone())
-        } else {
-            None
-        }
+    pub fn lobby_channel(&self) -> broadcast::Sender<String> {
+        self.lobby_channel.clone()
     }
 
     pub fn ensure_channel(&self, game_id: GameID) -> broadcast::Sender<String> {
-        match self.query_channel(&game_id) {
-            Some(channel) => channel,
-            _ => {
-                self.insert_channel(game_id);
-                self.query_channel(&game_id).unwrap()
-            }
-        }
+        self.ensure_room(game_id).channel.clone()
+    }
+
+    pub fn ensure_room(&self, game_id: GameID) -> Arc<GameRoom> {
+        self.rooms
+            .lock()
+            .unwrap()
+            .entry(game_id)
+            .or_insert_with(|| Arc::new(GameRoom::new()))
+            .clone()
     }
 }
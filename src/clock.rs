@@ -0,0 +1,275 @@
+// chess-style game clocks
+//
+// each seat has its own time, which only runs on that player's turn,
+// and grows by the increment after every move they make
+// clocks live in memory with the game's room, and are saved with the game
+// after every move, so they can be picked up again after a restart
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::board::{count_plies, get_next_player_num};
+use crate::channels::GameRoom;
+use crate::entity::game::Model as Game;
+
+// clocks only start once both players have made a move
+// so nobody loses on time waiting for an opponent to show up
+pub const CLOCK_START_PLIES: usize = 2;
+
+const MAX_INITIAL_MINUTES: u64 = 180;
+const MAX_INCREMENT_SECS: u64 = 180;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeControl {
+    pub initial: Duration,
+    pub increment: Duration,
+}
+
+impl TimeControl {
+    pub fn parse(text: &str) -> Option<Self> {
+        // in the form -- minutes+seconds (e.g. 3+2)
+        let (minutes, seconds) = text.trim().split_once('+')?;
+        let minutes: u64 = minutes.parse().ok()?;
+        let seconds: u64 = seconds.parse().ok()?;
+
+        if minutes == 0 || minutes > MAX_INITIAL_MINUTES || seconds > MAX_INCREMENT_SECS {
+            return None;
+        }
+
+        Some(Self {
+            initial: Duration::from_secs(minutes * 60),
+            increment: Duration::from_secs(seconds),
+        })
+    }
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}+{}",
+            self.initial.as_secs() / 60,
+            self.increment.as_secs()
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct ClockReading {
+    pub time_left_ms: [u64; 2],
+    // 0 if neither clock is running
+    pub running_num: u8,
+}
+
+impl ClockReading {
+    pub fn write(&self) -> String {
+        format!(
+            "Clock {} {} {}",
+            self.time_left_ms[0], self.time_left_ms[1], self.running_num
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct Clock {
+    time_left: [Duration; 2],
+    increment: Duration,
+    // the seat whose time is running, and since when
+    running: Option<(u8, Instant)>,
+    // bumped whenever the running seat changes
+    // so a flag timer set for an earlier turn knows to stand down
+    turn: u64,
+}
+
+impl Clock {
+    pub fn new(time_control: TimeControl) -> Self {
+        Self {
+            time_left: [time_control.initial; 2],
+            increment: time_control.increment,
+            running: None,
+            turn: 0,
+        }
+    }
+
+    pub fn restore(game: &Game, running_num: Option<u8>) -> Option<Self> {
+        // rebuilds the clock from what was last saved with the game
+        let time_control = game.time_control()?;
+        let mut clock = Self::new(time_control);
+
+        for (i, time_left_ms) in [game.player1_time_left_ms, game.player2_time_left_ms]
+            .into_iter()
+            .enumerate()
+        {
+            if let Some(time_left_ms) = time_left_ms {
+                clock.time_left[i] = Duration::from_millis(time_left_ms.max(0) as u64);
+            }
+        }
+
+        if let (Some(running_num), Some(updated_at), None) =
+            (running_num, game.clock_updated_at, game.ended_at)
+        {
+            let now = Instant::now();
+            let elapsed = (Utc::now() - updated_at.with_timezone(&Utc))
+                .to_std()
+                .unwrap_or_default();
+            clock.running = Some((running_num, now.checked_sub(elapsed).unwrap_or(now)));
+        }
+
+        Some(clock)
+    }
+
+    pub fn turn(&self) -> u64 {
+        self.turn
+    }
+
+    pub fn running_num(&self) -> u8 {
+        self.running.map(|(num, _)| num).unwrap_or(0)
+    }
+
+    pub fn time_left(&self, now: Instant) -> [Duration; 2] {
+        let mut time_left = self.time_left;
+        if let Some((num, since)) = self.running {
+            let i = num as usize - 1;
+            time_left[i] = time_left[i].saturating_sub(now.saturating_duration_since(since));
+        }
+        time_left
+    }
+
+    pub fn read(&self, now: Instant) -> ClockReading {
+        let time_left = self.time_left(now);
+        ClockReading {
+            time_left_ms: [
+                time_left[0].as_millis() as u64,
+                time_left[1].as_millis() as u64,
+            ],
+            running_num: self.running_num(),
+        }
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        // when the running seat runs out of time
+        self.running
+            .map(|(num, since)| since + self.time_left[num as usize - 1])
+    }
+
+    pub fn is_flagged(&self, now: Instant) -> bool {
+        match self.running {
+            Some((num, _)) => self.time_left(now)[num as usize - 1].is_zero(),
+            None => false,
+        }
+    }
+
+    pub fn press(&mut self, player_num: u8, next_num: Option<u8>, now: Instant) {
+        // the player has made their move, so their time stops
+        // and gets the increment, if it was running
+        if self.stop(now) == Some(player_num) {
+            self.time_left[player_num as usize - 1] += self.increment;
+        }
+        self.start(next_num, now);
+    }
+
+    pub fn switch(&mut self, next_num: Option<u8>, now: Instant) {
+        // hands the turn over without anyone having moved (e.g. after a takeback)
+        self.stop(now);
+        self.start(next_num, now);
+    }
+
+    pub fn stop(&mut self, now: Instant) -> Option<u8> {
+        // returns whose time was running
+        let (num, _) = self.running?;
+        self.time_left = self.time_left(now);
+        self.running = None;
+        self.turn += 1;
+        Some(num)
+    }
+
+    fn start(&mut self, num: Option<u8>, now: Instant) {
+        if let Some(num) = num {
+            self.running = Some((num, now));
+            self.turn += 1;
+        }
+    }
+}
+
+pub fn get_clock_running_num(game_board: &[Vec<u8>]) -> Option<u8> {
+    // whose clock should be running, once clocks have started
+    match count_plies(game_board) >= CLOCK_START_PLIES {
+        true => Some(get_next_player_num(game_board)),
+        _ => None,
+    }
+}
+
+pub fn with_clock<T>(
+    game: &Game,
+    running_num: Option<u8>,
+    room: &GameRoom,
+    f: impl FnOnce(&mut Clock) -> T,
+) -> Option<T> {
+    // runs f on the game's clock, restoring it first if the room doesn't have it yet
+    // returns None for untimed games
+    let mut clock = room.clock.lock().unwrap();
+    if clock.is_none() {
+        *clock = Clock::restore(game, running_num);
+    }
+    clock.as_mut().map(f)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn time_controls_are_parsed() {
+        let time_control = TimeControl::parse("3+2").unwrap();
+        assert_eq!(time_control.initial, Duration::from_secs(180));
+        assert_eq!(time_control.increment, Duration::from_secs(2));
+        assert_eq!(time_control.to_string(), "3+2");
+
+        assert!(TimeControl::parse("0+5").is_none());
+        assert!(TimeControl::parse("10").is_none());
+        assert!(TimeControl::parse("ten+0").is_none());
+        assert!(TimeControl::parse("999+0").is_none());
+    }
+
+    #[test]
+    fn only_the_running_seat_loses_time() {
+        let start = Instant::now();
+        let mut clock = Clock::new(TimeControl::parse("1+2").unwrap());
+        clock.switch(Some(1), start);
+
+        let now = start + Duration::from_secs(10);
+        assert_eq!(
+            clock.time_left(now),
+            [Duration::from_secs(50), Duration::from_secs(60)]
+        );
+
+        // the increment goes to the player who just moved
+        clock.press(1, Some(2), now);
+        let now = now + Duration::from_secs(5);
+        assert_eq!(
+            clock.time_left(now),
+            [Duration::from_secs(52), Duration::from_secs(55)]
+        );
+        assert_eq!(clock.running_num(), 2);
+    }
+
+    #[test]
+    fn running_out_of_time_flags() {
+        let start = Instant::now();
+        let mut clock = Clock::new(TimeControl::parse("1+0").unwrap());
+        clock.switch(Some(2), start);
+
+        assert_eq!(clock.deadline(), Some(start + Duration::from_secs(60)));
+        assert!(!clock.is_flagged(start + Duration::from_secs(59)));
+        assert!(clock.is_flagged(start + Duration::from_secs(61)));
+
+        // a stopped clock never flags
+        let turn = clock.turn();
+        clock.stop(start + Duration::from_secs(30));
+        assert!(clock.turn() > turn);
+        assert!(!clock.is_flagged(start + Duration::from_secs(61)));
+    }
+}
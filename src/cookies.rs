@@ -1,14 +1,158 @@
+use std::env;
+use std::sync::Arc;
+
 use async_trait::async_trait;
 use axum::extract::{FromRequest, RequestParts};
 use axum::http::StatusCode;
+use chrono::Utc;
+use cookie::SameSite;
+use hmac::{Hmac, Mac, NewMac};
+use sea_orm::DatabaseConnection;
+use sha2::Sha256;
 use tower_cookies::{Cookie as TowerCookie, Cookies as TowerCookies};
+use url::Url;
 use uuid::Uuid;
 
+use crate::entity;
+
 const COOKIE_NAME: &str = "stacky_sides_cookie";
+const SESSION_MAX_AGE_DAYS: i64 = 30;
+const SESSION_KEY_MIN_LEN: usize = 32;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub struct SessionConfig {
+    // the first key signs new cookies
+    // every key is accepted when verifying (for key rotation)
+    keys: Vec<Vec<u8>>,
+    secure: bool,
+}
+
+impl SessionConfig {
+    pub fn new(keys: Vec<Vec<u8>>, secure: bool) -> Self {
+        assert!(!keys.is_empty(), "at least one session key is required");
+        Self { keys, secure }
+    }
+
+    pub fn from_env(base_url: &Url) -> Self {
+        let keys = env::var("SESSION_KEYS").expect("SESSION_KEYS is not set in environment");
+        let keys = keys
+            .split(',')
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(|key| {
+                if key.len() < SESSION_KEY_MIN_LEN {
+                    panic!(
+                        "Each key in SESSION_KEYS must be at least {} characters long",
+                        SESSION_KEY_MIN_LEN
+                    );
+                }
+                key.as_bytes().to_vec()
+            })
+            .collect::<Vec<_>>();
 
-#[derive(Clone, Copy)]
+        Self::new(keys, base_url.scheme() == "https")
+    }
+
+    pub fn new_in_arc(self) -> Arc<Self> {
+        Arc::new(self)
+    }
+
+    fn signature(key: &[u8], payload: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(key).expect("HMAC can take a key of any size");
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    // value is in the form -- session_id.expires_at.signature
+    // where expires_at is a unix timestamp
+    // and signature is the base64 encoded HMAC of the first two parts
+    fn sign(&self, session_id: Uuid, expires_at: i64) -> String {
+        let payload = format!("{}.{}", session_id, expires_at);
+        let signature = Self::signature(&self.keys[0], &payload)
+            .finalize()
+            .into_bytes();
+
+        format!(
+            "{}.{}",
+            payload,
+            base64::encode_config(signature, base64::URL_SAFE_NO_PAD)
+        )
+    }
+
+    fn verify(&self, value: &str, now: i64) -> Option<Uuid> {
+        let (payload, signature) = value.rsplit_once('.')?;
+        let signature = base64::decode_config(signature, base64::URL_SAFE_NO_PAD).ok()?;
+
+        let is_signed = self
+            .keys
+            .iter()
+            .any(|key| Self::signature(key, payload).verify(&signature).is_ok());
+        if !is_signed {
+            return None;
+        }
+
+        let (session_id, expires_at) = payload.split_once('.')?;
+        let expires_at: i64 = expires_at.parse().ok()?;
+        if expires_at <= now {
+            return None;
+        }
+
+        session_id.parse().ok()
+    }
+
+    fn build_cookie(&self, session_id: Uuid, now: i64) -> TowerCookie<'static> {
+        let max_age = time::Duration::days(SESSION_MAX_AGE_DAYS);
+        let expires_at = now + max_age.whole_seconds();
+
+        TowerCookie::build(COOKIE_NAME, self.sign(session_id, expires_at))
+            .path("/")
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .secure(self.secure)
+            .max_age(max_age)
+            .finish()
+    }
+}
+
+#[derive(Clone)]
 pub struct Cookies {
+    // the key this browser plays as
+    // a guest's own session id, or the account's uuid once logged in
     pub session_id: Uuid,
+    // the account session kept in the cookie, once logged in
+    account_session_id: Option<Uuid>,
+    jar: TowerCookies,
+    session_config: Arc<SessionConfig>,
+}
+
+impl Cookies {
+    pub fn is_logged_in(&self) -> bool {
+        self.account_session_id.is_some()
+    }
+
+    pub fn account_session_id(&self) -> Option<Uuid> {
+        self.account_session_id
+    }
+
+    // moves this browser over to an account's session, on login or registering
+    pub fn log_in(&mut self, session: &entity::account_session::Model) {
+        let now = Utc::now().timestamp();
+        self.jar
+            .add(self.session_config.build_cookie(session.uuid, now));
+        self.session_id = session.account_id;
+        self.account_session_id = Some(session.uuid);
+    }
+
+    // moves this browser over to a fresh guest session
+    pub fn log_out(&mut self) {
+        let now = Utc::now().timestamp();
+        let session_id = Uuid::new_v4();
+        self.jar
+            .add(self.session_config.build_cookie(session_id, now));
+        self.session_id = session_id;
+        self.account_session_id = None;
+    }
 }
 
 #[async_trait]
@@ -29,13 +173,110 @@ where
             "Can't extract cookies. Is `CookieManagerLayer` enabled?",
         ))?;
 
-        let session_id = cookies
+        let session_config = extensions.get::<Arc<SessionConfig>>().cloned().ok_or((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Can't extract cookies. Is `SessionConfig` added as an extension?",
+        ))?;
+
+        let conn = extensions.get::<DatabaseConnection>().cloned().ok_or((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Can't extract cookies. Is `DatabaseConnection` added as an extension?",
+        ))?;
+
+        // tampered, expired or missing cookies all start a fresh session
+        let now = Utc::now().timestamp();
+        let mut cookie_id = cookies
             .get(COOKIE_NAME)
-            .and_then(|cookie| cookie.value().parse().ok())
-            .unwrap_or(Uuid::new_v4());
+            .and_then(|cookie| session_config.verify(cookie.value(), now))
+            .unwrap_or_else(Uuid::new_v4);
+
+        let lookup = entity::account_session::look_up(cookie_id, &conn)
+            .await
+            .map_err(|_| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Can't look up the session",
+                )
+            })?;
+        let (session_id, account_session_id) = match lookup.account_id {
+            Some(account_id) => (account_id, Some(cookie_id)),
+            None => {
+                // registering turns the guest's key into the account's uuid
+                // so a guest cookie still holding it (e.g. one copied before registering)
+                // starts a fresh session rather than playing as the account
+                if lookup.is_account_key {
+                    cookie_id = Uuid::new_v4();
+                }
+                (cookie_id, None)
+            }
+        };
+
+        // re-issue on every request, so that active sessions don't expire
+        // and cookies signed with an older key move on to the current one
+        cookies.add(session_config.build_cookie(cookie_id, now));
+
+        Ok(Cookies {
+            session_id,
+            account_session_id,
+            jar: cookies,
+            session_config,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const NOW: i64 = 1_600_000_000;
+
+    fn get_config(keys: &[&str]) -> SessionConfig {
+        SessionConfig::new(keys.iter().map(|k| k.as_bytes().to_vec()).collect(), false)
+    }
+
+    #[test]
+    fn signed_session_is_verified() {
+        let config = get_config(&["current-key"]);
+        let session_id = Uuid::new_v4();
+
+        let value = config.sign(session_id, NOW + 60);
+        assert_eq!(config.verify(&value, NOW), Some(session_id));
+    }
+
+    #[test]
+    fn tampered_session_is_rejected() {
+        let config = get_config(&["current-key"]);
+        let value = config.sign(Uuid::new_v4(), NOW + 60);
+
+        let other_id = Uuid::new_v4().to_string();
+        let tampered = format!("{}{}", other_id, &value[other_id.len()..]);
+        assert_eq!(config.verify(&tampered, NOW), None);
+
+        let extended = value.replace(&(NOW + 60).to_string(), &(NOW + 6000).to_string());
+        assert_eq!(config.verify(&extended, NOW), None);
+
+        let bare_id = Uuid::new_v4().to_string();
+        assert_eq!(config.verify(&bare_id, NOW), None);
+    }
+
+    #[test]
+    fn expired_session_is_rejected() {
+        let config = get_config(&["current-key"]);
+        let value = config.sign(Uuid::new_v4(), NOW);
+
+        assert_eq!(config.verify(&value, NOW), None);
+    }
+
+    #[test]
+    fn rotated_keys_are_accepted() {
+        let old_config = get_config(&["old-key"]);
+        let session_id = Uuid::new_v4();
+        let value = old_config.sign(session_id, NOW + 60);
 
-        cookies.add(TowerCookie::new(COOKIE_NAME, session_id.to_string()));
+        let rotated_config = get_config(&["new-key", "old-key"]);
+        assert_eq!(rotated_config.verify(&value, NOW), Some(session_id));
 
-        Ok(Cookies { session_id })
+        let retired_config = get_config(&["new-key"]);
+        assert_eq!(retired_config.verify(&value, NOW), None);
     }
 }
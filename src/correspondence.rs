@@ -0,0 +1,121 @@
+// correspondence games give each player hours or days per move
+//
+// deadlines are kept with the game in the database (not in memory, like clocks),
+// so a scheduler can forfeit overdue moves, even across server restarts
+// a deadline only runs once both seats are taken
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use sea_orm::{DatabaseConnection, DbErr};
+use tokio::sync::broadcast;
+
+use crate::board::{get_next_player_num, get_opponent_num};
+use crate::channels::GameChannels;
+use crate::entity;
+use crate::entity::game::ResultReason;
+use crate::games::{end_game, get_board_state};
+
+const MAX_MOVE_DEADLINE_HOURS: i32 = 14 * 24;
+const SCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+pub fn parse_move_deadline(text: &str) -> Option<i32> {
+    // in the form -- 12h or 3d
+    // returns the hours allowed per move
+    let text = text.trim();
+    let (number, hours_per_unit) = match text.chars().last()? {
+        'h' => (&text[..text.len() - 1], 1),
+        'd' => (&text[..text.len() - 1], 24),
+        _ => return None,
+    };
+    let hours = number.parse::<i32>().ok()?.checked_mul(hours_per_unit)?;
+
+    match hours > 0 && hours <= MAX_MOVE_DEADLINE_HOURS {
+        true => Some(hours),
+        _ => None,
+    }
+}
+
+pub fn describe_move_deadline(hours: i32) -> String {
+    match (hours % 24, hours / 24) {
+        (0, 1) => String::from("1 day per move"),
+        (0, days) => format!("{} days per move", days),
+        (_, _) if hours == 1 => String::from("1 hour per move"),
+        _ => format!("{} hours per move", hours),
+    }
+}
+
+pub async fn restart_move_deadline(
+    game: &entity::game::Model,
+    conn: &DatabaseConnection,
+    channel: &broadcast::Sender<String>,
+) -> Result<(), DbErr> {
+    // gives the player to move a fresh deadline, and lets everyone know
+    if let Some(move_deadline_at) = entity::game::start_move_deadline(game, conn).await? {
+        let _ = channel.send(format!("Deadline {}", move_deadline_at.to_rfc3339()));
+    }
+
+    Ok(())
+}
+
+pub async fn run(conn: DatabaseConnection, game_channels: Arc<GameChannels>) {
+    let mut interval = tokio::time::interval(SCAN_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(err) = forfeit_overdue(&conn, &game_channels).await {
+            tracing::error!("could not forfeit overdue games: {}", err);
+        }
+    }
+}
+
+async fn forfeit_overdue(
+    conn: &DatabaseConnection,
+    game_channels: &GameChannels,
+) -> Result<(), String> {
+    let games = entity::game::find_overdue(Utc::now(), conn)
+        .await
+        .map_err(|err| format!("Database error: {}", err))?;
+
+    for game in games {
+        forfeit(game, conn, game_channels).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn forfeit(
+    game: entity::game::Model,
+    conn: &DatabaseConnection,
+    game_channels: &GameChannels,
+) -> Result<(), String> {
+    // the player who should have moved loses
+    let board_state = get_board_state(&game, conn).await?;
+    let winner_num = get_opponent_num(get_next_player_num(&board_state)) as usize;
+
+    end_game(game, winner_num, ResultReason::Timeout, conn, game_channels).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn move_deadlines_are_parsed() {
+        assert_eq!(parse_move_deadline("12h"), Some(12));
+        assert_eq!(parse_move_deadline("3d"), Some(72));
+        assert_eq!(parse_move_deadline("0d"), None);
+        assert_eq!(parse_move_deadline("30d"), None);
+        assert_eq!(parse_move_deadline("3+2"), None);
+        assert_eq!(parse_move_deadline(""), None);
+    }
+
+    #[test]
+    fn move_deadlines_are_described() {
+        assert_eq!(describe_move_deadline(24), "1 day per move");
+        assert_eq!(describe_move_deadline(72), "3 days per move");
+        assert_eq!(describe_move_deadline(12), "12 hours per move");
+    }
+}
@@ -0,0 +1,272 @@
+// post-game analysis
+//
+// every position in a game is searched to a fixed depth, so each play can be compared
+// with the best one found there
+// scores are turned into an advantage, from -100 (white wins) to 100 (black wins),
+// and plays are judged by how much of the player's advantage they gave away
+// on boards with a tablebase, the solver finds every play's true value instead,
+// so the advantage is all or nothing, and any play that changes the result is a blunder
+// (as long as it can do that for the whole game within its budget)
+
+use std::time::{Duration, Instant};
+
+use super::minimax::{score_plays, WIN_SCORE};
+use super::perfect::TABLE_BITS;
+use super::solver::{Geometry, Solver, Stones};
+use super::tablebase::Tablebase;
+use crate::board::get_next_player_num;
+
+pub const ANALYSIS_DEPTH: u32 = 4;
+// how long the solver may take over a game
+pub const EXACT_ANALYSIS_BUDGET: Duration = Duration::from_secs(10);
+
+// how quickly scores close in on a sure win
+// (a score of this much is an advantage of about 76)
+const ADVANTAGE_SCALE: f64 = 200.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Judgement {
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Judgement {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Inaccuracy => "inaccuracy",
+            Self::Mistake => "mistake",
+            Self::Blunder => "blunder",
+        }
+    }
+
+    fn from_loss(loss: i32) -> Option<Self> {
+        // loss is the advantage given away, from the player's side
+        match loss {
+            30.. => Some(Self::Blunder),
+            20..=29 => Some(Self::Mistake),
+            10..=19 => Some(Self::Inaccuracy),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlyAnalysis {
+    pub player_num: u8,
+    pub play: (usize, usize),
+    // the same as play, unless another one was found to be better
+    pub best_play: (usize, usize),
+    // black's advantage after the play
+    pub advantage: i32,
+    pub judgement: Option<Judgement>,
+}
+
+pub fn analyze(boards: &[Vec<Vec<u8>>], depth: u32) -> Vec<PlyAnalysis> {
+    // boards are the game's positions in order, starting from the empty board
+    boards
+        .windows(2)
+        .map_while(|pair| analyze_ply(&pair[0], &pair[1], depth))
+        .collect()
+}
+
+fn analyze_ply(before: &[Vec<u8>], after: &[Vec<u8>], depth: u32) -> Option<PlyAnalysis> {
+    let play = find_play(before, after)?;
+    let player_num = get_next_player_num(before);
+
+    let scores = score_plays(before, depth);
+    let played_score = scores
+        .iter()
+        .find(|(other, _)| *other == play)
+        .map(|&(_, score)| score)?;
+    let (best_play, best_score) =
+        scores
+            .into_iter()
+            .fold((play, played_score), |best, (other, score)| {
+                match score > best.1 {
+                    true => (other, score),
+                    _ => best,
+                }
+            });
+
+    let advantage = to_advantage(played_score);
+    Some(PlyAnalysis {
+        player_num,
+        play,
+        best_play,
+        advantage: match player_num {
+            1 => advantage,
+            _ => -advantage,
+        },
+        judgement: Judgement::from_loss(to_advantage(best_score) - advantage),
+    })
+}
+
+pub fn analyze_exact(
+    boards: &[Vec<Vec<u8>>],
+    tablebase: &Tablebase,
+    budget: Duration,
+) -> Option<Vec<PlyAnalysis>> {
+    // None if the solver couldn't get through the game within the budget
+    let geometry = Geometry::new(tablebase.rows, tablebase.cols)?;
+    let mut solver = Solver::new(geometry, TABLE_BITS)
+        .with_tablebase(tablebase)
+        .with_deadline(Instant::now() + budget);
+
+    let plies = boards
+        .windows(2)
+        .map_while(|pair| analyze_exact_ply(&mut solver, &pair[0], &pair[1]))
+        .collect();
+
+    match solver.is_out_of_time() {
+        true => None,
+        _ => Some(plies),
+    }
+}
+
+fn analyze_exact_ply(
+    solver: &mut Solver,
+    before: &[Vec<u8>],
+    after: &[Vec<u8>],
+) -> Option<PlyAnalysis> {
+    let play = find_play(before, after)?;
+    let player_num = get_next_player_num(before);
+    let cols = solver.geometry.cols;
+
+    let values = solver.solve_plays(&Stones::from_board(before))?;
+    let cell = play.0 * cols + play.1;
+    let played_value = values
+        .iter()
+        .find(|&&(other, _)| other == cell)
+        .map(|&(_, value)| value)?;
+    let (best_cell, best_value) =
+        values
+            .into_iter()
+            .fold((cell, played_value), |best, (other, value)| {
+                match value > best.1 {
+                    true => (other, value),
+                    _ => best,
+                }
+            });
+
+    let advantage = 100 * played_value.signum() as i32;
+    Some(PlyAnalysis {
+        player_num,
+        play,
+        best_play: (best_cell / cols, best_cell % cols),
+        advantage: match player_num {
+            1 => advantage,
+            _ => -advantage,
+        },
+        judgement: Judgement::from_loss(100 * best_value.signum() as i32 - advantage),
+    })
+}
+
+fn find_play(before: &[Vec<u8>], after: &[Vec<u8>]) -> Option<(usize, usize)> {
+    // the cell that was free before and isn't after
+    before
+        .iter()
+        .zip(after)
+        .enumerate()
+        .find_map(|(row, cells)| {
+            cells
+                .0
+                .iter()
+                .zip(cells.1)
+                .position(|(&cell_before, &cell_after)| cell_before == 0 && cell_after != 0)
+                .map(|col| (row, col))
+        })
+}
+
+fn to_advantage(score: i32) -> i32 {
+    // only sure results reach 100
+    if score >= WIN_SCORE {
+        100
+    } else if score <= -WIN_SCORE {
+        -100
+    } else {
+        let advantage = 100.0 * (score as f64 / ADVANTAGE_SCALE).tanh();
+        (advantage.round() as i32).clamp(-99, 99)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn missed_wins_are_blunders() {
+        // black has three on the top row, but plays elsewhere
+        // and white goes on to connect four down the right edge
+        let mut boards = vec![vec![
+            vec![1, 1, 1, 0, 0, 0, 2],
+            vec![0, 0, 0, 0, 0, 0, 2],
+            vec![0, 0, 0, 0, 0, 0, 2],
+            vec![0, 0, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0, 0],
+        ]];
+        for (row, col, player_num) in [(6, 0, 1), (3, 6, 2)] {
+            let mut board = boards.last().unwrap().clone();
+            board[row][col] = player_num;
+            boards.push(board);
+        }
+
+        let plies = analyze(&boards, 2);
+        assert_eq!(plies.len(), 2);
+
+        assert_eq!(plies[0].player_num, 1);
+        assert_eq!(plies[0].play, (6, 0));
+        assert_eq!(plies[0].best_play, (0, 3));
+        assert_eq!(plies[0].judgement, Some(Judgement::Blunder));
+        assert_eq!(plies[0].advantage, -100);
+
+        assert_eq!(plies[1].play, (3, 6));
+        assert_eq!(plies[1].best_play, (3, 6));
+        assert_eq!(plies[1].judgement, None);
+        assert_eq!(plies[1].advantage, -100);
+    }
+
+    #[test]
+    fn solved_boards_are_judged_by_their_results() {
+        // black can win on the top row, but plays elsewhere
+        // and white wins there instead
+        let mut boards = vec![vec![
+            vec![1, 1, 1, 0],
+            vec![2, 0, 0, 2],
+            vec![0, 0, 0, 2],
+            vec![1, 0, 0, 2],
+        ]];
+        for (row, col, player_num) in [(2, 0, 1), (0, 3, 2)] {
+            let mut board = boards.last().unwrap().clone();
+            board[row][col] = player_num;
+            boards.push(board);
+        }
+
+        let plies = analyze_exact(
+            &boards,
+            &Tablebase::new(4, 4, 0, vec![]),
+            EXACT_ANALYSIS_BUDGET,
+        )
+        .unwrap();
+        assert_eq!(plies.len(), 2);
+
+        assert_eq!(plies[0].best_play, (0, 3));
+        assert_eq!(plies[0].judgement, Some(Judgement::Blunder));
+        assert_eq!(plies[0].advantage, -100);
+
+        assert_eq!(plies[1].best_play, (0, 3));
+        assert_eq!(plies[1].judgement, None);
+        assert_eq!(plies[1].advantage, -100);
+    }
+
+    #[test]
+    fn advantages_are_bounded() {
+        assert_eq!(to_advantage(0), 0);
+        assert_eq!(to_advantage(WIN_SCORE + 3), 100);
+        assert_eq!(to_advantage(-WIN_SCORE), -100);
+        assert_eq!(to_advantage(WIN_SCORE - 1), 99);
+        assert!(to_advantage(50) > 0 && to_advantage(50) < to_advantage(100));
+    }
+}
@@ -0,0 +1,284 @@
+// engines that run as separate programs, written in whatever language
+//
+// each is configured with a command, and started the first time it's needed
+// the server talks to it over its stdin and stdout, one line at a time:
+//
+//     server:  stacky
+//     engine:  stackyok                      (anything before this is ignored)
+//     server:  position 0000000/1000000/...  (rows top to bottom, cells left to right,
+//                                             0 for free, 1 for black, 2 for white)
+//     server:  go player x movetime ms seed n [time ms1 ms2]
+//     engine:  bestplay row col              (anything before this is ignored)
+//
+// where x is the player the engine plays for, movetime is how long it may think,
+// and time is what's left on each player's clock, in timed games
+// an engine that crashes, hangs, or plays something it can't is restarted,
+// and a random play is made for it instead
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::random::Random;
+use super::{Engine, Position};
+use crate::board::{get_next_player_num, is_valid_move};
+
+// how long an engine has to start up,
+// and how long past its movetime it has to answer
+const GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+pub struct External {
+    name: String,
+    description: String,
+    command: Vec<String>,
+    // None until first needed, and after a failure
+    process: Mutex<Option<Process>>,
+}
+
+struct Process {
+    child: Child,
+    stdin: ChildStdin,
+    // lines from the engine's stdout, read on a thread of their own
+    // so waiting on them can time out
+    lines: Receiver<String>,
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl External {
+    pub fn new(name: String, command: Vec<String>) -> Self {
+        Self {
+            description: format!("By {} (external)", name),
+            name,
+            command,
+            process: Mutex::new(None),
+        }
+    }
+
+    fn ask(
+        &self,
+        position: &Position,
+        budget: Duration,
+        seed: u64,
+    ) -> Result<(usize, usize), String> {
+        let mut process = self.process.lock().unwrap();
+        if process.is_none() {
+            *process = Some(start(&self.command)?);
+        }
+
+        let result = exchange(process.as_mut().unwrap(), position, budget, seed);
+        if result.is_err() {
+            // start over next time
+            *process = None;
+        }
+
+        result
+    }
+}
+
+impl Engine for External {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn choose_play(
+        &self,
+        position: &Position,
+        budget: Duration,
+        seed: u64,
+    ) -> Option<(usize, usize)> {
+        match self.ask(position, budget, seed) {
+            Ok(play) => Some(play),
+            Err(err) => {
+                tracing::error!("external engine {} failed: {}", self.name, err);
+                Random.choose_play(position, budget, seed)
+            }
+        }
+    }
+}
+
+pub fn parse_engines(text: &str) -> Vec<External> {
+    // in the form -- name=command with args;other=command
+    // (names are made of lowercase letters, digits, - and _)
+    text.split(';')
+        .filter_map(|entry| {
+            let (name, command) = entry.split_once('=')?;
+            let name = name.trim();
+            let command = command
+                .split_whitespace()
+                .map(String::from)
+                .collect::<Vec<_>>();
+
+            let is_valid_name = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+            match is_valid_name && !command.is_empty() {
+                true => Some(External::new(name.to_string(), command)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn start(command: &[String]) -> Result<Process, String> {
+    let mut child = Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("could not start {}: {}", command[0], err))?;
+
+    let stdin = child.stdin.take().unwrap();
+    let stdout = child.stdout.take().unwrap();
+    let (lines_tx, lines_rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if lines_tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut process = Process {
+        child,
+        stdin,
+        lines: lines_rx,
+    };
+    send(&mut process, "stacky")?;
+    wait_for(&process, GRACE_PERIOD, |line| {
+        (line.trim() == "stackyok").then_some(())
+    })?;
+
+    Ok(process)
+}
+
+fn exchange(
+    process: &mut Process,
+    position: &Position,
+    budget: Duration,
+    seed: u64,
+) -> Result<(usize, usize), String> {
+    // anything left over from an earlier exchange is stale
+    while process.lines.try_recv().is_ok() {}
+
+    send(process, &write_position(&position.board))?;
+    send(process, &write_go(position, budget, seed))?;
+
+    let play = wait_for(process, budget + GRACE_PERIOD, read_play)?;
+    match is_valid_move(play.0, play.1, &position.board) {
+        true => Ok(play),
+        _ => Err(format!("invalid play: {} {}", play.0, play.1)),
+    }
+}
+
+fn send(process: &mut Process, line: &str) -> Result<(), String> {
+    writeln!(process.stdin, "{}", line)
+        .and_then(|_| process.stdin.flush())
+        .map_err(|err| format!("could not write to engine: {}", err))
+}
+
+fn wait_for<T>(
+    process: &Process,
+    timeout: Duration,
+    read: impl Fn(&str) -> Option<T>,
+) -> Result<T, String> {
+    // skips lines until one can be read
+    let deadline = Instant::now() + timeout;
+    loop {
+        let time_left = deadline.saturating_duration_since(Instant::now());
+        match process.lines.recv_timeout(time_left) {
+            Ok(line) => {
+                if let Some(value) = read(&line) {
+                    return Ok(value);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => return Err(String::from("timed out")),
+            Err(RecvTimeoutError::Disconnected) => return Err(String::from("engine exited")),
+        }
+    }
+}
+
+fn write_position(board: &[Vec<u8>]) -> String {
+    let rows = board
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.to_string()).collect::<String>())
+        .collect::<Vec<_>>();
+    format!("position {}", rows.join("/"))
+}
+
+fn write_go(position: &Position, budget: Duration, seed: u64) -> String {
+    let mut line = format!(
+        "go player {} movetime {} seed {}",
+        get_next_player_num(&position.board),
+        budget.as_millis(),
+        seed
+    );
+    if let Some([time_left1, time_left2]) = position.time_left_ms {
+        line.push_str(&format!(" time {} {}", time_left1, time_left2));
+    }
+    line
+}
+
+fn read_play(line: &str) -> Option<(usize, usize)> {
+    // bestplay row col
+    match line.split_whitespace().collect::<Vec<_>>()[..] {
+        ["bestplay", row, col] => Some((row.parse().ok()?, col.parse().ok()?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn engines_are_parsed() {
+        let engines =
+            parse_engines("bot=/usr/bin/bot --level 3; Bad Name=bot;empty=;other=./other");
+
+        assert_eq!(engines.len(), 2);
+        assert_eq!(engines[0].name(), "bot");
+        assert_eq!(engines[0].command, vec!["/usr/bin/bot", "--level", "3"]);
+        assert_eq!(engines[1].name(), "other");
+    }
+
+    #[test]
+    fn messages_are_written_and_read() {
+        let mut position = Position::new(vec![vec![0, 1, 0], vec![0, 0, 2]]);
+        position.time_left_ms = Some([1500, 900]);
+
+        assert_eq!(write_position(&position.board), "position 010/002");
+        assert_eq!(
+            write_go(&position, Duration::from_millis(250), 7),
+            "go player 1 movetime 250 seed 7 time 1500 900"
+        );
+        assert_eq!(read_play("bestplay 2 0"), Some((2, 0)));
+        assert_eq!(read_play("info depth 3"), None);
+        assert_eq!(read_play("bestplay two 0"), None);
+    }
+
+    #[test]
+    fn engines_that_cannot_start_are_replaced_by_random_plays() {
+        let engine = External::new(
+            String::from("missing"),
+            vec![String::from("/nonexistent/stacky-engine")],
+        );
+        let position = Position::new(vec![vec![1, 0, 0], vec![2, 0, 0]]);
+
+        let play = engine.choose_play(&position, Duration::from_millis(10), 7);
+        assert!(play.is_some_and(|(row, col)| is_valid_move(row, col, &position.board)));
+    }
+}
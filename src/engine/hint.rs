@@ -0,0 +1,121 @@
+// hints for the player to move
+//
+// an engine picks the play, and the hint says what the play is for,
+// in terms a beginner can follow
+
+use std::time::Duration;
+
+use super::{get_valid_plays, Engine, Position};
+use crate::board::{get_next_player_num, get_opponent_num, is_winning_move};
+
+// the engine hints come from, and how long it thinks about each
+// (falling back to the default engine if it's missing)
+pub const HINT_ENGINE: &str = "minimax";
+pub const HINT_BUDGET: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reason {
+    // the play connects four
+    Win,
+    // the play stops the opponent from connecting four on their next move
+    Block,
+    // the play sets up four to connect on the player's next move
+    Threat,
+    // the play builds towards lines of four
+    Build,
+}
+
+impl Reason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Win => "win",
+            Self::Block => "block",
+            Self::Threat => "threat",
+            Self::Build => "build",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hint {
+    pub play: (usize, usize),
+    pub reason: Reason,
+}
+
+pub fn suggest(engine: &dyn Engine, board: &[Vec<u8>], seed: u64) -> Option<Hint> {
+    let play = engine.choose_play(&Position::new(board.to_vec()), HINT_BUDGET, seed)?;
+    Some(Hint {
+        play,
+        reason: explain(board, play),
+    })
+}
+
+pub fn explain(board: &[Vec<u8>], (row, col): (usize, usize)) -> Reason {
+    let player_num = get_next_player_num(board);
+    let mut board = board.to_vec();
+    let winning_plays = count_winning_plays(&mut board, player_num);
+
+    board[row][col] = get_opponent_num(player_num);
+    let is_block = is_winning_move(row, col, &board);
+
+    board[row][col] = player_num;
+    if is_winning_move(row, col, &board) {
+        return Reason::Win;
+    }
+    if is_block {
+        return Reason::Block;
+    }
+
+    // whether the play gives the player a new way to connect four next time
+    match count_winning_plays(&mut board, player_num) > winning_plays {
+        true => Reason::Threat,
+        _ => Reason::Build,
+    }
+}
+
+fn count_winning_plays(board: &mut [Vec<u8>], player_num: u8) -> usize {
+    // the plays that would connect four for the player, if it were their turn
+    get_valid_plays(board)
+        .into_iter()
+        .filter(|&(row, col)| {
+            board[row][col] = player_num;
+            let is_winning = is_winning_move(row, col, board);
+            board[row][col] = 0;
+            is_winning
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn plays_are_explained() {
+        // black to move, with three on the top row and white three down the right edge
+        let board = vec![
+            vec![1, 1, 1, 0, 0, 0, 2],
+            vec![0, 0, 0, 0, 0, 0, 2],
+            vec![0, 0, 0, 0, 0, 0, 2],
+            vec![0, 0, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0, 0],
+        ];
+        assert_eq!(explain(&board, (0, 3)), Reason::Win);
+        assert_eq!(explain(&board, (3, 6)), Reason::Block);
+
+        // black to move, with two on the top row
+        let board = vec![
+            vec![1, 1, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0, 2],
+            vec![0, 0, 0, 0, 0, 0, 2],
+        ];
+        assert_eq!(explain(&board, (0, 2)), Reason::Threat);
+        assert_eq!(explain(&board, (3, 0)), Reason::Build);
+    }
+}
@@ -0,0 +1,235 @@
+// monte carlo tree search
+//
+// grows a tree of plays from the current position (UCT),
+// judging each play by how many random games played out from it were won,
+// and spending more of its budget on the plays that look best
+// (given the same seed, it always picks the same play, unless time runs out first)
+
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use super::{get_valid_plays, Engine, Position};
+use crate::board::{get_next_player_num, get_opponent_num, is_board_full, is_winning_move};
+
+// how much to favour plays that haven't been tried much
+const EXPLORATION: f64 = std::f64::consts::SQRT_2;
+
+pub struct Mcts {
+    // how many playouts to run for each play, at most
+    pub iterations: u32,
+}
+
+impl Default for Mcts {
+    fn default() -> Self {
+        Self { iterations: 3000 }
+    }
+}
+
+impl Engine for Mcts {
+    fn name(&self) -> &str {
+        "mcts"
+    }
+
+    fn description(&self) -> &str {
+        "By tree search"
+    }
+
+    fn choose_play(
+        &self,
+        position: &Position,
+        budget: Duration,
+        seed: u64,
+    ) -> Option<(usize, usize)> {
+        search(&position.board, self.iterations, budget, seed)
+    }
+}
+
+struct Node {
+    // the play that led here, and who made it
+    play: Option<(usize, usize)>,
+    player_num: u8,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Vec<(usize, usize)>,
+    // Some(x) if the game is over here -- x won, or 0 for a draw
+    result: Option<u8>,
+    visits: u32,
+    // playouts won by player_num (draws count as half a win)
+    wins: f64,
+}
+
+impl Node {
+    fn new(
+        play: Option<(usize, usize)>,
+        player_num: u8,
+        parent: Option<usize>,
+        board: &[Vec<u8>],
+    ) -> Self {
+        let result = match play {
+            Some((row, col)) if is_winning_move(row, col, board) => Some(player_num),
+            _ if is_board_full(board) => Some(0),
+            _ => None,
+        };
+
+        Self {
+            play,
+            player_num,
+            parent,
+            children: Vec::new(),
+            untried: match result {
+                Some(_) => Vec::new(),
+                None => get_valid_plays(board),
+            },
+            result,
+            visits: 0,
+            wins: 0.0,
+        }
+    }
+
+    fn score(&self, parent_visits: u32) -> f64 {
+        self.wins / self.visits as f64
+            + EXPLORATION * ((parent_visits as f64).ln() / self.visits as f64).sqrt()
+    }
+}
+
+fn search(
+    board: &[Vec<u8>],
+    iterations: u32,
+    budget: Duration,
+    seed: u64,
+) -> Option<(usize, usize)> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let started_at = Instant::now();
+
+    // the root belongs to whoever moved last
+    let to_move_num = get_next_player_num(board);
+    let mut nodes = vec![Node::new(None, get_opponent_num(to_move_num), None, board)];
+
+    for _ in 0..iterations {
+        if started_at.elapsed() >= budget {
+            break;
+        }
+
+        let mut board = board.to_vec();
+
+        // selection -- follow the best scoring plays down to a node with plays left to try
+        let mut id = 0;
+        while nodes[id].untried.is_empty() && !nodes[id].children.is_empty() {
+            let parent_visits = nodes[id].visits;
+            id = *nodes[id]
+                .children
+                .iter()
+                .max_by(|&&a, &&b| {
+                    nodes[a]
+                        .score(parent_visits)
+                        .total_cmp(&nodes[b].score(parent_visits))
+                })
+                .unwrap();
+            let (row, col) = nodes[id].play.unwrap();
+            board[row][col] = nodes[id].player_num;
+        }
+
+        // expansion -- try one of those plays
+        if !nodes[id].untried.is_empty() {
+            let i = rng.gen_range(0..nodes[id].untried.len());
+            let (row, col) = nodes[id].untried.swap_remove(i);
+            let player_num = get_opponent_num(nodes[id].player_num);
+            board[row][col] = player_num;
+
+            nodes.push(Node::new(Some((row, col)), player_num, Some(id), &board));
+            let child_id = nodes.len() - 1;
+            nodes[id].children.push(child_id);
+            id = child_id;
+        }
+
+        // simulation -- play the rest of the game out at random
+        let winner_num = match nodes[id].result {
+            Some(winner_num) => winner_num,
+            None => play_out(&mut board, get_opponent_num(nodes[id].player_num), &mut rng),
+        };
+
+        // backpropagation
+        let mut current = Some(id);
+        while let Some(id) = current {
+            let node = &mut nodes[id];
+            node.visits += 1;
+            node.wins += match winner_num {
+                0 => 0.5,
+                _ if winner_num == node.player_num => 1.0,
+                _ => 0.0,
+            };
+            current = node.parent;
+        }
+    }
+
+    // the most visited play is the one the search trusts most
+    let root = &nodes[0];
+    root.children
+        .iter()
+        .max_by_key(|&&id| nodes[id].visits)
+        .and_then(|&id| nodes[id].play)
+        .or_else(|| root.untried.first().copied())
+}
+
+fn play_out(board: &mut [Vec<u8>], mut player_num: u8, rng: &mut StdRng) -> u8 {
+    // returns the winner, or 0 for a draw
+    loop {
+        let (row, col) = match get_valid_plays(board).choose(rng) {
+            Some(&play) => play,
+            None => return 0,
+        };
+
+        board[row][col] = player_num;
+        if is_winning_move(row, col, board) {
+            return player_num;
+        }
+        player_num = get_opponent_num(player_num);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::board::is_valid_move;
+
+    fn get_board() -> Vec<Vec<u8>> {
+        // black to move, and can win by stacking a fourth ball on the left of row 3
+        vec![
+            vec![0, 0, 0, 0, 0, 0, 2],
+            vec![0, 0, 0, 0, 0, 0, 2],
+            vec![1, 0, 0, 0, 0, 0, 2],
+            vec![0, 0, 0, 0, 0, 0, 0],
+            vec![1, 0, 0, 0, 0, 0, 0],
+            vec![1, 0, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0, 0],
+        ]
+    }
+
+    #[test]
+    fn the_same_seed_picks_the_same_play() {
+        let board = vec![vec![0; 7]; 7];
+        let budget = Duration::from_secs(60);
+
+        let play = search(&board, 500, budget, 42);
+        assert_eq!(play, search(&board, 500, budget, 42));
+        assert!(play.is_some_and(|(row, col)| is_valid_move(row, col, &board)));
+    }
+
+    #[test]
+    fn winning_plays_are_found() {
+        let engine = Mcts::default();
+        assert_eq!(
+            engine.choose_play(&Position::new(get_board()), Duration::from_secs(60), 7),
+            Some((3, 0))
+        );
+    }
+
+    #[test]
+    fn full_boards_have_nothing_to_play() {
+        let board = vec![vec![1, 2], vec![2, 1]];
+        assert_eq!(search(&board, 100, Duration::from_secs(60), 7), None);
+    }
+}
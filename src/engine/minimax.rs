@@ -0,0 +1,272 @@
+// minimax search, with alpha-beta pruning
+//
+// looks ahead a few plies (more while time allows), assuming both sides play their best,
+// and scores the positions it stops at by the lines of four each player could still make
+// (equally good plays are told apart by the seed)
+
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use super::{get_valid_plays, Engine, Position};
+use crate::board::{get_next_player_num, get_opponent_num, is_winning_move};
+
+// more than any position could score
+// (wins found sooner score a little higher)
+pub const WIN_SCORE: i32 = 1_000_000;
+
+// what a line of four with 0, 1, 2 or 3 of a player's balls (and none of the opponent's)
+// is worth to them
+const LINE_SCORES: [i32; 4] = [0, 1, 8, 64];
+
+pub struct Minimax {
+    pub max_depth: u32,
+}
+
+impl Default for Minimax {
+    fn default() -> Self {
+        Self { max_depth: 6 }
+    }
+}
+
+impl Engine for Minimax {
+    fn name(&self) -> &str {
+        "minimax"
+    }
+
+    fn description(&self) -> &str {
+        "By looking ahead"
+    }
+
+    fn choose_play(
+        &self,
+        position: &Position,
+        budget: Duration,
+        seed: u64,
+    ) -> Option<(usize, usize)> {
+        let mut plays = get_valid_plays(&position.board);
+        plays.shuffle(&mut StdRng::seed_from_u64(seed));
+        let mut best_play = *plays.first()?;
+
+        let player_num = get_next_player_num(&position.board);
+        let deadline = Instant::now() + budget;
+        let mut board = position.board.clone();
+
+        // keep the best play from the deepest search that finished in time,
+        // and try it first on the next one down (so more of the rest gets pruned)
+        for depth in 1..=self.max_depth {
+            let (play, score) = match search_plays(&mut board, &plays, player_num, depth, deadline)
+            {
+                Some(found) => found,
+                None => break,
+            };
+
+            best_play = play;
+            if score >= WIN_SCORE {
+                break;
+            }
+
+            let i = plays.iter().position(|&other| other == play).unwrap();
+            plays[..=i].rotate_right(1);
+        }
+
+        Some(best_play)
+    }
+}
+
+pub fn score_plays(board: &[Vec<u8>], depth: u32) -> Vec<((usize, usize), i32)> {
+    // every play's score for the player to move, looking depth plies ahead
+    // (with nothing cut short, so plays other than the best one get their true scores too)
+    let player_num = get_next_player_num(board);
+    let deadline = Instant::now() + Duration::from_secs(24 * 60 * 60);
+    let mut board = board.to_vec();
+
+    get_valid_plays(&board)
+        .into_iter()
+        .filter_map(|play| {
+            let score = score_play(
+                &mut board,
+                play,
+                player_num,
+                depth.max(1),
+                -WIN_SCORE * 2,
+                WIN_SCORE * 2,
+                deadline,
+            )?;
+            Some((play, score))
+        })
+        .collect()
+}
+
+fn search_plays(
+    board: &mut [Vec<u8>],
+    plays: &[(usize, usize)],
+    player_num: u8,
+    depth: u32,
+    deadline: Instant,
+) -> Option<((usize, usize), i32)> {
+    // the best of the plays, and its score
+    // (None if time ran out before the search was done)
+    let mut best = (plays[0], -WIN_SCORE * 2);
+    let mut alpha = -WIN_SCORE * 2;
+
+    for &play in plays {
+        let score = score_play(
+            board,
+            play,
+            player_num,
+            depth,
+            alpha,
+            WIN_SCORE * 2,
+            deadline,
+        )?;
+        if score > best.1 {
+            best = (play, score);
+        }
+        alpha = alpha.max(score);
+    }
+
+    Some(best)
+}
+
+fn score_play(
+    board: &mut [Vec<u8>],
+    (row, col): (usize, usize),
+    player_num: u8,
+    depth: u32,
+    alpha: i32,
+    beta: i32,
+    deadline: Instant,
+) -> Option<i32> {
+    board[row][col] = player_num;
+    let score = match is_winning_move(row, col, board) {
+        true => Some(WIN_SCORE + depth as i32),
+        _ => negamax(
+            board,
+            get_opponent_num(player_num),
+            depth - 1,
+            -beta,
+            -alpha,
+            deadline,
+        )
+        .map(|score| -score),
+    };
+    board[row][col] = 0;
+
+    score
+}
+
+fn negamax(
+    board: &mut [Vec<u8>],
+    player_num: u8,
+    depth: u32,
+    mut alpha: i32,
+    beta: i32,
+    deadline: Instant,
+) -> Option<i32> {
+    // scores the position for player_num, who is to move
+    if Instant::now() >= deadline {
+        return None;
+    }
+
+    let plays = get_valid_plays(board);
+    if plays.is_empty() {
+        return Some(0);
+    }
+    if depth == 0 {
+        return Some(evaluate(board, player_num));
+    }
+
+    let mut best = -WIN_SCORE * 2;
+    for play in plays {
+        let score = score_play(board, play, player_num, depth, alpha, beta, deadline)?;
+        best = best.max(score);
+        alpha = alpha.max(score);
+        if alpha >= beta {
+            break;
+        }
+    }
+
+    Some(best)
+}
+
+fn evaluate(board: &[Vec<u8>], player_num: u8) -> i32 {
+    // sums up every line of four either player could still make
+    let opponent_num = get_opponent_num(player_num);
+    let (height, width) = (board.len() as isize, board[0].len() as isize);
+    let mut score = 0;
+
+    for row in 0..height {
+        for col in 0..width {
+            for (d_row, d_col) in [(0, 1), (1, 0), (1, 1), (1, -1)] {
+                let (end_row, end_col) = (row + 3 * d_row, col + 3 * d_col);
+                if end_row >= height || end_col < 0 || end_col >= width {
+                    continue;
+                }
+
+                let (mut own, mut other) = (0, 0);
+                for k in 0..4 {
+                    let cell = board[(row + k * d_row) as usize][(col + k * d_col) as usize];
+                    if cell == player_num {
+                        own += 1;
+                    } else if cell == opponent_num {
+                        other += 1;
+                    }
+                }
+
+                match (own, other) {
+                    (own, 0) if own < 4 => score += LINE_SCORES[own],
+                    (0, other) if other < 4 => score -= LINE_SCORES[other],
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    score
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn winning_plays_are_taken() {
+        // black stacks a fourth ball on the left of row 3
+        let board = vec![
+            vec![0, 0, 0, 0, 0, 0, 2],
+            vec![0, 0, 0, 0, 0, 0, 2],
+            vec![1, 0, 0, 0, 0, 0, 2],
+            vec![0, 0, 0, 0, 0, 0, 0],
+            vec![1, 0, 0, 0, 0, 0, 0],
+            vec![1, 0, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0, 0],
+        ];
+        let engine = Minimax { max_depth: 4 };
+        assert_eq!(
+            engine.choose_play(&Position::new(board), Duration::from_secs(60), 7),
+            Some((3, 0))
+        );
+    }
+
+    #[test]
+    fn losing_threats_are_blocked() {
+        // white must stop black from completing the top row
+        let board = vec![
+            vec![1, 1, 1, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0, 2],
+            vec![0, 0, 0, 0, 0, 0, 2],
+            vec![0, 0, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0, 0],
+        ];
+        let engine = Minimax { max_depth: 2 };
+        assert_eq!(
+            engine.choose_play(&Position::new(board), Duration::from_secs(60), 7),
+            Some((0, 3))
+        );
+    }
+}
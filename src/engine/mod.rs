@@ -0,0 +1,264 @@
+// the engines the computer can play with
+//
+// an engine picks a play for whoever is to move, given the position,
+// how long it may think, and a seed for any choices it makes at random
+// (so the same position, budget and seed get the same play)
+// engines are registered by name, and each game against the computer keeps the name of its own
+
+pub mod analysis;
+pub mod external;
+pub mod hint;
+pub mod mcts;
+pub mod minimax;
+pub mod naive;
+pub mod perfect;
+pub mod random;
+pub mod solver;
+pub mod tablebase;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::Rng;
+use serde::Serialize;
+
+use crate::board::{get_next_player_num, is_valid_move};
+
+// how long the computer may think about each play
+// (unless the game gives its engines longer, see Model::engine_move_time)
+pub const TIME_BUDGET: Duration = Duration::from_secs(2);
+// in timed games, it thinks for at most this share of its time left
+const TIME_LEFT_SHARE: u32 = 20;
+
+const MAX_THINK_DELAY: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Position {
+    pub board: Vec<Vec<u8>>,
+    // milliseconds left on each player's clock, in timed games
+    pub time_left_ms: Option<[u64; 2]>,
+}
+
+impl Position {
+    pub fn new(board: Vec<Vec<u8>>) -> Self {
+        Self {
+            board,
+            time_left_ms: None,
+        }
+    }
+
+    pub fn get_budget(&self, max_budget: Duration) -> Duration {
+        // how long the player to move may think, up to max_budget
+        // (never long enough to run out of time)
+        match self.time_left_ms {
+            Some(time_left_ms) => {
+                let i = get_next_player_num(&self.board) as usize - 1;
+                max_budget.min(Duration::from_millis(time_left_ms[i]) / TIME_LEFT_SHARE)
+            }
+            None => max_budget,
+        }
+    }
+}
+
+// how long the computer takes to play, however quick its engine is
+// so its opponent has time to breathe between moves
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ThinkDelay {
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl Default for ThinkDelay {
+    fn default() -> Self {
+        Self {
+            min: Duration::from_millis(500),
+            max: Duration::from_millis(2000),
+        }
+    }
+}
+
+impl ThinkDelay {
+    pub fn parse(text: &str) -> Option<Self> {
+        // in the form -- min-max, in milliseconds (e.g. 500-2000, or 0-0 for none)
+        let (min, max) = text.trim().split_once('-')?;
+        let min = Duration::from_millis(min.parse().ok()?);
+        let max = Duration::from_millis(max.parse().ok()?);
+
+        match min <= max && max <= MAX_THINK_DELAY {
+            true => Some(Self { min, max }),
+            _ => None,
+        }
+    }
+
+    pub fn pick(&self, board: &[Vec<u8>], rng: &mut impl Rng) -> Duration {
+        // positions with more plays to choose from take longer,
+        // give or take a quarter
+        let most_plays = board.len() * 2;
+        let complexity = get_valid_plays(board).len().min(most_plays) as f64 / most_plays as f64;
+        let span = (self.max - self.min).as_secs_f64();
+        let delay = self.min.as_secs_f64() + span * complexity * rng.gen_range(0.75..=1.25);
+
+        Duration::from_secs_f64(delay).clamp(self.min, self.max)
+    }
+}
+
+// an engine, and how the computer playing with it behaves
+#[derive(Clone)]
+pub struct Computer {
+    pub engine: Arc<dyn Engine>,
+    pub think_delay: ThinkDelay,
+}
+
+pub trait Engine: Send + Sync {
+    fn name(&self) -> &str;
+
+    // shown to players choosing an opponent
+    fn description(&self) -> &str;
+
+    // returns None if there's nothing left to play
+    fn choose_play(
+        &self,
+        position: &Position,
+        budget: Duration,
+        seed: u64,
+    ) -> Option<(usize, usize)>;
+}
+
+#[derive(Debug, Serialize)]
+pub struct EngineChoice<'a> {
+    pub name: &'a str,
+    pub description: &'a str,
+}
+
+pub struct Engines {
+    // the first is the default
+    engines: Vec<Arc<dyn Engine>>,
+    think_delay: ThinkDelay,
+}
+
+impl Default for Engines {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engines {
+    pub fn new() -> Self {
+        Self {
+            engines: vec![
+                Arc::new(naive::Naive),
+                Arc::new(random::Random),
+                Arc::new(minimax::Minimax::default()),
+                Arc::new(mcts::Mcts::default()),
+            ],
+            think_delay: ThinkDelay::default(),
+        }
+    }
+
+    pub fn register(&mut self, engine: Arc<dyn Engine>) {
+        // built-in engines keep their names
+        match self.find(engine.name()) {
+            Some(_) => tracing::warn!("engine name already taken: {}", engine.name()),
+            None => self.engines.push(engine),
+        }
+    }
+
+    pub fn find(&self, name: &str) -> Option<Arc<dyn Engine>> {
+        self.engines
+            .iter()
+            .find(|engine| engine.name() == name)
+            .cloned()
+    }
+
+    pub fn find_or_default(&self, name: Option<&str>) -> Arc<dyn Engine> {
+        // games from before engines could be chosen (or whose engine is gone)
+        // get the default one
+        name.and_then(|name| self.find(name))
+            .unwrap_or_else(|| self.engines[0].clone())
+    }
+
+    pub fn set_think_delay(&mut self, think_delay: ThinkDelay) {
+        self.think_delay = think_delay;
+    }
+
+    pub fn find_computer(&self, name: Option<&str>) -> Computer {
+        Computer {
+            engine: self.find_or_default(name),
+            think_delay: self.think_delay,
+        }
+    }
+
+    pub fn choices(&self) -> Vec<EngineChoice<'_>> {
+        self.engines
+            .iter()
+            .map(|engine| EngineChoice {
+                name: engine.name(),
+                description: engine.description(),
+            })
+            .collect()
+    }
+}
+
+pub fn get_seed(game_id: uuid::Uuid, plies: usize) -> u64 {
+    // the same position in the same game gets the same seed
+    game_id.as_u128() as u64 ^ plies as u64
+}
+
+pub fn get_valid_plays(board: &[Vec<u8>]) -> Vec<(usize, usize)> {
+    // (a row with free cells always has one that can be played)
+    (0..board.len())
+        .flat_map(|row| (0..board[row].len()).map(move |col| (row, col)))
+        .filter(|&(row, col)| is_valid_move(row, col, board))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unknown_engines_fall_back_to_the_default() {
+        let engines = Engines::new();
+
+        assert_eq!(engines.find_or_default(Some("mcts")).name(), "mcts");
+        assert_eq!(engines.find_or_default(Some("nope")).name(), "naive");
+        assert_eq!(engines.find_or_default(None).name(), "naive");
+        assert!(engines.find("nope").is_none());
+    }
+
+    #[test]
+    fn think_delays_stay_in_bounds() {
+        let think_delay = ThinkDelay::parse("200-1000").unwrap();
+        let mut rng = rand::thread_rng();
+
+        // every row can be played from both ends
+        let open_board = vec![vec![0; 7]; 7];
+        // only one play left
+        let mut closed_board = vec![vec![1; 7]; 7];
+        closed_board[3][3] = 0;
+
+        for _ in 0..100 {
+            let delay = think_delay.pick(&open_board, &mut rng);
+            assert!(delay >= Duration::from_millis(700) && delay <= think_delay.max);
+            let delay = think_delay.pick(&closed_board, &mut rng);
+            assert!(delay >= think_delay.min && delay < Duration::from_millis(400));
+        }
+
+        assert!(ThinkDelay::parse("1000-200").is_none());
+        assert!(ThinkDelay::parse("0-60000").is_none());
+        assert_eq!(ThinkDelay::parse("0-0").unwrap().max, Duration::ZERO);
+    }
+
+    #[test]
+    fn timed_engines_keep_time_in_hand() {
+        let mut position = Position::new(vec![vec![0; 7]; 7]);
+        assert_eq!(position.get_budget(TIME_BUDGET), TIME_BUDGET);
+
+        position.time_left_ms = Some([10_000, 600_000]);
+        assert_eq!(position.get_budget(TIME_BUDGET), Duration::from_millis(500));
+        assert_eq!(
+            position.get_budget(Duration::from_millis(200)),
+            Duration::from_millis(200)
+        );
+    }
+}
@@ -0,0 +1,44 @@
+// the computer's original engine
+// it fills the rightmost column from the top, then takes the first cell it can
+
+use std::time::Duration;
+
+use super::{Engine, Position};
+use crate::board::is_valid_move;
+
+pub struct Naive;
+
+impl Engine for Naive {
+    fn name(&self) -> &str {
+        "naive"
+    }
+
+    fn description(&self) -> &str {
+        "Naively"
+    }
+
+    fn choose_play(
+        &self,
+        position: &Position,
+        _budget: Duration,
+        _seed: u64,
+    ) -> Option<(usize, usize)> {
+        let board = &position.board;
+        for i in 0..board.len() {
+            if board[i][board[i].len() - 1] == 0 {
+                return Some((i, board[i].len() - 1));
+            }
+        }
+
+        // rightmost column is full, so take the first cell that can be played
+        for i in 0..board.len() {
+            for j in 0..board[i].len() {
+                if is_valid_move(i, j, board) {
+                    return Some((i, j));
+                }
+            }
+        }
+
+        None
+    }
+}
@@ -0,0 +1,130 @@
+// perfect play, on boards small enough to solve
+//
+// the solver finds every play's value, starting from the tablebase for the board,
+// and the engine makes the best one -- winning as soon as it can, or losing as late as it can
+// (equally good plays are told apart by the seed)
+// it only plays perfectly on boards it has a tablebase for, and looks ahead like minimax elsewhere
+// (or when the solver can't finish within half of the budget, leaving minimax the other half)
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use super::minimax::Minimax;
+use super::solver::{Geometry, Solver, Stones};
+use super::tablebase::Tablebases;
+use super::{Engine, Position};
+
+// the solver's transposition table has 2^20 slots (16MB) while it plays
+pub const TABLE_BITS: u32 = 20;
+
+pub struct Perfect {
+    tablebases: Arc<Tablebases>,
+    fallback: Minimax,
+}
+
+impl Perfect {
+    pub fn new(tablebases: Arc<Tablebases>) -> Self {
+        Self {
+            tablebases,
+            fallback: Minimax::default(),
+        }
+    }
+}
+
+impl Engine for Perfect {
+    fn name(&self) -> &str {
+        "perfect"
+    }
+
+    fn description(&self) -> &str {
+        "Perfectly (on solved boards)"
+    }
+
+    fn choose_play(
+        &self,
+        position: &Position,
+        budget: Duration,
+        seed: u64,
+    ) -> Option<(usize, usize)> {
+        let board = &position.board;
+        let (rows, cols) = (board.len(), board.first().map_or(0, Vec::len));
+        let tablebase = match self.tablebases.find(rows, cols) {
+            Some(tablebase) => tablebase,
+            None => return self.fallback.choose_play(position, budget, seed),
+        };
+
+        let started_at = Instant::now();
+        let mut solver = Solver::new(Geometry::new(rows, cols)?, TABLE_BITS)
+            .with_tablebase(tablebase)
+            .with_deadline(started_at + budget / 2);
+        let mut plays = match solver.solve_plays(&Stones::from_board(board)) {
+            Some(plays) => plays,
+            None => {
+                let budget = budget.saturating_sub(started_at.elapsed());
+                return self.fallback.choose_play(position, budget, seed);
+            }
+        };
+        plays.shuffle(&mut StdRng::seed_from_u64(seed));
+
+        plays
+            .into_iter()
+            .max_by_key(|&(_, value)| value)
+            .map(|(cell, _)| (cell / cols, cell % cols))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::tablebase::Tablebase;
+    use crate::engine::TIME_BUDGET;
+
+    #[test]
+    fn wins_are_taken_and_threats_blocked() {
+        // a tablebase with nothing in it still has the solver take over on its board
+        let tablebases = Tablebases::from(vec![Tablebase::new(4, 4, 0, vec![])]);
+        let engine = Perfect::new(Arc::new(tablebases));
+
+        // black can win on the top row straight away
+        let board = vec![
+            vec![1, 1, 1, 0],
+            vec![2, 0, 0, 0],
+            vec![2, 0, 0, 0],
+            vec![0, 0, 0, 2],
+        ];
+        assert_eq!(
+            engine.choose_play(&Position::new(board), TIME_BUDGET, 7),
+            Some((0, 3))
+        );
+
+        // white has to stop it
+        let board = vec![
+            vec![1, 1, 1, 0],
+            vec![2, 0, 0, 0],
+            vec![2, 0, 0, 0],
+            vec![2, 0, 0, 1],
+        ];
+        assert_eq!(
+            engine.choose_play(&Position::new(board), TIME_BUDGET, 7),
+            Some((0, 3))
+        );
+    }
+
+    #[test]
+    fn looks_ahead_when_out_of_time() {
+        // the empty 5x5 board takes seconds to solve, so the solver gives up straight away
+        let tablebases = Tablebases::from(vec![Tablebase::new(5, 5, 0, vec![])]);
+        let engine = Perfect::new(Arc::new(tablebases));
+
+        let board = vec![vec![0; 5]; 5];
+        let play = engine.choose_play(&Position::new(board.clone()), Duration::ZERO, 7);
+        assert_eq!(
+            play,
+            Minimax::default().choose_play(&Position::new(board), Duration::ZERO, 7)
+        );
+    }
+}
@@ -0,0 +1,31 @@
+// plays anything it can, at random
+
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use super::{get_valid_plays, Engine, Position};
+
+pub struct Random;
+
+impl Engine for Random {
+    fn name(&self) -> &str {
+        "random"
+    }
+
+    fn description(&self) -> &str {
+        "At random"
+    }
+
+    fn choose_play(
+        &self,
+        position: &Position,
+        _budget: Duration,
+        seed: u64,
+    ) -> Option<(usize, usize)> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        get_valid_plays(&position.board).choose(&mut rng).copied()
+    }
+}
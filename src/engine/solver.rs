@@ -0,0 +1,552 @@
+// a solver for small boards
+//
+// searches right to the end of the game, to find who wins with perfect play and how soon
+// positions are kept as bitboards (a bit per cell for each player), and a position shares its key
+// with its mirror images -- left to right, top to bottom, or both -- so the search meets each once
+// what the search finds goes in a transposition table, and it takes the values in a tablebase
+// as given, where it has one for the board
+// a search can be given a deadline, and gives up on the position if it isn't done by then
+
+use std::time::Instant;
+
+use super::tablebase::Tablebase;
+
+// keys count in base 3, a digit per cell, so they fit in a u64 up to this many cells
+pub const MAX_CELLS: usize = 40;
+
+// the value of a position for the player to move:
+// positive if they win, negative if they lose, and 0 for a draw
+// the further from 0, the sooner the game ends (a win on the board's last cell is worth 1)
+pub type Value = i8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    // how many plies until the game ends, counting the last one
+    Win(usize),
+    Loss(usize),
+    Draw,
+}
+
+// the cells of a board, numbered row by row, and what can be made of them
+pub struct Geometry {
+    pub rows: usize,
+    pub cols: usize,
+    // every line of four, as a mask of its cells
+    lines: Vec<u64>,
+    // the lines through each cell
+    cell_lines: Vec<Vec<u64>>,
+    // the cells beside each cell, in its row
+    neighbours: Vec<u64>,
+    // where each cell ends up in the board's mirror images
+    mirrors: [Vec<usize>; 3],
+    // 3 to the power of each cell
+    powers: Vec<u64>,
+}
+
+impl Geometry {
+    pub fn new(rows: usize, cols: usize) -> Option<Self> {
+        if rows == 0 || cols == 0 || rows * cols > MAX_CELLS {
+            return None;
+        }
+
+        let cell = |row: usize, col: usize| row * cols + col;
+        let mut lines = vec![];
+        for row in 0..rows as isize {
+            for col in 0..cols as isize {
+                for (d_row, d_col) in [(0, 1), (1, 0), (1, 1), (1, -1)] {
+                    let (end_row, end_col) = (row + 3 * d_row, col + 3 * d_col);
+                    if end_row >= rows as isize || end_col < 0 || end_col >= cols as isize {
+                        continue;
+                    }
+
+                    lines.push((0..4).fold(0, |line, k| {
+                        line | 1 << cell((row + k * d_row) as usize, (col + k * d_col) as usize)
+                    }));
+                }
+            }
+        }
+        let cell_lines = (0..rows * cols)
+            .map(|i| {
+                lines
+                    .iter()
+                    .copied()
+                    .filter(|&line| line & 1 << i != 0)
+                    .collect()
+            })
+            .collect();
+        let neighbours = (0..rows * cols)
+            .map(|i| {
+                let (row, col) = (i / cols, i % cols);
+                let left = if col > 0 { 1 << cell(row, col - 1) } else { 0 };
+                let right = if col + 1 < cols {
+                    1 << cell(row, col + 1)
+                } else {
+                    0
+                };
+                left | right
+            })
+            .collect();
+
+        let mirror = |flip_rows: bool, flip_cols: bool| {
+            (0..rows * cols)
+                .map(|i| {
+                    let (row, col) = (i / cols, i % cols);
+                    let row = if flip_rows { rows - 1 - row } else { row };
+                    let col = if flip_cols { cols - 1 - col } else { col };
+                    cell(row, col)
+                })
+                .collect()
+        };
+
+        Some(Self {
+            rows,
+            cols,
+            lines,
+            cell_lines,
+            neighbours,
+            mirrors: [mirror(false, true), mirror(true, false), mirror(true, true)],
+            powers: (0..rows * cols).map(|i| 3u64.pow(i as u32)).collect(),
+        })
+    }
+
+    pub fn cells(&self) -> usize {
+        self.rows * self.cols
+    }
+
+    pub fn outcome(&self, value: Value, plies: usize) -> Outcome {
+        // a value of v means the game ends with cells + 1 - v balls on the board
+        let plies_left = |value: Value| self.cells() + 1 - value.unsigned_abs() as usize - plies;
+        match value {
+            0 => Outcome::Draw,
+            1.. => Outcome::Win(plies_left(value)),
+            _ => Outcome::Loss(plies_left(value)),
+        }
+    }
+
+    pub fn plays(&self, stones: &Stones) -> Vec<usize> {
+        cells_of(self.playable(stones.taken())).collect()
+    }
+
+    pub fn is_winning_play(&self, stones: &Stones, cell: usize) -> bool {
+        let own = stones.balls[stones.plies % 2] | 1 << cell;
+        self.cell_lines[cell].iter().any(|&line| line & !own == 0)
+    }
+
+    pub fn key(&self, stones: &Stones) -> u64 {
+        // the smallest of the position's keys and its mirror images' keys
+        let mut keys = [0; 4];
+        for (digit, balls) in [(1, stones.balls[0]), (2, stones.balls[1])] {
+            for i in cells_of(balls) {
+                keys[0] += digit * self.powers[i];
+                for (key, mirror) in keys[1..].iter_mut().zip(&self.mirrors) {
+                    *key += digit * self.powers[mirror[i]];
+                }
+            }
+        }
+
+        keys.into_iter().min().unwrap()
+    }
+
+    fn playable(&self, taken: u64) -> u64 {
+        // the free cell on either end of each row's free stretch
+        let row_mask = (1 << self.cols) - 1;
+        let mut playable = 0;
+
+        for row in 0..self.rows {
+            let row_taken = taken >> (row * self.cols) & row_mask;
+            let left = row_taken.trailing_ones() as usize;
+            if left >= self.cols {
+                continue;
+            }
+            let right = self.cols - 1 - (row_taken << (64 - self.cols)).leading_ones() as usize;
+            playable |= (1 << left | 1 << right) << (row * self.cols);
+        }
+
+        playable
+    }
+
+    fn winning_cells(&self, own: u64, taken: u64) -> u64 {
+        // the free cells that would make four in a line with the balls in own
+        // (whether or not they can be played yet)
+        self.lines
+            .iter()
+            .filter(|&&line| (own & line).count_ones() == 3 && taken & line & !own == 0)
+            .fold(0, |cells, &line| cells | line & !own)
+    }
+
+    fn rank_play(&self, own: u64, other: u64, cell: usize) -> u32 {
+        // plays that set up more ways to win are tried first,
+        // then the ones building on more lines the opponent hasn't blocked
+        let own = own | 1 << cell;
+        let threats = (self.winning_cells(own, own | other) & !other).count_ones();
+        let lines = self.cell_lines[cell]
+            .iter()
+            .filter(|&&line| other & line == 0)
+            .map(|&line| 1 << (own & line).count_ones())
+            .sum::<u32>();
+        threats << 16 | lines
+    }
+}
+
+fn cells_of(mask: u64) -> impl Iterator<Item = usize> {
+    let mut mask = mask;
+    std::iter::from_fn(move || match mask {
+        0 => None,
+        _ => {
+            let i = mask.trailing_zeros() as usize;
+            mask &= mask - 1;
+            Some(i)
+        }
+    })
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stones {
+    // a bit per cell, for player 1's balls and player 2's
+    pub balls: [u64; 2],
+    pub plies: usize,
+}
+
+impl Stones {
+    pub fn from_board(board: &[Vec<u8>]) -> Self {
+        let mut stones = Self::default();
+        for (i, &cell) in board.iter().flatten().enumerate() {
+            if cell == 1 || cell == 2 {
+                stones.balls[cell as usize - 1] |= 1 << i;
+                stones.plies += 1;
+            }
+        }
+        stones
+    }
+
+    pub fn taken(&self) -> u64 {
+        self.balls[0] | self.balls[1]
+    }
+
+    pub fn play(&self, cell: usize) -> Self {
+        let mut stones = *self;
+        stones.balls[self.plies % 2] |= 1 << cell;
+        stones.plies += 1;
+        stones
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    key: u64,
+    lower: Value,
+    upper: Value,
+}
+
+// no position has this key (3 to the 40 is less)
+const NO_KEY: u64 = u64::MAX;
+// the clock is only checked every so many positions
+const DEADLINE_CHECK_NODES: u64 = 1024;
+
+pub struct Solver<'a> {
+    pub geometry: Geometry,
+    tablebase: Option<&'a Tablebase>,
+    // each position goes in one slot, taking it over from whatever was there
+    table: Vec<Entry>,
+    // how many positions have been searched
+    pub nodes: u64,
+    deadline: Option<Instant>,
+    is_out_of_time: bool,
+}
+
+impl<'a> Solver<'a> {
+    pub fn new(geometry: Geometry, table_bits: u32) -> Self {
+        let empty = Entry {
+            key: NO_KEY,
+            lower: Value::MIN,
+            upper: Value::MAX,
+        };
+        Self {
+            geometry,
+            tablebase: None,
+            table: vec![empty; 1 << table_bits],
+            nodes: 0,
+            deadline: None,
+            is_out_of_time: false,
+        }
+    }
+
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn is_out_of_time(&self) -> bool {
+        self.is_out_of_time
+    }
+
+    pub fn with_tablebase(mut self, tablebase: &'a Tablebase) -> Self {
+        if (tablebase.rows, tablebase.cols) == (self.geometry.rows, self.geometry.cols) {
+            self.tablebase = Some(tablebase);
+        }
+        self
+    }
+
+    pub fn solve(&mut self, stones: &Stones) -> Option<Value> {
+        // (None if the deadline passed before the search was done)
+        // narrows down the value with searches that only ask whether it's above some guess
+        // (which cut off far more than a search for the value itself would)
+        // guessing closer to a draw first, as most positions are nearer a draw than the end
+        let cells = self.geometry.cells() as Value;
+        let (mut lower, mut upper) = (-cells, cells);
+
+        while lower < upper {
+            let mut guess = lower + (upper - lower) / 2;
+            if guess <= 0 && lower / 2 < guess {
+                guess = lower / 2;
+            } else if guess >= 0 && upper / 2 > guess {
+                guess = upper / 2;
+            }
+
+            let value = self.negamax(stones, guess, guess + 1);
+            if self.is_out_of_time {
+                return None;
+            }
+            if value <= guess {
+                upper = value;
+            } else {
+                lower = value;
+            }
+        }
+
+        Some(lower)
+    }
+
+    pub fn solve_plays(&mut self, stones: &Stones) -> Option<Vec<(usize, Value)>> {
+        // every play's value for the player making it
+        let cells = self.geometry.cells();
+        self.geometry
+            .plays(stones)
+            .into_iter()
+            .map(|cell| match self.geometry.is_winning_play(stones, cell) {
+                true => Some((cell, (cells - stones.plies) as Value)),
+                _ => Some((cell, -self.solve(&stones.play(cell))?)),
+            })
+            .collect()
+    }
+
+    fn negamax(&mut self, stones: &Stones, mut alpha: Value, mut beta: Value) -> Value {
+        // the value of the position if it's between alpha and beta,
+        // or else a bound on it, on the same side of the window as the value
+        // (or nothing to go by, once the deadline has passed)
+        if self.nodes.is_multiple_of(DEADLINE_CHECK_NODES)
+            && self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            self.is_out_of_time = true;
+        }
+        if self.is_out_of_time {
+            return 0;
+        }
+        self.nodes += 1;
+        let geometry = &self.geometry;
+        let (cells, plies) = (geometry.cells() as Value, stones.plies as Value);
+        let own = stones.balls[stones.plies % 2];
+        let other = stones.balls[1 - stones.plies % 2];
+        let taken = own | other;
+
+        let mut plays = geometry.playable(taken);
+        if geometry.winning_cells(own, taken) & plays != 0 {
+            return cells - plies;
+        }
+        // a full board, or one about to be, is a draw
+        if plies + 1 >= cells {
+            return 0;
+        }
+
+        // any cell the opponent would win on has to be taken before they get to it
+        // (and there's no taking two at once)
+        let threats = geometry.winning_cells(other, taken);
+        match (threats & plays).count_ones() {
+            0 => {}
+            1 => plays &= threats,
+            _ => return -(cells - plies - 1),
+        }
+        // and playing beside one only lets them in
+        let plays = cells_of(plays)
+            .filter(|&cell| geometry.neighbours[cell] & !taken & threats == 0)
+            .collect::<Vec<_>>();
+        if plays.is_empty() {
+            return -(cells - plies - 1);
+        }
+
+        // from here, the soonest the player can win is on their next play
+        // and the soonest they can lose is on the opponent's play after that
+        let upper = (cells - plies - 2).max(0);
+        let lower = -(cells - plies - 3).max(0);
+        if lower >= beta {
+            return lower;
+        }
+        if upper <= alpha {
+            return upper;
+        }
+        alpha = alpha.max(lower);
+        beta = beta.min(upper);
+
+        let key = geometry.key(stones);
+        if let Some(value) = self
+            .tablebase
+            .and_then(|tablebase| tablebase.get(stones.plies, key))
+        {
+            return value;
+        }
+
+        let slot =
+            (key.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 32) as usize & (self.table.len() - 1);
+        let entry = self.table[slot];
+        let (mut known_lower, mut known_upper) = (lower, upper);
+        if entry.key == key {
+            if entry.lower >= beta {
+                return entry.lower;
+            }
+            if entry.upper <= alpha {
+                return entry.upper;
+            }
+            known_lower = known_lower.max(entry.lower);
+            known_upper = known_upper.min(entry.upper);
+            alpha = alpha.max(known_lower);
+            beta = beta.min(known_upper);
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+
+        let mut plays = plays;
+        plays.sort_by_cached_key(|&cell| std::cmp::Reverse(geometry.rank_play(own, other, cell)));
+
+        let (alpha_before, beta_before) = (alpha, beta);
+        let mut best = Value::MIN;
+        for cell in plays {
+            let value = -self.negamax(&stones.play(cell), -beta, -alpha);
+            best = best.max(value);
+            alpha = alpha.max(value);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        // a search cut short has found nothing worth keeping
+        if self.is_out_of_time {
+            return 0;
+        }
+
+        // what the search found is a bound on the value, unless it fell inside the window
+        if best > alpha_before {
+            known_lower = known_lower.max(best);
+        }
+        if best < beta_before {
+            known_upper = known_upper.min(best);
+        }
+        self.table[slot] = Entry {
+            key,
+            lower: known_lower,
+            upper: known_upper,
+        };
+
+        best
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::board::{count_plies, get_next_player_num, is_winning_move};
+    use crate::engine::get_valid_plays;
+
+    fn search_everything(board: &mut [Vec<u8>]) -> Value {
+        // the value of the position, from a search with nothing cut short
+        let cells = board.len() * board[0].len();
+        let plies = count_plies(board);
+        let player_num = get_next_player_num(board);
+
+        get_valid_plays(board)
+            .into_iter()
+            .map(|(row, col)| {
+                board[row][col] = player_num;
+                let value = match is_winning_move(row, col, board) {
+                    true => (cells - plies) as Value,
+                    _ => -search_everything(board),
+                };
+                board[row][col] = 0;
+                value
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn solutions_match_a_full_search() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut solver = Solver::new(Geometry::new(4, 4).unwrap(), 16);
+
+        for _ in 0..20 {
+            // a few random plies in, as long as no one's won yet
+            let mut board = vec![vec![0; 4]; 4];
+            for _ in 0..8 {
+                let (row, col) = *get_valid_plays(&board).choose(&mut rng).unwrap();
+                board[row][col] = get_next_player_num(&board);
+                if is_winning_move(row, col, &board) {
+                    board[row][col] = 0;
+                    break;
+                }
+            }
+
+            let stones = Stones::from_board(&board);
+            assert_eq!(solver.solve(&stones), Some(search_everything(&mut board)));
+        }
+    }
+
+    #[test]
+    fn searches_give_up_at_the_deadline() {
+        let mut solver =
+            Solver::new(Geometry::new(5, 5).unwrap(), 16).with_deadline(Instant::now());
+        assert_eq!(solver.solve(&Stones::default()), None);
+        assert!(solver.is_out_of_time());
+        assert_eq!(solver.solve_plays(&Stones::default()), None);
+    }
+
+    #[test]
+    fn mirror_images_share_keys() {
+        let geometry = Geometry::new(5, 5).unwrap();
+        let board = vec![
+            vec![1, 2, 0, 0, 0],
+            vec![0, 0, 0, 0, 1],
+            vec![0, 0, 0, 0, 0],
+            vec![2, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0],
+        ];
+        let key = geometry.key(&Stones::from_board(&board));
+
+        let flipped = board.iter().rev().cloned().collect::<Vec<_>>();
+        assert_eq!(geometry.key(&Stones::from_board(&flipped)), key);
+        let mirrored = board
+            .iter()
+            .map(|row| row.iter().rev().copied().collect())
+            .collect::<Vec<_>>();
+        assert_eq!(geometry.key(&Stones::from_board(&mirrored)), key);
+
+        let mut other = board.clone();
+        other[4][4] = 1;
+        other[0][4] = 2;
+        assert_ne!(geometry.key(&Stones::from_board(&other)), key);
+    }
+
+    #[test]
+    fn values_tell_how_soon_the_game_ends() {
+        let geometry = Geometry::new(4, 4).unwrap();
+        assert_eq!(geometry.outcome(16 - 5, 5), Outcome::Win(1));
+        assert_eq!(geometry.outcome(-(16 - 5 - 1), 5), Outcome::Loss(2));
+        assert_eq!(geometry.outcome(1, 5), Outcome::Win(11));
+        assert_eq!(geometry.outcome(0, 5), Outcome::Draw);
+    }
+}
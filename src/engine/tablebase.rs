@@ -0,0 +1,193 @@
+// tablebases, worked out ahead of time by the solver
+//
+// a tablebase has the value of every position on a board of its size, up to some number of plies in,
+// keyed the way the solver keys them (see solver.rs)
+// the solver takes it from there, and has little enough left to search that it can play perfectly
+// on that board as the game goes on
+//
+// on disk, a tablebase is
+//   the magic bytes "STKYTB" and the format version (a byte)
+//   the rows, columns and plies it covers (a byte each)
+//   how many positions it has (8 bytes, little-endian)
+//   their keys, in ascending order (8 bytes each, little-endian)
+//   and their values, in the same order (a byte each)
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use super::solver::Value;
+
+// the paths to tablebases go in an environment variable, separated by commas
+const PATHS_SEPARATOR: char = ',';
+
+const MAGIC: &[u8; 6] = b"STKYTB";
+const VERSION: u8 = 1;
+
+// the tablebases the server has, no more than one for each size of board
+#[derive(Default)]
+pub struct Tablebases {
+    tablebases: Vec<Tablebase>,
+}
+
+impl Tablebases {
+    pub fn load(paths: &str) -> Self {
+        // tablebases that can't be read are left out
+        let mut tablebases = Self::default();
+        for path in paths.split(PATHS_SEPARATOR).map(str::trim) {
+            if path.is_empty() {
+                continue;
+            }
+            match Tablebase::load(Path::new(path)) {
+                Ok(tablebase) if tablebases.find(tablebase.rows, tablebase.cols).is_none() => {
+                    tracing::info!(
+                        "loaded {}x{} tablebase: {} positions",
+                        tablebase.rows,
+                        tablebase.cols,
+                        tablebase.len()
+                    );
+                    tablebases.tablebases.push(tablebase);
+                }
+                Ok(_) => tracing::warn!("tablebase for the same board already loaded: {}", path),
+                Err(err) => tracing::warn!("could not load tablebase {}: {}", path, err),
+            }
+        }
+        tablebases
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tablebases.is_empty()
+    }
+
+    pub fn find(&self, rows: usize, cols: usize) -> Option<&Tablebase> {
+        self.tablebases
+            .iter()
+            .find(|tablebase| (tablebase.rows, tablebase.cols) == (rows, cols))
+    }
+}
+
+impl From<Vec<Tablebase>> for Tablebases {
+    fn from(tablebases: Vec<Tablebase>) -> Self {
+        Self { tablebases }
+    }
+}
+
+pub struct Tablebase {
+    pub rows: usize,
+    pub cols: usize,
+    // positions with more balls than this on the board aren't in it
+    pub plies: usize,
+    keys: Vec<u64>,
+    values: Vec<Value>,
+}
+
+impl Tablebase {
+    pub fn new(rows: usize, cols: usize, plies: usize, mut positions: Vec<(u64, Value)>) -> Self {
+        positions.sort_unstable_by_key(|&(key, _)| key);
+        positions.dedup_by_key(|&mut (key, _)| key);
+        let (keys, values) = positions.into_iter().unzip();
+        Self {
+            rows,
+            cols,
+            plies,
+            keys,
+            values,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn get(&self, plies: usize, key: u64) -> Option<Value> {
+        if plies > self.plies {
+            return None;
+        }
+        self.keys.binary_search(&key).ok().map(|i| self.values[i])
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let mut header = [0; 10];
+        reader.read_exact(&mut header)?;
+        if &header[..6] != MAGIC || header[6] != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a tablebase, or one from another version",
+            ));
+        }
+        let (rows, cols, plies) = (header[7] as usize, header[8] as usize, header[9] as usize);
+
+        let mut bytes = [0; 8];
+        reader.read_exact(&mut bytes)?;
+        let len = u64::from_le_bytes(bytes) as usize;
+
+        let mut keys = Vec::with_capacity(len);
+        for _ in 0..len {
+            reader.read_exact(&mut bytes)?;
+            keys.push(u64::from_le_bytes(bytes));
+        }
+        let mut values = vec![0; len];
+        reader.read_exact(&mut values)?;
+
+        Ok(Self {
+            rows,
+            cols,
+            plies,
+            keys,
+            values: values.into_iter().map(|value| value as Value).collect(),
+        })
+    }
+
+    fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION, self.rows as u8, self.cols as u8, self.plies as u8])?;
+        writer.write_all(&(self.len() as u64).to_le_bytes())?;
+        for key in &self.keys {
+            writer.write_all(&key.to_le_bytes())?;
+        }
+        let values = self
+            .values
+            .iter()
+            .map(|&value| value as u8)
+            .collect::<Vec<_>>();
+        writer.write_all(&values)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tablebases_read_back() {
+        let tablebase = Tablebase::new(5, 5, 2, vec![(30, -3), (0, 0), (7, 12)]);
+        let mut bytes = vec![];
+        tablebase.write(&mut bytes).unwrap();
+
+        let tablebase = Tablebase::read(&mut bytes.as_slice()).unwrap();
+        assert_eq!((tablebase.rows, tablebase.cols, tablebase.plies), (5, 5, 2));
+        assert_eq!(tablebase.len(), 3);
+        assert_eq!(tablebase.get(1, 7), Some(12));
+        assert_eq!(tablebase.get(2, 30), Some(-3));
+        assert_eq!(tablebase.get(1, 8), None);
+        // positions further in than it covers are never in it
+        assert_eq!(tablebase.get(3, 0), None);
+
+        bytes[0] = b'X';
+        assert!(Tablebase::read(&mut bytes.as_slice()).is_err());
+    }
+}
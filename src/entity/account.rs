@@ -0,0 +1,176 @@
+use std::sync::OnceLock;
+
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::{FixedOffset, Utc};
+use sea_orm::entity::prelude::*;
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, DbErr, Statement};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub const USERNAME_MIN_LEN: usize = 3;
+pub const USERNAME_MAX_LEN: usize = 20;
+pub const PASSWORD_MIN_LEN: usize = 8;
+pub const PASSWORD_MAX_LEN: usize = 128;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "account")]
+pub struct Model {
+    // an account's uuid doubles as its owner's player key
+    // (i.e. what's stored in a game's player1_key/player2_key)
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub uuid: Uuid,
+    pub created_at: DateTimeWithTimeZone,
+    #[sea_orm(unique)]
+    pub username: String,
+    #[serde(skip_serializing)]
+    pub password_hash: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+pub async fn create(
+    player_key: Uuid,
+    username: String,
+    password_hash: String,
+    conn: &DatabaseConnection,
+) -> Result<bool, DbErr> {
+    // assumes that username has already been validated
+    // and that password has already been hashed
+    // returns false if the username was taken in the meantime
+    let stmt = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        INSERT INTO account (uuid, created_at, username, password_hash)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT DO NOTHING
+        "#,
+        vec![
+            player_key.into(),
+            Utc::now().with_timezone(&FixedOffset::east(0)).into(),
+            username.into(),
+            password_hash.into(),
+        ],
+    );
+    let res = conn.execute(stmt).await?;
+
+    Ok(res.rows_affected() == 1)
+}
+
+pub async fn find_by_id(
+    player_key: Uuid,
+    conn: &DatabaseConnection,
+) -> Result<Option<Model>, DbErr> {
+    Entity::find_by_id(player_key).one(conn).await
+}
+
+pub async fn find_by_username(
+    username: &str,
+    conn: &DatabaseConnection,
+) -> Result<Option<Model>, DbErr> {
+    Entity::find()
+        .filter(Column::Username.eq(normalize_username(username)))
+        .one(conn)
+        .await
+}
+
+pub fn normalize_username(username: &str) -> String {
+    username.trim().to_lowercase()
+}
+
+pub fn validate_username(username: &str) -> Result<String, &'static str> {
+    let username = normalize_username(username);
+
+    if username.len() < USERNAME_MIN_LEN {
+        return Err("username is too short");
+    }
+
+    if username.len() > USERNAME_MAX_LEN {
+        return Err("username is too long");
+    }
+
+    if !username
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+    {
+        return Err("username can only contain letters, digits, '_', '-' and '.'");
+    }
+
+    Ok(username)
+}
+
+pub fn validate_password(password: &str) -> Result<(), &'static str> {
+    if password.chars().count() < PASSWORD_MIN_LEN {
+        return Err("password is too short");
+    }
+
+    if password.chars().count() > PASSWORD_MAX_LEN {
+        return Err("password is too long");
+    }
+
+    Ok(())
+}
+
+// hashing is deliberately slow
+// so call these from a blocking task rather than an async one
+pub fn hash_password(password: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|err| format!("could not hash password: {}", err))
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+// turns down a login for a username with no account
+// after checking the password against a stand-in hash
+// so that it takes as long as a wrong password would
+pub fn reject_password(password: &str) -> bool {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    let dummy_hash = DUMMY_HASH.get_or_init(|| {
+        hash_password("no account has this password").expect("could not hash dummy password")
+    });
+
+    verify_password(password, dummy_hash);
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn usernames_are_normalized() {
+        assert_eq!(validate_username(" Ada.L "), Ok(String::from("ada.l")));
+        assert!(validate_username("ab").is_err());
+        assert!(validate_username("no spaces").is_err());
+        assert!(validate_username("zoë").is_err());
+        assert!(validate_username("a_username_that_is_too_long").is_err());
+    }
+
+    #[test]
+    fn passwords_are_hashed_and_verified() {
+        assert!(validate_password("short").is_err());
+        assert!(validate_password("long enough").is_ok());
+
+        let hash = hash_password("correct horse").unwrap();
+        assert_ne!(hash, "correct horse");
+        assert!(verify_password("correct horse", &hash));
+        assert!(!verify_password("battery staple", &hash));
+        assert!(!verify_password("correct horse", "not a hash"));
+        assert!(!reject_password("correct horse"));
+    }
+}
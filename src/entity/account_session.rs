@@ -0,0 +1,80 @@
+use chrono::{FixedOffset, Utc};
+use sea_orm::entity::prelude::*;
+use sea_orm::{DatabaseConnection, DbBackend, DbErr, FromQueryResult, Set, Statement};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "account_session")]
+pub struct Model {
+    // what a logged in browser keeps in its cookie
+    // a new one is made on every login, and logging out deletes it
+    // so the account's uuid never has to leave the server
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub uuid: Uuid,
+    pub account_id: Uuid,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::account::Entity",
+        from = "Column::AccountId",
+        to = "super::account::Column::Uuid"
+    )]
+    Account,
+}
+
+impl Related<super::account::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Account.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+pub async fn create(account_id: Uuid, conn: &DatabaseConnection) -> Result<Model, DbErr> {
+    let session = ActiveModel {
+        uuid: Set(Uuid::new_v4()),
+        account_id: Set(account_id),
+        created_at: Set(Utc::now().with_timezone(&FixedOffset::east(0))),
+    };
+
+    session.insert(conn).await
+}
+
+#[derive(Debug, FromQueryResult)]
+pub struct SessionLookup {
+    // the account logged in with the session, if the id is an account session's
+    pub account_id: Option<Uuid>,
+    // whether the id is an account's own uuid
+    pub is_account_key: bool,
+}
+
+pub async fn look_up(session_id: Uuid, conn: &DatabaseConnection) -> Result<SessionLookup, DbErr> {
+    let stmt = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        SELECT s.account_id, a.uuid IS NOT NULL AS is_account_key
+        FROM (SELECT $1::uuid AS id) AS c
+        LEFT JOIN account_session s ON s.uuid = c.id
+        LEFT JOIN account a ON a.uuid = c.id
+        "#,
+        vec![session_id.into()],
+    );
+
+    let lookup = SessionLookup::find_by_statement(stmt).one(conn).await?;
+    Ok(lookup.unwrap_or(SessionLookup {
+        account_id: None,
+        is_account_key: false,
+    }))
+}
+
+pub async fn revoke(session_id: Uuid, conn: &DatabaseConnection) -> Result<(), DbErr> {
+    Entity::delete_many()
+        .filter(Column::Uuid.eq(session_id))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
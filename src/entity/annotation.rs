@@ -0,0 +1,102 @@
+use chrono::{FixedOffset, Utc};
+use sea_orm::entity::prelude::*;
+use sea_orm::sea_query::Expr;
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbErr, QueryOrder, Set};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::engine::analysis::PlyAnalysis;
+
+// what the post-game analysis made of each ply
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "annotation")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub game_id: Uuid,
+    // counted from 1
+    pub ply: i16,
+    pub player_num: i16,
+    pub play_row: i16,
+    pub play_col: i16,
+    // the play the engine preferred (the same as the one made, if nothing was better)
+    pub best_row: i16,
+    pub best_col: i16,
+    // black's advantage after the ply, from -100 (white wins) to 100 (black wins)
+    pub advantage: i16,
+    // inaccuracy, mistake or blunder (None for plays that were good enough)
+    pub judgement: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::game::Entity",
+        from = "Column::GameId",
+        to = "super::game::Column::Uuid"
+    )]
+    Game,
+}
+
+impl Related<super::game::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Game.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+pub async fn find_for_game(game_id: Uuid, conn: &DatabaseConnection) -> Result<Vec<Model>, DbErr> {
+    Entity::find()
+        .filter(Column::GameId.eq(game_id))
+        .order_by_asc(Column::Ply)
+        .all(conn)
+        .await
+}
+
+pub async fn save_analysis(
+    game_id: Uuid,
+    plies: Vec<PlyAnalysis>,
+    conn: &DatabaseConnection,
+) -> Result<(), DbErr> {
+    // replaces any earlier annotations, and marks the game as analyzed
+    let txn = conn.begin().await?;
+
+    Entity::delete_many()
+        .filter(Column::GameId.eq(game_id))
+        .exec(&txn)
+        .await?;
+
+    let annotations = plies
+        .into_iter()
+        .enumerate()
+        .map(|(i, ply)| ActiveModel {
+            game_id: Set(game_id),
+            ply: Set(i as i16 + 1),
+            player_num: Set(ply.player_num as i16),
+            play_row: Set(ply.play.0 as i16),
+            play_col: Set(ply.play.1 as i16),
+            best_row: Set(ply.best_play.0 as i16),
+            best_col: Set(ply.best_play.1 as i16),
+            advantage: Set(ply.advantage as i16),
+            judgement: Set(ply
+                .judgement
+                .map(|judgement| judgement.as_str().to_string())),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+    if !annotations.is_empty() {
+        Entity::insert_many(annotations).exec(&txn).await?;
+    }
+
+    super::game::Entity::update_many()
+        .col_expr(
+            super::game::Column::AnalyzedAt,
+            Expr::value(Utc::now().with_timezone(&FixedOffset::east(0))),
+        )
+        .filter(super::game::Column::Uuid.eq(game_id))
+        .exec(&txn)
+        .await?;
+
+    txn.commit().await
+}
@@ -1,6 +1,6 @@
 use chrono::{FixedOffset, Utc};
 use sea_orm::entity::prelude::*;
-use sea_orm::{DatabaseConnection, DbErr, Set};
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbErr, QueryOrder, QuerySelect, Set};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use uuid::Uuid;
@@ -34,40 +34,79 @@ impl Related<super::game::Entity> for Entity {
 impl ActiveModelBehavior for ActiveModel {}
 
 pub async fn create_next(
-    game_id: Uuid,
+    parent: &Model,
     parent_state: Vec<Vec<u8>>,
     row: usize,
     col: usize,
     player_num: u8,
     conn: &DatabaseConnection,
-) -> Result<Model, DbErr> {
+) -> Result<Option<Model>, DbErr> {
     // assumes that row and column obey game rules
     // with respect to parent state
+    // returns None if the parent is no longer the game's latest board
+    // (e.g. a move was taken back in the meantime) or the game has ended
+    let txn = conn.begin().await?;
+
+    // lock the game, as takebacks do, so the latest board can't change under us
+    let is_ongoing = super::game::Entity::find_by_id(parent.game_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+        .is_some_and(|game| game.ended_at.is_none());
+    let latest_id = Entity::find()
+        .filter(Column::GameId.eq(parent.game_id))
+        .order_by_desc(Column::CreatedAt)
+        .order_by_desc(Column::Id)
+        .one(&txn)
+        .await?
+        .map(|board| board.id);
+    if !is_ongoing || latest_id != Some(parent.id) {
+        txn.rollback().await?;
+        return Ok(None);
+    }
+
     let mut state = parent_state;
     state[row][col] = player_num;
 
     let board = ActiveModel {
-        game_id: Set(game_id),
+        game_id: Set(parent.game_id),
         state: Set(json!(state)),
         created_at: Set(Utc::now().with_timezone(&FixedOffset::east(0))),
         ..Default::default()
-    };
+    }
+    .insert(&txn)
+    .await?;
 
-    board.insert(conn).await
+    txn.commit().await?;
+
+    Ok(Some(board))
+}
+
+pub async fn find_for_game(game_id: Uuid, conn: &DatabaseConnection) -> Result<Vec<Model>, DbErr> {
+    // the game's positions in the order they were played, starting from the empty board
+    Entity::find()
+        .filter(Column::GameId.eq(game_id))
+        .order_by_asc(Column::CreatedAt)
+        .order_by_asc(Column::Id)
+        .all(conn)
+        .await
 }
 
-pub async fn create_initial(game_id: Uuid, conn: &DatabaseConnection) -> Result<Model, DbErr> {
-    create_next(game_id, init_state(), 0, 0, 0, conn).await
+pub async fn create_initial(
+    game_id: Uuid,
+    size: usize,
+    conn: &DatabaseConnection,
+) -> Result<Model, DbErr> {
+    let board = ActiveModel {
+        game_id: Set(game_id),
+        state: Set(json!(init_state(size))),
+        created_at: Set(Utc::now().with_timezone(&FixedOffset::east(0))),
+        ..Default::default()
+    };
+
+    board.insert(conn).await
 }
 
-pub fn init_state() -> Vec<Vec<u8>> {
-    vec![
-        vec![0, 0, 0, 0, 0, 0, 0],
-        vec![0, 0, 0, 0, 0, 0, 0],
-        vec![0, 0, 0, 0, 0, 0, 0],
-        vec![0, 0, 0, 0, 0, 0, 0],
-        vec![0, 0, 0, 0, 0, 0, 0],
-        vec![0, 0, 0, 0, 0, 0, 0],
-        vec![0, 0, 0, 0, 0, 0, 0],
-    ]
+pub fn init_state(size: usize) -> Vec<Vec<u8>> {
+    vec![vec![0; size]; size]
 }
@@ -1,10 +1,20 @@
+use std::time::Duration;
+
 use chrono::{FixedOffset, Utc};
+use rand::Rng;
 use sea_orm::entity::prelude::*;
-use sea_orm::{DatabaseConnection, DbErr, Set};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    Condition, ConnectionTrait, DatabaseConnection, DatabaseTransaction, DbBackend, DbErr,
+    FromQueryResult, Set, Statement,
+};
 use sea_orm::{QueryOrder, QuerySelect};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::clock::TimeControl;
+use crate::rating;
+
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "game")]
 pub struct Model {
@@ -16,12 +26,94 @@ pub struct Model {
     pub player2_key: Option<Uuid>,
     pub winner_key: Option<Uuid>,
     pub ended_at: Option<DateTimeWithTimeZone>,
+    pub is_rated: bool,
+    // 1 or 2 for the winning player, 0 for a draw
+    // (winner_key alone can't tell a draw from a win by the computer)
+    pub winner_num: Option<i16>,
+    pub result_reason: Option<String>,
+    // listed in the lobby while waiting for an opponent
+    pub is_public: bool,
+    // the game this one is a rematch of
+    pub previous_game_id: Option<Uuid>,
+    // how many takebacks the players may make between them
+    // (always 0 in rated games)
+    pub takeback_limit: i16,
+    // the time control, for timed games
+    pub clock_initial_secs: Option<i32>,
+    pub clock_increment_secs: Option<i32>,
+    // the clocks as of clock_updated_at
+    // (when the running seat's time started running)
+    pub player1_time_left_ms: Option<i64>,
+    pub player2_time_left_ms: Option<i64>,
+    pub clock_updated_at: Option<DateTimeWithTimeZone>,
+    // the hours allowed per move, for correspondence games
+    pub move_deadline_hours: Option<i32>,
+    // when the player to move forfeits
+    // (only set once both seats are taken)
+    pub move_deadline_at: Option<DateTimeWithTimeZone>,
+    // the name of the engine the computer plays with, in the computer's seat
+    // (None for the default engine, and for seats held by people)
+    pub player1_engine: Option<String>,
+    pub player2_engine: Option<String>,
+    // how long each engine gets per move, when two computers play each other
+    pub engine_move_secs: Option<i32>,
+    // how many hints each player has asked for
+    pub player1_hints: i16,
+    pub player2_hints: i16,
+    // when the post-game analysis was saved
+    pub analyzed_at: Option<DateTimeWithTimeZone>,
+    // the number of rows, and of cells in each
+    pub board_size: i16,
+}
+
+impl Model {
+    pub fn time_control(&self) -> Option<TimeControl> {
+        match (self.clock_initial_secs, self.clock_increment_secs) {
+            (Some(initial_secs), Some(increment_secs)) => Some(TimeControl {
+                initial: Duration::from_secs(initial_secs.max(0) as u64),
+                increment: Duration::from_secs(increment_secs.max(0) as u64),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn is_computer(&self, player_num: u8) -> bool {
+        // the computer sits in whichever seat no person holds
+        // (both of them, in exhibitions between two computers)
+        let key = match player_num {
+            1 => self.player1_key,
+            2 => self.player2_key,
+            _ => return false,
+        };
+        self.is_against_ai && key.is_none()
+    }
+
+    pub fn is_exhibition(&self) -> bool {
+        self.is_computer(1) && self.is_computer(2)
+    }
+
+    pub fn engine(&self, player_num: u8) -> Option<&str> {
+        match player_num {
+            1 => self.player1_engine.as_deref(),
+            2 => self.player2_engine.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn engine_move_time(&self) -> Option<Duration> {
+        self.engine_move_secs
+            .map(|secs| Duration::from_secs(secs.max(0) as u64))
+    }
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(has_many = "super::board::Entity")]
     Board,
+    #[sea_orm(has_many = "super::takeback::Entity")]
+    Takeback,
+    #[sea_orm(has_many = "super::annotation::Entity")]
+    Annotation,
 }
 
 impl Related<super::board::Entity> for Entity {
@@ -30,33 +122,490 @@ impl Related<super::board::Entity> for Entity {
     }
 }
 
+impl Related<super::annotation::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Annotation.def()
+    }
+}
+
+impl Related<super::takeback::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Takeback.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResultReason {
+    // a player connected four balls
+    Connect,
+    // no more moves left on the board
+    BoardFull,
+    // a player gave up
+    Resignation,
+    // the players agreed to a draw
+    Agreement,
+    // a player ran out of time
+    Timeout,
+    // a player left during their turn and didn't come back in time
+    Abandonment,
+    // the game was called off after a player left
+    Aborted,
+}
+
+impl ResultReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Connect => "connect",
+            Self::BoardFull => "board_full",
+            Self::Resignation => "resignation",
+            Self::Agreement => "agreement",
+            Self::Timeout => "timeout",
+            Self::Abandonment => "abandonment",
+            Self::Aborted => "aborted",
+        }
+    }
+
+    pub fn is_rated(&self) -> bool {
+        // aborted games count for nobody
+        // (see MIN_RATED_PLIES for games that end too early)
+        match self {
+            Self::Connect
+            | Self::BoardFull
+            | Self::Resignation
+            | Self::Agreement
+            | Self::Timeout
+            | Self::Abandonment => true,
+            Self::Aborted => false,
+        }
+    }
+}
+
+// games decided before both players have made a move aren't rated
+pub const MIN_RATED_PLIES: usize = 2;
+
+// the creator's seat for a game, when they'd rather leave it to chance
+pub const RANDOM_SEAT: usize = 3;
+
+// how long engines may get per move in exhibitions, in seconds
+pub const DEFAULT_ENGINE_MOVE_SECS: i32 = 2;
+pub const MAX_ENGINE_MOVE_SECS: i32 = 10;
+
+// games between people are always played on the full board
+// (but the computer will play on a smaller one, down to the smallest with room for four in a row)
+pub const DEFAULT_BOARD_SIZE: i16 = 7;
+pub const MIN_BOARD_SIZE: i16 = 4;
+
+pub const DEFAULT_TAKEBACK_LIMIT: i16 = 3;
+pub const MAX_TAKEBACK_LIMIT: i16 = 10;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GameSettings {
+    pub is_against_ai: bool,
+    // only human vs human games are rated
+    pub is_rated: bool,
+    // or listed in the lobby
+    pub is_public: bool,
+    // rated games allow no takebacks
+    pub takeback_limit: i16,
+    // None for untimed games
+    pub time_control: Option<TimeControl>,
+    // correspondence games have a deadline per move instead of clocks
+    // (but not against the computer, which never keeps anyone waiting)
+    pub move_deadline_hours: Option<i32>,
+    // the engines for the seats the computer takes, if not the default one
+    // (against a person, the computer plays with the same engine whichever seat it takes)
+    pub engines: [Option<String>; 2],
+    // how long each engine gets per move, in exhibitions
+    pub engine_move_secs: Option<i32>,
+    pub board_size: i16,
+}
+
+impl GameSettings {
+    pub fn new(
+        is_against_ai: bool,
+        is_rated: bool,
+        is_public: bool,
+        takeback_limit: i16,
+        time_control: Option<TimeControl>,
+        move_deadline_hours: Option<i32>,
+    ) -> Self {
+        let is_rated = is_rated && !is_against_ai;
+        Self {
+            time_control,
+            move_deadline_hours: match (is_against_ai, time_control) {
+                (false, None) => move_deadline_hours,
+                _ => None,
+            },
+            is_against_ai,
+            is_rated,
+            is_public: is_public && !is_against_ai,
+            takeback_limit: match is_rated {
+                true => 0,
+                _ => takeback_limit.clamp(0, MAX_TAKEBACK_LIMIT),
+            },
+            engines: [None, None],
+            engine_move_secs: None,
+            board_size: DEFAULT_BOARD_SIZE,
+        }
+    }
+
+    pub fn with_engines(mut self, engines: [Option<String>; 2]) -> Self {
+        // only games against the computer have engines
+        if self.is_against_ai {
+            self.engines = engines;
+        }
+        self
+    }
+
+    pub fn with_engine_move_secs(mut self, engine_move_secs: Option<i32>) -> Self {
+        self.engine_move_secs = engine_move_secs
+            .filter(|_| self.is_against_ai)
+            .map(|secs| secs.clamp(1, MAX_ENGINE_MOVE_SECS));
+        self
+    }
+
+    pub fn with_board_size(mut self, board_size: Option<i16>) -> Self {
+        self.board_size = board_size
+            .filter(|_| self.is_against_ai)
+            .map(|size| size.clamp(MIN_BOARD_SIZE, DEFAULT_BOARD_SIZE))
+            .unwrap_or(DEFAULT_BOARD_SIZE);
+        self
+    }
+}
+
+impl From<&Model> for GameSettings {
+    fn from(game: &Model) -> Self {
+        Self::new(
+            game.is_against_ai,
+            game.is_rated,
+            game.is_public,
+            game.takeback_limit,
+            game.time_control(),
+            game.move_deadline_hours,
+        )
+        .with_engines([game.player1_engine.clone(), game.player2_engine.clone()])
+        .with_engine_move_secs(game.engine_move_secs)
+        .with_board_size(Some(game.board_size))
+    }
+}
+
 pub async fn create(
     creator_key: Uuid,
+    creator_seat: usize,
     conn: &DatabaseConnection,
-    is_against_ai: bool,
+    settings: GameSettings,
+) -> Result<Model, DbErr> {
+    // the creator picks a seat, RANDOM_SEAT to toss for one, or 0 to just watch
+    // (against the computer, it takes any seat the creator doesn't
+    // so just watching makes for an exhibition between two computers)
+    let creator_seat = match creator_seat {
+        RANDOM_SEAT => rand::thread_rng().gen_range(1..=2),
+        _ => creator_seat,
+    };
+
+    match creator_seat {
+        1 => create_with_players(Some(creator_key), None, conn, settings).await,
+        2 => create_with_players(None, Some(creator_key), conn, settings).await,
+        _ => create_with_players(None, None, conn, settings).await,
+    }
+}
+
+pub async fn create_with_players(
+    player1_key: Option<Uuid>,
+    player2_key: Option<Uuid>,
+    conn: &DatabaseConnection,
+    settings: GameSettings,
+) -> Result<Model, DbErr> {
+    insert(player1_key, player2_key, None, conn, settings).await
+}
+
+pub async fn create_rematch(game: &Model, conn: &DatabaseConnection) -> Result<Model, DbErr> {
+    // same settings, with the players swapping seats
+    // (the computer included, so whoever moved second gets to move first)
+    let mut settings = GameSettings::from(game);
+    settings.engines.swap(0, 1);
+
+    insert(
+        game.player2_key,
+        game.player1_key,
+        Some(game.uuid),
+        conn,
+        settings,
+    )
+    .await
+}
+
+pub async fn find_unanalyzed(limit: u64, conn: &DatabaseConnection) -> Result<Vec<Model>, DbErr> {
+    // games that have ended but haven't been analyzed yet, the most recent first
+    Entity::find()
+        .filter(Column::EndedAt.is_not_null())
+        .filter(Column::AnalyzedAt.is_null())
+        .order_by_desc(Column::EndedAt)
+        .limit(limit)
+        .all(conn)
+        .await
+}
+
+pub async fn find_rematch(
+    game_id: Uuid,
+    conn: &DatabaseConnection,
+) -> Result<Option<Model>, DbErr> {
+    Entity::find()
+        .filter(Column::PreviousGameId.eq(game_id))
+        .one(conn)
+        .await
+}
+
+async fn insert(
+    player1_key: Option<Uuid>,
+    player2_key: Option<Uuid>,
+    previous_game_id: Option<Uuid>,
+    conn: &DatabaseConnection,
+    settings: GameSettings,
 ) -> Result<Model, DbErr> {
     let game = ActiveModel {
         uuid: Set(Uuid::new_v4()),
         created_at: Set(Utc::now().with_timezone(&FixedOffset::east(0))),
-        is_against_ai: Set(is_against_ai),
-        player1_key: Set(Some(creator_key)),
+        is_against_ai: Set(settings.is_against_ai),
+        is_rated: Set(settings.is_rated),
+        is_public: Set(settings.is_public),
+        takeback_limit: Set(settings.takeback_limit),
+        clock_initial_secs: Set(settings
+            .time_control
+            .map(|time_control| time_control.initial.as_secs() as i32)),
+        clock_increment_secs: Set(settings
+            .time_control
+            .map(|time_control| time_control.increment.as_secs() as i32)),
+        move_deadline_hours: Set(settings.move_deadline_hours),
+        // engines only go in the seats the computer takes
+        player1_engine: Set(settings.engines[0]
+            .clone()
+            .filter(|_| player1_key.is_none())),
+        player2_engine: Set(settings.engines[1]
+            .clone()
+            .filter(|_| player2_key.is_none())),
+        engine_move_secs: Set(settings.engine_move_secs),
+        board_size: Set(settings.board_size),
+        player1_key: Set(player1_key),
+        player2_key: Set(player2_key),
+        previous_game_id: Set(previous_game_id),
         ..Default::default()
     };
 
     let game = game.insert(conn).await?;
 
     // create initial board for this game
-    super::board::create_initial(game.uuid, conn).await?;
+    super::board::create_initial(game.uuid, game.board_size as usize, conn).await?;
+
+    // games that start with both players seated start their move deadline too
+    let move_deadline_at = start_move_deadline(&game, conn).await?;
 
-    Ok(game)
+    Ok(Model {
+        move_deadline_at,
+        ..game
+    })
 }
 
 pub async fn find_by_id(game_id: Uuid, conn: &DatabaseConnection) -> Result<Option<Model>, DbErr> {
     Entity::find_by_id(game_id).one(conn).await
 }
 
+pub fn get_player_num(game: &Model, player_key: Uuid) -> usize {
+    // 1 -- player 1, black
+    // 2 -- player 2, white
+    // 0 -- observer
+    if game.player1_key == Some(player_key) {
+        1
+    } else if game.player2_key == Some(player_key) {
+        2
+    } else {
+        0
+    }
+}
+
+pub async fn take_seat(
+    game_id: Uuid,
+    player_num: usize,
+    player_key: Uuid,
+    conn: &DatabaseConnection,
+) -> Result<bool, DbErr> {
+    // taking the seat and checking that it is still free happen in one statement
+    // so two visitors racing for the same seat can't both get it
+    let (seat, other_seat) = match player_num {
+        1 => (Column::Player1Key, Column::Player2Key),
+        2 => (Column::Player2Key, Column::Player1Key),
+        _ => return Ok(false),
+    };
+
+    let res = Entity::update_many()
+        .col_expr(seat, Expr::value(player_key))
+        .filter(Column::Uuid.eq(game_id))
+        .filter(Column::IsAgainstAi.eq(false))
+        .filter(Column::EndedAt.is_null())
+        .filter(seat.is_null())
+        .filter(
+            Condition::any()
+                .add(other_seat.is_null())
+                .add(other_seat.ne(player_key)),
+        )
+        .exec(conn)
+        .await?;
+
+    Ok(res.rows_affected == 1)
+}
+
+pub async fn move_seat(
+    game: &Model,
+    player_num: usize,
+    player_key: Uuid,
+    txn: &DatabaseTransaction,
+) -> Result<bool, DbErr> {
+    // moves the seat to player_key, as long as it still belongs to whoever held it in game
+    // so two claims on the same seat can't both move it
+    let (seat, holder, other_seat) = match player_num {
+        1 => (Column::Player1Key, game.player1_key, Column::Player2Key),
+        2 => (Column::Player2Key, game.player2_key, Column::Player1Key),
+        _ => return Ok(false),
+    };
+    let holder = match holder {
+        Some(holder) => seat.eq(holder),
+        None => seat.is_null(),
+    };
+
+    let res = Entity::update_many()
+        .col_expr(seat, Expr::value(player_key))
+        .filter(Column::Uuid.eq(game.uuid))
+        .filter(Column::EndedAt.is_null())
+        .filter(holder)
+        .filter(
+            Condition::any()
+                .add(other_seat.is_null())
+                .add(other_seat.ne(player_key)),
+        )
+        .exec(txn)
+        .await?;
+
+    Ok(res.rows_affected == 1)
+}
+
+#[derive(Debug, FromQueryResult)]
+struct HintsUsed {
+    hints_used: i16,
+}
+
+pub async fn use_hint(
+    game_id: Uuid,
+    player_num: u8,
+    conn: &DatabaseConnection,
+) -> Result<Option<i16>, DbErr> {
+    // counts a hint for the player, and returns how many they've now used
+    // (as long as the game allows them -- rated games and games that have ended don't)
+    let hints = match player_num {
+        1 => "player1_hints",
+        2 => "player2_hints",
+        _ => return Ok(None),
+    };
+
+    // the count comes back from the update itself, so hints asked for at the same time
+    // each get their own number
+    let stmt = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        &format!(
+            r#"
+            UPDATE game SET {hints} = {hints} + 1
+            WHERE uuid = $1 AND NOT is_rated AND ended_at IS NULL
+            RETURNING {hints} AS hints_used
+            "#,
+            hints = hints
+        ),
+        vec![game_id.into()],
+    );
+
+    let used = HintsUsed::find_by_statement(stmt).one(conn).await?;
+    Ok(used.map(|used| used.hints_used))
+}
+
+pub async fn save_clock(
+    game_id: Uuid,
+    time_left_ms: [u64; 2],
+    conn: &DatabaseConnection,
+) -> Result<(), DbErr> {
+    Entity::update_many()
+        .col_expr(
+            Column::Player1TimeLeftMs,
+            Expr::value(time_left_ms[0] as i64),
+        )
+        .col_expr(
+            Column::Player2TimeLeftMs,
+            Expr::value(time_left_ms[1] as i64),
+        )
+        .col_expr(
+            Column::ClockUpdatedAt,
+            Expr::value(Utc::now().with_timezone(&FixedOffset::east(0))),
+        )
+        .filter(Column::Uuid.eq(game_id))
+        .exec(conn)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn start_move_deadline(
+    game: &Model,
+    conn: &DatabaseConnection,
+) -> Result<Option<DateTimeWithTimeZone>, DbErr> {
+    // gives the player to move their full time per move, from now
+    // returns the new deadline, if the game has one
+    // (nobody is held to a deadline while waiting for an opponent to sit down)
+    let hours = match (game.move_deadline_hours, game.player1_key, game.player2_key) {
+        (Some(hours), Some(_), Some(_)) => hours,
+        _ => return Ok(None),
+    };
+
+    let move_deadline_at =
+        Utc::now().with_timezone(&FixedOffset::east(0)) + chrono::Duration::hours(hours as i64);
+
+    Entity::update_many()
+        .col_expr(Column::MoveDeadlineAt, Expr::value(move_deadline_at))
+        .filter(Column::Uuid.eq(game.uuid))
+        .filter(Column::EndedAt.is_null())
+        .exec(conn)
+        .await?;
+
+    Ok(Some(move_deadline_at))
+}
+
+pub async fn find_overdue(
+    now: chrono::DateTime<Utc>,
+    conn: &DatabaseConnection,
+) -> Result<Vec<Model>, DbErr> {
+    // ongoing correspondence games whose player to move is out of time
+    Entity::find()
+        .filter(Column::MoveDeadlineAt.lt(now.with_timezone(&FixedOffset::east(0))))
+        .filter(Column::EndedAt.is_null())
+        .all(conn)
+        .await
+}
+
+pub fn is_overdue(game: &Model) -> bool {
+    game.ended_at.is_none()
+        && game
+            .move_deadline_at
+            .is_some_and(|move_deadline_at| move_deadline_at < Utc::now())
+}
+
+pub async fn find_running_clocks(conn: &DatabaseConnection) -> Result<Vec<Model>, DbErr> {
+    // ongoing timed games whose clocks have started
+    Entity::find()
+        .filter(Column::ClockInitialSecs.is_not_null())
+        .filter(Column::ClockUpdatedAt.is_not_null())
+        .filter(Column::EndedAt.is_null())
+        .all(conn)
+        .await
+}
+
 pub async fn get_most_recent_board(
     game: &Model,
     conn: &DatabaseConnection,
@@ -67,3 +616,455 @@ pub async fn get_most_recent_board(
         .one(conn)
         .await
 }
+
+pub async fn merge_player(
+    from_key: Uuid,
+    into_key: Uuid,
+    conn: &DatabaseConnection,
+) -> Result<(), DbErr> {
+    // move every game played as `from_key` over to `into_key`
+    // except those where `into_key` holds the other seat,
+    // as one key can't be both players in a game
+    // `from_key`'s rating goes along with its games, unless `into_key` has a rating of its own
+    // in which case the rated games that have ended stay behind, along with the rating
+    let txn = conn.begin().await?;
+
+    let mut skipped_games = Entity::find()
+        .filter(
+            Condition::any()
+                .add(
+                    Condition::all()
+                        .add(Column::Player1Key.eq(from_key))
+                        .add(Column::Player2Key.eq(into_key)),
+                )
+                .add(
+                    Condition::all()
+                        .add(Column::Player1Key.eq(into_key))
+                        .add(Column::Player2Key.eq(from_key)),
+                ),
+        )
+        .all(&txn)
+        .await?
+        .into_iter()
+        .map(|game| game.uuid)
+        .collect::<Vec<_>>();
+
+    if !super::player::move_rating(from_key, into_key, &txn).await? {
+        let rated_games = Entity::find()
+            .filter(
+                Condition::any()
+                    .add(Column::Player1Key.eq(from_key))
+                    .add(Column::Player2Key.eq(from_key)),
+            )
+            .filter(Column::IsRated.eq(true))
+            .filter(Column::EndedAt.is_not_null())
+            .all(&txn)
+            .await?;
+        skipped_games.extend(rated_games.into_iter().map(|game| game.uuid));
+    }
+
+    for (column, key) in [
+        (Column::Player1Key, from_key),
+        (Column::Player2Key, from_key),
+        (Column::WinnerKey, from_key),
+    ] {
+        Entity::update_many()
+            .col_expr(column, Expr::value(into_key))
+            .filter(column.eq(key))
+            .filter(Column::Uuid.is_not_in(skipped_games.clone()))
+            .exec(&txn)
+            .await?;
+    }
+
+    txn.commit().await
+}
+
+pub async fn finish(
+    game: Model,
+    winner_num: usize,
+    reason: ResultReason,
+    conn: &DatabaseConnection,
+) -> Result<Option<Model>, DbErr> {
+    // every game ends here, whatever the reason
+    // returns None if the game had already ended
+    let txn = conn.begin().await?;
+
+    // the computer's seat has no key, so neither does its win
+    let winner_key = match winner_num {
+        1 => game.player1_key,
+        2 => game.player2_key,
+        _ => None,
+    };
+
+    // only end the game if nothing else has ended it in the meantime
+    let res = Entity::update_many()
+        .col_expr(Column::WinnerKey, Expr::value(winner_key))
+        .col_expr(Column::WinnerNum, Expr::value(winner_num as i16))
+        .col_expr(Column::ResultReason, Expr::value(reason.as_str()))
+        .col_expr(
+            Column::EndedAt,
+            Expr::value(Utc::now().with_timezone(&FixedOffset::east(0))),
+        )
+        .filter(Column::Uuid.eq(game.uuid))
+        .filter(Column::EndedAt.is_null())
+        .exec(&txn)
+        .await?;
+    if res.rows_affected != 1 {
+        txn.rollback().await?;
+        return Ok(None);
+    }
+
+    let game = Entity::find_by_id(game.uuid).one(&txn).await?.unwrap();
+
+    let plies = super::board::Entity::find()
+        .filter(super::board::Column::GameId.eq(game.uuid))
+        .count(&txn)
+        .await?
+        - 1;
+
+    if game.is_rated && reason.is_rated() && plies >= MIN_RATED_PLIES {
+        if let (Some(player1_key), Some(player2_key)) = (game.player1_key, game.player2_key) {
+            let score = match winner_num {
+                1 => 1.0,
+                2 => 0.0,
+                _ => 0.5,
+            };
+            super::player::update_ratings(game.uuid, player1_key, player2_key, score, &txn).await?;
+        }
+    }
+
+    txn.commit().await?;
+
+    Ok(Some(game))
+}
+
+#[derive(Clone, Debug, FromQueryResult, Serialize)]
+pub struct PlayerGame {
+    pub uuid: Uuid,
+    pub created_at: DateTimeWithTimeZone,
+    pub ended_at: Option<DateTimeWithTimeZone>,
+    pub is_against_ai: bool,
+    pub is_rated: bool,
+    pub winner_num: Option<i16>,
+    pub result_reason: Option<String>,
+    // the seat the player held in this game
+    pub player_num: i32,
+    pub opponent_key: Option<Uuid>,
+    pub opponent_name: Option<String>,
+    pub rating_change: Option<f64>,
+    // how many hints the player had
+    pub hints_used: i16,
+}
+
+#[derive(Clone, Debug, FromQueryResult, Serialize)]
+pub struct PlayerRecord {
+    pub wins: i64,
+    pub losses: i64,
+    pub draws: i64,
+}
+
+#[derive(Clone, Debug, FromQueryResult, Serialize)]
+pub struct HeadToHead {
+    pub opponent_key: Uuid,
+    pub opponent_name: Option<String>,
+    pub games: i64,
+    pub wins: i64,
+    pub losses: i64,
+    pub draws: i64,
+}
+
+// the queries below are backed by the (player1_key, created_at)
+// and (player2_key, created_at) indexes on the game table
+
+pub async fn find_by_player(
+    player_key: Uuid,
+    limit: usize,
+    offset: usize,
+    conn: &DatabaseConnection,
+) -> Result<Vec<PlayerGame>, DbErr> {
+    let stmt = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        SELECT g.*,
+            COALESCE(p.name, a.username) AS opponent_name,
+            rh.rating_after - rh.rating_before AS rating_change
+        FROM (
+            SELECT uuid, created_at, ended_at, is_against_ai, is_rated,
+                winner_num, result_reason,
+                CASE WHEN player1_key = $1 THEN 1 ELSE 2 END AS player_num,
+                CASE WHEN player1_key = $1 THEN player2_key ELSE player1_key END
+                    AS opponent_key,
+                CASE WHEN player1_key = $1 THEN player1_hints ELSE player2_hints END
+                    AS hints_used
+            FROM game
+            WHERE player1_key = $1 OR player2_key = $1
+            ORDER BY created_at DESC
+            LIMIT $2 OFFSET $3
+        ) g
+        LEFT JOIN player p ON p.uuid = g.opponent_key
+        LEFT JOIN account a ON a.uuid = g.opponent_key
+        LEFT JOIN rating_history rh ON rh.game_id = g.uuid AND rh.player_key = $1
+        ORDER BY g.created_at DESC
+        "#,
+        vec![
+            player_key.into(),
+            (limit as i64).into(),
+            (offset as i64).into(),
+        ],
+    );
+
+    PlayerGame::find_by_statement(stmt).all(conn).await
+}
+
+#[derive(Clone, Debug, FromQueryResult, Serialize)]
+pub struct OngoingGame {
+    pub uuid: Uuid,
+    pub created_at: DateTimeWithTimeZone,
+    pub is_against_ai: bool,
+    pub is_rated: bool,
+    pub clock_initial_secs: Option<i32>,
+    pub clock_increment_secs: Option<i32>,
+    pub move_deadline_hours: Option<i32>,
+    pub move_deadline_at: Option<DateTimeWithTimeZone>,
+    // the seat the player holds in this game
+    pub player_num: i32,
+    pub opponent_key: Option<Uuid>,
+    pub opponent_name: Option<String>,
+    // the latest board, to tell whose turn it is
+    pub state: Json,
+}
+
+pub async fn find_ongoing_by_player(
+    player_key: Uuid,
+    conn: &DatabaseConnection,
+) -> Result<Vec<OngoingGame>, DbErr> {
+    // the games a player is seated in that haven't ended yet,
+    // the most pressing deadline first
+    let stmt = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        SELECT g.uuid, g.created_at, g.is_against_ai, g.is_rated,
+            g.clock_initial_secs, g.clock_increment_secs,
+            g.move_deadline_hours, g.move_deadline_at,
+            CASE WHEN g.player1_key = $1 THEN 1 ELSE 2 END AS player_num,
+            CASE WHEN g.player1_key = $1 THEN g.player2_key ELSE g.player1_key END
+                AS opponent_key,
+            COALESCE(p.name, a.username) AS opponent_name,
+            b.state
+        FROM game g
+        JOIN LATERAL (
+            SELECT state FROM board
+            WHERE board.game_id = g.uuid
+            ORDER BY created_at DESC, id DESC
+            LIMIT 1
+        ) b ON TRUE
+        LEFT JOIN player p
+            ON p.uuid = CASE WHEN g.player1_key = $1 THEN g.player2_key ELSE g.player1_key END
+        LEFT JOIN account a
+            ON a.uuid = CASE WHEN g.player1_key = $1 THEN g.player2_key ELSE g.player1_key END
+        WHERE (g.player1_key = $1 OR g.player2_key = $1) AND g.ended_at IS NULL
+        ORDER BY g.move_deadline_at ASC NULLS LAST, g.created_at DESC
+        "#,
+        vec![player_key.into()],
+    );
+
+    OngoingGame::find_by_statement(stmt).all(conn).await
+}
+
+pub async fn get_record_for_player(
+    player_key: Uuid,
+    conn: &DatabaseConnection,
+) -> Result<PlayerRecord, DbErr> {
+    let stmt = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        SELECT
+            COUNT(*) FILTER (
+                WHERE (player1_key = $1 AND winner_num = 1)
+                    OR (player2_key = $1 AND winner_num = 2)
+            ) AS wins,
+            COUNT(*) FILTER (
+                WHERE (player1_key = $1 AND winner_num = 2)
+                    OR (player2_key = $1 AND winner_num = 1)
+            ) AS losses,
+            COUNT(*) FILTER (WHERE winner_num = 0) AS draws
+        FROM game
+        WHERE (player1_key = $1 OR player2_key = $1) AND ended_at IS NOT NULL
+            -- aborted games count for nobody
+            AND result_reason IS DISTINCT FROM 'aborted'
+        "#,
+        vec![player_key.into()],
+    );
+
+    let record = PlayerRecord::find_by_statement(stmt).one(conn).await?;
+    Ok(record.unwrap_or(PlayerRecord {
+        wins: 0,
+        losses: 0,
+        draws: 0,
+    }))
+}
+
+pub async fn get_head_to_heads(
+    player_key: Uuid,
+    limit: usize,
+    offset: usize,
+    conn: &DatabaseConnection,
+) -> Result<Vec<HeadToHead>, DbErr> {
+    // most frequent human opponents first
+    let stmt = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        SELECT h.*, COALESCE(p.name, a.username) AS opponent_name
+        FROM (
+            SELECT
+                CASE WHEN player1_key = $1 THEN player2_key ELSE player1_key END
+                    AS opponent_key,
+                COUNT(*) AS games,
+                COUNT(*) FILTER (
+                    WHERE (player1_key = $1 AND winner_num = 1)
+                        OR (player2_key = $1 AND winner_num = 2)
+                ) AS wins,
+                COUNT(*) FILTER (
+                    WHERE (player1_key = $1 AND winner_num = 2)
+                        OR (player2_key = $1 AND winner_num = 1)
+                ) AS losses,
+                COUNT(*) FILTER (WHERE winner_num = 0) AS draws
+            FROM game
+            WHERE (player1_key = $1 OR player2_key = $1)
+                AND ended_at IS NOT NULL
+                AND result_reason IS DISTINCT FROM 'aborted'
+                AND NOT is_against_ai
+            GROUP BY 1
+        ) h
+        LEFT JOIN player p ON p.uuid = h.opponent_key
+        LEFT JOIN account a ON a.uuid = h.opponent_key
+        WHERE h.opponent_key IS NOT NULL
+        ORDER BY h.games DESC, h.opponent_key
+        LIMIT $2 OFFSET $3
+        "#,
+        vec![
+            player_key.into(),
+            (limit as i64).into(),
+            (offset as i64).into(),
+        ],
+    );
+
+    HeadToHead::find_by_statement(stmt).all(conn).await
+}
+
+#[derive(Clone, Debug, FromQueryResult, Serialize)]
+pub struct LobbyGame {
+    pub uuid: Uuid,
+    pub created_at: DateTimeWithTimeZone,
+    pub is_rated: bool,
+    // the first seat that is still free
+    pub free_seat: i32,
+    // the seated player, if any
+    pub player_key: Option<Uuid>,
+    pub player_name: Option<String>,
+    pub player_rating: f64,
+}
+
+pub const LOBBY_MAX_GAMES: usize = 50;
+
+pub async fn find_open_public(
+    game_id: Option<Uuid>,
+    conn: &DatabaseConnection,
+) -> Result<Vec<LobbyGame>, DbErr> {
+    // public human vs human games that still have a free seat,
+    // newest first -- or just the one game, if game_id is given
+    let stmt = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        SELECT g.uuid, g.created_at, g.is_rated,
+            CASE WHEN g.player1_key IS NULL THEN 1 ELSE 2 END AS free_seat,
+            COALESCE(g.player1_key, g.player2_key) AS player_key,
+            COALESCE(p.name, a.username) AS player_name,
+            COALESCE(p.rating, $1) AS player_rating
+        FROM game g
+        LEFT JOIN player p ON p.uuid = COALESCE(g.player1_key, g.player2_key)
+        LEFT JOIN account a ON a.uuid = COALESCE(g.player1_key, g.player2_key)
+        WHERE g.is_public
+            AND NOT g.is_against_ai
+            AND g.ended_at IS NULL
+            AND (g.player1_key IS NULL OR g.player2_key IS NULL)
+            AND ($2::uuid IS NULL OR g.uuid = $2)
+        ORDER BY g.created_at DESC
+        LIMIT $3
+        "#,
+        vec![
+            rating::DEFAULT_RATING.into(),
+            game_id.into(),
+            (LOBBY_MAX_GAMES as i64).into(),
+        ],
+    );
+
+    LobbyGame::find_by_statement(stmt).all(conn).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rated_games_allow_no_takebacks() {
+        assert_eq!(
+            GameSettings::new(false, true, false, 3, None, None).takeback_limit,
+            0
+        );
+        assert_eq!(
+            GameSettings::new(false, false, false, 3, None, None).takeback_limit,
+            3
+        );
+        assert_eq!(
+            GameSettings::new(true, true, false, 3, None, None).takeback_limit,
+            3
+        );
+        assert_eq!(
+            GameSettings::new(false, false, false, 99, None, None).takeback_limit,
+            MAX_TAKEBACK_LIMIT
+        );
+    }
+
+    #[test]
+    fn only_games_against_the_computer_have_engines() {
+        let engines = [Some(String::from("minimax")), Some(String::from("mcts"))];
+        let settings = GameSettings::new(true, false, false, 3, None, None)
+            .with_engines(engines.clone())
+            .with_engine_move_secs(Some(60));
+        assert_eq!(settings.engines, engines);
+        assert_eq!(settings.engine_move_secs, Some(MAX_ENGINE_MOVE_SECS));
+
+        let settings = GameSettings::new(false, false, false, 3, None, None)
+            .with_engines(engines)
+            .with_engine_move_secs(Some(2));
+        assert_eq!(settings.engines, [None, None]);
+        assert_eq!(settings.engine_move_secs, None);
+    }
+
+    #[test]
+    fn only_the_computer_plays_on_smaller_boards() {
+        let settings = GameSettings::new(true, false, false, 3, None, None);
+        assert_eq!(settings.clone().with_board_size(Some(5)).board_size, 5);
+        assert_eq!(
+            settings.clone().with_board_size(Some(2)).board_size,
+            MIN_BOARD_SIZE
+        );
+        assert_eq!(
+            settings.with_board_size(Some(9)).board_size,
+            DEFAULT_BOARD_SIZE
+        );
+
+        let settings = GameSettings::new(false, false, false, 3, None, None);
+        assert_eq!(
+            settings.with_board_size(Some(5)).board_size,
+            DEFAULT_BOARD_SIZE
+        );
+    }
+
+    #[test]
+    fn aborted_games_are_not_rated() {
+        assert!(ResultReason::Abandonment.is_rated());
+        assert!(!ResultReason::Aborted.is_rated());
+    }
+}
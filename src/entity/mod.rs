@@ -1,3 +1,10 @@
+pub mod account;
+pub mod account_session;
+pub mod annotation;
 pub mod board;
 pub mod game;
+pub mod player;
+pub mod rating_history;
+pub mod seat_claim;
 pub mod setup;
+pub mod takeback;
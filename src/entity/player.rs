@@ -0,0 +1,313 @@
+use chrono::{FixedOffset, Utc};
+use sea_orm::entity::prelude::*;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ConnectionTrait, DatabaseConnection, DatabaseTransaction, DbBackend, DbErr, FromQueryResult,
+    QuerySelect, Set, Statement,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::rating::{self, Rating};
+
+pub const NAME_MAX_LEN: usize = 20;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "player")]
+pub struct Model {
+    // same key as the one stored in a game's player1_key/player2_key
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub uuid: Uuid,
+    pub created_at: DateTimeWithTimeZone,
+    pub name: Option<String>,
+    pub rating: f64,
+    pub rated_games: i32,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::rating_history::Entity")]
+    RatingHistory,
+}
+
+impl Related<super::rating_history::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RatingHistory.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+pub async fn find_by_id(
+    player_key: Uuid,
+    conn: &DatabaseConnection,
+) -> Result<Option<Model>, DbErr> {
+    Entity::find_by_id(player_key).one(conn).await
+}
+
+pub async fn get_name(
+    player_key: Option<Uuid>,
+    conn: &DatabaseConnection,
+) -> Result<Option<String>, DbErr> {
+    let player = match player_key {
+        Some(player_key) => find_by_id(player_key, conn).await?,
+        None => None,
+    };
+
+    Ok(player.and_then(|player| player.name))
+}
+
+#[derive(Clone, Debug, FromQueryResult, Serialize)]
+pub struct RankedPlayer {
+    pub uuid: Uuid,
+    pub name: Option<String>,
+    pub rating: f64,
+    pub rated_games: i32,
+}
+
+pub async fn find_ranked(
+    limit: usize,
+    offset: usize,
+    conn: &DatabaseConnection,
+) -> Result<Vec<RankedPlayer>, DbErr> {
+    // backed by the index on player.rating
+    let stmt = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        SELECT p.uuid, COALESCE(p.name, a.username) AS name, p.rating, p.rated_games
+        FROM player p
+        LEFT JOIN account a ON a.uuid = p.uuid
+        WHERE p.rated_games > 0
+        ORDER BY p.rating DESC, p.uuid
+        LIMIT $1 OFFSET $2
+        "#,
+        vec![(limit as i64).into(), (offset as i64).into()],
+    );
+
+    RankedPlayer::find_by_statement(stmt).all(conn).await
+}
+
+pub async fn get_display_name(
+    player_key: Uuid,
+    conn: &DatabaseConnection,
+) -> Result<Option<String>, DbErr> {
+    // a player's chosen name, or else their account's username
+    match get_name(Some(player_key), conn).await? {
+        Some(name) => Ok(Some(name)),
+        None => Ok(super::account::find_by_id(player_key, conn)
+            .await?
+            .map(|account| account.username)),
+    }
+}
+
+pub async fn get_rating(
+    player_key: Option<Uuid>,
+    conn: &DatabaseConnection,
+) -> Result<Option<i64>, DbErr> {
+    // seated players who haven't played a rated game yet have the default rating
+    let rating = match player_key {
+        Some(player_key) => Some(
+            find_by_id(player_key, conn)
+                .await?
+                .map(|player| player.rating)
+                .unwrap_or(rating::DEFAULT_RATING),
+        ),
+        None => None,
+    };
+
+    Ok(rating.map(|rating| rating.round() as i64))
+}
+
+pub async fn set_name(
+    player_key: Uuid,
+    name: String,
+    conn: &DatabaseConnection,
+) -> Result<Model, DbErr> {
+    // assumes that name has already been validated
+    match find_by_id(player_key, conn).await? {
+        Some(player) => {
+            let mut player: ActiveModel = player.into();
+            player.name = Set(Some(name));
+            player.update(conn).await
+        }
+        None => {
+            let player = ActiveModel {
+                uuid: Set(player_key),
+                created_at: Set(Utc::now().with_timezone(&FixedOffset::east(0))),
+                name: Set(Some(name)),
+                ..Default::default()
+            };
+            player.insert(conn).await
+        }
+    }
+}
+
+async fn find_or_create_for_update(
+    player_key: Uuid,
+    txn: &DatabaseTransaction,
+) -> Result<Model, DbErr> {
+    // make sure there's a row first, without failing if another game makes it at the same time
+    let stmt = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        INSERT INTO player (uuid, created_at, name, rating, rated_games)
+        VALUES ($1, $2, NULL, $3, 0)
+        ON CONFLICT (uuid) DO NOTHING
+        "#,
+        vec![
+            player_key.into(),
+            Utc::now().with_timezone(&FixedOffset::east(0)).into(),
+            rating::DEFAULT_RATING.into(),
+        ],
+    );
+    txn.execute(stmt).await?;
+
+    // then lock it until the transaction ends
+    // so concurrent games can't overwrite each other's rating updates
+    Entity::find_by_id(player_key)
+        .lock_exclusive()
+        .one(txn)
+        .await?
+        .ok_or_else(|| DbErr::RecordNotFound(format!("player {}", player_key)))
+}
+
+pub async fn update_ratings(
+    game_id: Uuid,
+    player1_key: Uuid,
+    player2_key: Uuid,
+    score: f64,
+    txn: &DatabaseTransaction,
+) -> Result<(), DbErr> {
+    // score is from player 1's point of view
+    // 1 -- player 1 won, 0.5 -- draw, 0 -- player 2 won
+    // always lock in the same order, to avoid deadlocking with another game
+    let (player1, player2) = if player1_key < player2_key {
+        let player1 = find_or_create_for_update(player1_key, txn).await?;
+        let player2 = find_or_create_for_update(player2_key, txn).await?;
+        (player1, player2)
+    } else {
+        let player2 = find_or_create_for_update(player2_key, txn).await?;
+        let player1 = find_or_create_for_update(player1_key, txn).await?;
+        (player1, player2)
+    };
+
+    let (rating1, rating2) = rating::rate(
+        Rating {
+            rating: player1.rating,
+            rated_games: player1.rated_games,
+        },
+        Rating {
+            rating: player2.rating,
+            rated_games: player2.rated_games,
+        },
+        score,
+    );
+
+    for (player, new_rating) in [(player1, rating1), (player2, rating2)] {
+        super::rating_history::create(game_id, &player, new_rating.rating, txn).await?;
+
+        let mut player: ActiveModel = player.into();
+        player.rating = Set(new_rating.rating);
+        player.rated_games = Set(new_rating.rated_games);
+        player.update(txn).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn move_rating(
+    from_key: Uuid,
+    into_key: Uuid,
+    txn: &DatabaseTransaction,
+) -> Result<bool, DbErr> {
+    // hands from_key's rating, and the history behind it, over to into_key
+    // as long as into_key hasn't played a rated game of its own
+    // (two ratings can't be combined, so then nothing is moved and false is returned)
+    let (from, into) = if from_key < into_key {
+        let from = find_or_create_for_update(from_key, txn).await?;
+        let into = find_or_create_for_update(into_key, txn).await?;
+        (from, into)
+    } else {
+        let into = find_or_create_for_update(into_key, txn).await?;
+        let from = find_or_create_for_update(from_key, txn).await?;
+        (from, into)
+    };
+
+    if into.rated_games > 0 {
+        return Ok(from.rated_games == 0);
+    }
+    if from.rated_games == 0 {
+        return Ok(true);
+    }
+
+    super::rating_history::Entity::update_many()
+        .col_expr(
+            super::rating_history::Column::PlayerKey,
+            Expr::value(into_key),
+        )
+        .filter(super::rating_history::Column::PlayerKey.eq(from_key))
+        .exec(txn)
+        .await?;
+
+    let (rating, rated_games) = (from.rating, from.rated_games);
+    let mut from: ActiveModel = from.into();
+    from.rating = Set(rating::DEFAULT_RATING);
+    from.rated_games = Set(0);
+    from.update(txn).await?;
+
+    let mut into: ActiveModel = into.into();
+    into.rating = Set(rating);
+    into.rated_games = Set(rated_games);
+    into.update(txn).await?;
+
+    Ok(true)
+}
+
+pub fn validate_name(name: &str) -> Result<String, &'static str> {
+    // names go straight into the play page and into ws messages
+    // so keep them short, single-line and free of markup
+    let name = name.trim();
+
+    if name.is_empty() {
+        return Err("name cannot be empty");
+    }
+
+    if name.chars().count() > NAME_MAX_LEN {
+        return Err("name is too long");
+    }
+
+    if !name
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == ' ' || c == '_' || c == '-' || c == '.')
+    {
+        return Err("name can only contain letters, digits, spaces, '_', '-' and '.'");
+    }
+
+    if name.contains("  ") {
+        return Err("name cannot contain consecutive spaces");
+    }
+
+    Ok(name.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn valid_names_are_trimmed() {
+        assert_eq!(validate_name("  Ada  "), Ok(String::from("Ada")));
+        assert_eq!(validate_name("mr_x-2.0"), Ok(String::from("mr_x-2.0")));
+        assert_eq!(validate_name("Zoë Ọ"), Ok(String::from("Zoë Ọ")));
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        assert!(validate_name("").is_err());
+        assert!(validate_name("   ").is_err());
+        assert!(validate_name("a very long name that goes on").is_err());
+        assert!(validate_name("<b>bold</b>").is_err());
+        assert!(validate_name("two  spaces").is_err());
+        assert!(validate_name("new\nline").is_err());
+    }
+}
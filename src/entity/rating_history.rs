@@ -0,0 +1,65 @@
+use chrono::{FixedOffset, Utc};
+use sea_orm::entity::prelude::*;
+use sea_orm::{DatabaseTransaction, DbErr, Set};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "rating_history")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub player_key: Uuid,
+    pub game_id: Uuid,
+    pub created_at: DateTimeWithTimeZone,
+    pub rating_before: f64,
+    pub rating_after: f64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::player::Entity",
+        from = "Column::PlayerKey",
+        to = "super::player::Column::Uuid"
+    )]
+    Player,
+    #[sea_orm(
+        belongs_to = "super::game::Entity",
+        from = "Column::GameId",
+        to = "super::game::Column::Uuid"
+    )]
+    Game,
+}
+
+impl Related<super::player::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Player.def()
+    }
+}
+
+impl Related<super::game::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Game.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+pub async fn create(
+    game_id: Uuid,
+    player: &super::player::Model,
+    rating_after: f64,
+    txn: &DatabaseTransaction,
+) -> Result<Model, DbErr> {
+    let entry = ActiveModel {
+        player_key: Set(player.uuid),
+        game_id: Set(game_id),
+        created_at: Set(Utc::now().with_timezone(&FixedOffset::east(0))),
+        rating_before: Set(player.rating),
+        rating_after: Set(rating_after),
+        ..Default::default()
+    };
+
+    entry.insert(txn).await
+}
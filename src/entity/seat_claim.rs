@@ -0,0 +1,101 @@
+use chrono::{Duration, FixedOffset, Utc};
+use rand::RngCore;
+use sea_orm::entity::prelude::*;
+use sea_orm::sea_query::Expr;
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbErr, Set};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub const CLAIM_TTL_MINUTES: i64 = 10;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "seat_claim")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub token: String,
+    pub game_id: Uuid,
+    pub player_num: i16,
+    pub created_at: DateTimeWithTimeZone,
+    pub expires_at: DateTimeWithTimeZone,
+    pub used_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::game::Entity",
+        from = "Column::GameId",
+        to = "super::game::Column::Uuid"
+    )]
+    Game,
+}
+
+impl Related<super::game::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Game.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+pub async fn create(
+    game_id: Uuid,
+    player_num: usize,
+    conn: &DatabaseConnection,
+) -> Result<Model, DbErr> {
+    let mut token = [0u8; 24];
+    rand::thread_rng().fill_bytes(&mut token);
+
+    let now = Utc::now().with_timezone(&FixedOffset::east(0));
+    let claim = ActiveModel {
+        token: Set(base64::encode_config(token, base64::URL_SAFE_NO_PAD)),
+        game_id: Set(game_id),
+        player_num: Set(player_num as i16),
+        created_at: Set(now),
+        expires_at: Set(now + Duration::minutes(CLAIM_TTL_MINUTES)),
+        used_at: Set(None),
+    };
+
+    claim.insert(conn).await
+}
+
+pub async fn find_usable(token: &str, conn: &DatabaseConnection) -> Result<Option<Model>, DbErr> {
+    let now = Utc::now().with_timezone(&FixedOffset::east(0));
+    Entity::find_by_id(token.to_string())
+        .filter(Column::UsedAt.is_null())
+        .filter(Column::ExpiresAt.gt(now))
+        .one(conn)
+        .await
+}
+
+pub async fn redeem(
+    claim: Model,
+    game: &super::game::Model,
+    player_key: Uuid,
+    conn: &DatabaseConnection,
+) -> Result<bool, DbErr> {
+    // marks the claim as used and moves its seat to player_key, or does neither
+    // each step checks that it can still be made in the same statement that makes it,
+    // so a link can never be redeemed twice, and two links can't both take the seat
+    let txn = conn.begin().await?;
+
+    let now = Utc::now().with_timezone(&FixedOffset::east(0));
+    let res = Entity::update_many()
+        .col_expr(Column::UsedAt, Expr::value(now))
+        .filter(Column::Token.eq(claim.token))
+        .filter(Column::UsedAt.is_null())
+        .filter(Column::ExpiresAt.gt(now))
+        .exec(&txn)
+        .await?;
+
+    let player_num = claim.player_num as usize;
+    if res.rows_affected != 1 || !super::game::move_seat(game, player_num, player_key, &txn).await?
+    {
+        txn.rollback().await?;
+        return Ok(false);
+    }
+
+    txn.commit().await?;
+
+    Ok(true)
+}
@@ -1,16 +1,63 @@
 use sea_orm::error::DbErr;
 use sea_orm::sea_query::value::Value;
-use sea_orm::sea_query::{ColumnDef, TableCreateStatement};
-use sea_orm::{sea_query, ConnectionTrait, DbConn, ExecResult};
+use sea_orm::sea_query::{
+    ColumnDef, Iden, IndexCreateStatement, PostgresQueryBuilder, TableCreateStatement,
+};
+use sea_orm::{sea_query, ConnectionTrait, DbConn, ExecResult, Statement};
 use sea_query::foreign_key::{ForeignKey, ForeignKeyAction};
 
-use super::{board, game};
+use super::{
+    account, account_session, annotation, board, game, player, rating_history, seat_claim, takeback,
+};
+use crate::rating;
 
 async fn create_table(conn: &DbConn, stmt: &TableCreateStatement) -> Result<ExecResult, DbErr> {
     let builder = conn.get_database_backend();
     conn.execute(builder.build(stmt)).await
 }
 
+async fn add_columns<T>(conn: &DbConn, table: T, columns: Vec<ColumnDef>) -> Result<(), DbErr>
+where
+    T: Iden + Copy + 'static,
+{
+    // tables created by an earlier version won't have the newer columns
+    // sea-query can't build `ADD COLUMN IF NOT EXISTS` yet,
+    // so patch it in, to make this safe to run on every start
+    let builder = conn.get_database_backend();
+
+    for mut column in columns {
+        let stmt = sea_query::Table::alter()
+            .table(table)
+            .add_column(&mut column)
+            .to_owned();
+        let sql = builder
+            .build(&stmt)
+            .sql
+            .replacen("ADD COLUMN", "ADD COLUMN IF NOT EXISTS", 1);
+
+        conn.execute(Statement::from_string(builder, sql)).await?;
+    }
+
+    Ok(())
+}
+
+async fn create_indexes(conn: &DbConn, indexes: Vec<IndexCreateStatement>) -> Result<(), DbErr> {
+    // same story as add_columns -- no `CREATE INDEX IF NOT EXISTS` in sea-query yet
+    let builder = conn.get_database_backend();
+
+    for index in indexes {
+        let sql = index.to_string(PostgresQueryBuilder).replacen(
+            "CREATE INDEX",
+            "CREATE INDEX IF NOT EXISTS",
+            1,
+        );
+
+        conn.execute(Statement::from_string(builder, sql)).await?;
+    }
+
+    Ok(())
+}
+
 pub async fn create_game_table(conn: &DbConn) -> Result<ExecResult, DbErr> {
     let stmt = sea_query::Table::create()
         .table(game::Entity)
@@ -40,6 +87,85 @@ pub async fn create_game_table(conn: &DbConn) -> Result<ExecResult, DbErr> {
     create_table(conn, &stmt).await
 }
 
+pub async fn upgrade_game_table(conn: &DbConn) -> Result<(), DbErr> {
+    let columns = vec![
+        ColumnDef::new(game::Column::IsRated)
+            .boolean()
+            .not_null()
+            .default(Value::Bool(Some(false)))
+            .to_owned(),
+        ColumnDef::new(game::Column::WinnerNum)
+            .small_integer()
+            .to_owned(),
+        ColumnDef::new(game::Column::ResultReason)
+            .string()
+            .to_owned(),
+        ColumnDef::new(game::Column::IsPublic)
+            .boolean()
+            .not_null()
+            .default(Value::Bool(Some(false)))
+            .to_owned(),
+        ColumnDef::new(game::Column::PreviousGameId)
+            .uuid()
+            .to_owned(),
+        ColumnDef::new(game::Column::TakebackLimit)
+            .small_integer()
+            .not_null()
+            .default(Value::Int(Some(0)))
+            .to_owned(),
+        ColumnDef::new(game::Column::ClockInitialSecs)
+            .integer()
+            .to_owned(),
+        ColumnDef::new(game::Column::ClockIncrementSecs)
+            .integer()
+            .to_owned(),
+        ColumnDef::new(game::Column::Player1TimeLeftMs)
+            .big_integer()
+            .to_owned(),
+        ColumnDef::new(game::Column::Player2TimeLeftMs)
+            .big_integer()
+            .to_owned(),
+        ColumnDef::new(game::Column::ClockUpdatedAt)
+            .timestamp_with_time_zone()
+            .to_owned(),
+        ColumnDef::new(game::Column::MoveDeadlineHours)
+            .integer()
+            .to_owned(),
+        ColumnDef::new(game::Column::MoveDeadlineAt)
+            .timestamp_with_time_zone()
+            .to_owned(),
+        ColumnDef::new(game::Column::Player1Engine)
+            .string()
+            .to_owned(),
+        ColumnDef::new(game::Column::Player2Engine)
+            .string()
+            .to_owned(),
+        ColumnDef::new(game::Column::EngineMoveSecs)
+            .integer()
+            .to_owned(),
+        ColumnDef::new(game::Column::AnalyzedAt)
+            .timestamp_with_time_zone()
+            .to_owned(),
+        ColumnDef::new(game::Column::Player1Hints)
+            .small_integer()
+            .not_null()
+            .default(Value::Int(Some(0)))
+            .to_owned(),
+        ColumnDef::new(game::Column::Player2Hints)
+            .small_integer()
+            .not_null()
+            .default(Value::Int(Some(0)))
+            .to_owned(),
+        ColumnDef::new(game::Column::BoardSize)
+            .small_integer()
+            .not_null()
+            .default(Value::Int(Some(game::DEFAULT_BOARD_SIZE.into())))
+            .to_owned(),
+    ];
+
+    add_columns(conn, game::Entity, columns).await
+}
+
 pub async fn create_board_table(conn: &DbConn) -> Result<ExecResult, DbErr> {
     let stmt = sea_query::Table::create()
         .table(board::Entity)
@@ -70,3 +196,377 @@ pub async fn create_board_table(conn: &DbConn) -> Result<ExecResult, DbErr> {
 
     create_table(conn, &stmt).await
 }
+
+pub async fn create_player_table(conn: &DbConn) -> Result<ExecResult, DbErr> {
+    let stmt = sea_query::Table::create()
+        .table(player::Entity)
+        .if_not_exists()
+        .col(
+            ColumnDef::new(player::Column::Uuid)
+                .uuid()
+                .not_null()
+                .primary_key(),
+        )
+        .col(
+            ColumnDef::new(player::Column::CreatedAt)
+                .timestamp_with_time_zone()
+                .not_null(),
+        )
+        .col(ColumnDef::new(player::Column::Name).string_len(player::NAME_MAX_LEN as u32))
+        .to_owned();
+
+    create_table(conn, &stmt).await
+}
+
+pub async fn upgrade_player_table(conn: &DbConn) -> Result<(), DbErr> {
+    let columns = vec![
+        ColumnDef::new(player::Column::Rating)
+            .double()
+            .not_null()
+            .default(Value::Double(Some(rating::DEFAULT_RATING)))
+            .to_owned(),
+        ColumnDef::new(player::Column::RatedGames)
+            .integer()
+            .not_null()
+            .default(Value::Int(Some(0)))
+            .to_owned(),
+    ];
+
+    add_columns(conn, player::Entity, columns).await
+}
+
+pub async fn create_rating_history_table(conn: &DbConn) -> Result<ExecResult, DbErr> {
+    let stmt = sea_query::Table::create()
+        .table(rating_history::Entity)
+        .if_not_exists()
+        .col(
+            ColumnDef::new(rating_history::Column::Id)
+                .integer()
+                .not_null()
+                .auto_increment()
+                .primary_key(),
+        )
+        .col(
+            ColumnDef::new(rating_history::Column::PlayerKey)
+                .uuid()
+                .not_null(),
+        )
+        .foreign_key(
+            ForeignKey::create()
+                .name("FK_rating_history_player")
+                .from(rating_history::Entity, rating_history::Column::PlayerKey)
+                .to(player::Entity, player::Column::Uuid)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade),
+        )
+        .col(
+            ColumnDef::new(rating_history::Column::GameId)
+                .uuid()
+                .not_null(),
+        )
+        .foreign_key(
+            ForeignKey::create()
+                .name("FK_rating_history_game")
+                .from(rating_history::Entity, rating_history::Column::GameId)
+                .to(game::Entity, game::Column::Uuid)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade),
+        )
+        .col(
+            ColumnDef::new(rating_history::Column::CreatedAt)
+                .timestamp_with_time_zone()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(rating_history::Column::RatingBefore)
+                .double()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(rating_history::Column::RatingAfter)
+                .double()
+                .not_null(),
+        )
+        .to_owned();
+
+    create_table(conn, &stmt).await
+}
+
+pub async fn create_account_table(conn: &DbConn) -> Result<ExecResult, DbErr> {
+    let stmt = sea_query::Table::create()
+        .table(account::Entity)
+        .if_not_exists()
+        .col(
+            ColumnDef::new(account::Column::Uuid)
+                .uuid()
+                .not_null()
+                .primary_key(),
+        )
+        .col(
+            ColumnDef::new(account::Column::CreatedAt)
+                .timestamp_with_time_zone()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(account::Column::Username)
+                .string_len(account::USERNAME_MAX_LEN as u32)
+                .not_null()
+                .unique_key(),
+        )
+        .col(
+            ColumnDef::new(account::Column::PasswordHash)
+                .string()
+                .not_null(),
+        )
+        .to_owned();
+
+    create_table(conn, &stmt).await
+}
+
+pub async fn create_account_session_table(conn: &DbConn) -> Result<ExecResult, DbErr> {
+    let stmt = sea_query::Table::create()
+        .table(account_session::Entity)
+        .if_not_exists()
+        .col(
+            ColumnDef::new(account_session::Column::Uuid)
+                .uuid()
+                .not_null()
+                .primary_key(),
+        )
+        .col(
+            ColumnDef::new(account_session::Column::AccountId)
+                .uuid()
+                .not_null(),
+        )
+        .foreign_key(
+            ForeignKey::create()
+                .name("FK_account_session_account")
+                .from(account_session::Entity, account_session::Column::AccountId)
+                .to(account::Entity, account::Column::Uuid)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade),
+        )
+        .col(
+            ColumnDef::new(account_session::Column::CreatedAt)
+                .timestamp_with_time_zone()
+                .not_null(),
+        )
+        .to_owned();
+
+    create_table(conn, &stmt).await
+}
+
+pub async fn create_seat_claim_table(conn: &DbConn) -> Result<ExecResult, DbErr> {
+    let stmt = sea_query::Table::create()
+        .table(seat_claim::Entity)
+        .if_not_exists()
+        .col(
+            ColumnDef::new(seat_claim::Column::Token)
+                .string()
+                .not_null()
+                .primary_key(),
+        )
+        .col(ColumnDef::new(seat_claim::Column::GameId).uuid().not_null())
+        .foreign_key(
+            ForeignKey::create()
+                .name("FK_seat_claim_game")
+                .from(seat_claim::Entity, seat_claim::Column::GameId)
+                .to(game::Entity, game::Column::Uuid)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade),
+        )
+        .col(
+            ColumnDef::new(seat_claim::Column::PlayerNum)
+                .small_integer()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(seat_claim::Column::CreatedAt)
+                .timestamp_with_time_zone()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(seat_claim::Column::ExpiresAt)
+                .timestamp_with_time_zone()
+                .not_null(),
+        )
+        .col(ColumnDef::new(seat_claim::Column::UsedAt).timestamp_with_time_zone())
+        .to_owned();
+
+    create_table(conn, &stmt).await
+}
+
+pub async fn create_takeback_table(conn: &DbConn) -> Result<ExecResult, DbErr> {
+    let stmt = sea_query::Table::create()
+        .table(takeback::Entity)
+        .if_not_exists()
+        .col(
+            ColumnDef::new(takeback::Column::Id)
+                .integer()
+                .not_null()
+                .auto_increment()
+                .primary_key(),
+        )
+        .col(ColumnDef::new(takeback::Column::GameId).uuid().not_null())
+        .foreign_key(
+            ForeignKey::create()
+                .name("FK_takeback_game")
+                .from(takeback::Entity, takeback::Column::GameId)
+                .to(game::Entity, game::Column::Uuid)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade),
+        )
+        .col(
+            ColumnDef::new(takeback::Column::PlayerNum)
+                .small_integer()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(takeback::Column::Plies)
+                .small_integer()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(takeback::Column::CreatedAt)
+                .timestamp_with_time_zone()
+                .not_null(),
+        )
+        .to_owned();
+
+    create_table(conn, &stmt).await
+}
+
+pub async fn create_annotation_table(conn: &DbConn) -> Result<ExecResult, DbErr> {
+    let stmt = sea_query::Table::create()
+        .table(annotation::Entity)
+        .if_not_exists()
+        .col(
+            ColumnDef::new(annotation::Column::Id)
+                .integer()
+                .not_null()
+                .auto_increment()
+                .primary_key(),
+        )
+        .col(ColumnDef::new(annotation::Column::GameId).uuid().not_null())
+        .foreign_key(
+            ForeignKey::create()
+                .name("FK_annotation_game")
+                .from(annotation::Entity, annotation::Column::GameId)
+                .to(game::Entity, game::Column::Uuid)
+                .on_delete(ForeignKeyAction::Cascade)
+                .on_update(ForeignKeyAction::Cascade),
+        )
+        .col(
+            ColumnDef::new(annotation::Column::Ply)
+                .small_integer()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(annotation::Column::PlayerNum)
+                .small_integer()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(annotation::Column::PlayRow)
+                .small_integer()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(annotation::Column::PlayCol)
+                .small_integer()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(annotation::Column::BestRow)
+                .small_integer()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(annotation::Column::BestCol)
+                .small_integer()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(annotation::Column::Advantage)
+                .small_integer()
+                .not_null(),
+        )
+        .col(ColumnDef::new(annotation::Column::Judgement).string())
+        .to_owned();
+
+    create_table(conn, &stmt).await
+}
+
+pub async fn create_lookup_indexes(conn: &DbConn) -> Result<(), DbErr> {
+    // for the leaderboard, lobby and player profile pages,
+    // which list a player's games and rating history newest first,
+    // and for following a game to its rematch
+    // or counting the takebacks made in it,
+    // and for finding overdue correspondence moves and games left to analyze
+    let indexes = vec![
+        sea_query::Index::create()
+            .name("idx_game_player1_key_created_at")
+            .table(game::Entity)
+            .col(game::Column::Player1Key)
+            .col(game::Column::CreatedAt)
+            .to_owned(),
+        sea_query::Index::create()
+            .name("idx_game_player2_key_created_at")
+            .table(game::Entity)
+            .col(game::Column::Player2Key)
+            .col(game::Column::CreatedAt)
+            .to_owned(),
+        sea_query::Index::create()
+            .name("idx_game_is_public_ended_at")
+            .table(game::Entity)
+            .col(game::Column::IsPublic)
+            .col(game::Column::EndedAt)
+            .to_owned(),
+        sea_query::Index::create()
+            .name("idx_game_previous_game_id")
+            .table(game::Entity)
+            .col(game::Column::PreviousGameId)
+            .to_owned(),
+        sea_query::Index::create()
+            .name("idx_game_move_deadline_at")
+            .table(game::Entity)
+            .col(game::Column::MoveDeadlineAt)
+            .to_owned(),
+        sea_query::Index::create()
+            .name("idx_board_game_id_created_at")
+            .table(board::Entity)
+            .col(board::Column::GameId)
+            .col(board::Column::CreatedAt)
+            .to_owned(),
+        sea_query::Index::create()
+            .name("idx_player_rating")
+            .table(player::Entity)
+            .col(player::Column::Rating)
+            .to_owned(),
+        sea_query::Index::create()
+            .name("idx_rating_history_player_key_created_at")
+            .table(rating_history::Entity)
+            .col(rating_history::Column::PlayerKey)
+            .col(rating_history::Column::CreatedAt)
+            .to_owned(),
+        sea_query::Index::create()
+            .name("idx_takeback_game_id")
+            .table(takeback::Entity)
+            .col(takeback::Column::GameId)
+            .to_owned(),
+        sea_query::Index::create()
+            .name("idx_game_analyzed_at_ended_at")
+            .table(game::Entity)
+            .col(game::Column::AnalyzedAt)
+            .col(game::Column::EndedAt)
+            .to_owned(),
+        sea_query::Index::create()
+            .name("idx_annotation_game_id_ply")
+            .table(annotation::Entity)
+            .col(annotation::Column::GameId)
+            .col(annotation::Column::Ply)
+            .to_owned(),
+    ];
+
+    create_indexes(conn, indexes).await
+}
@@ -0,0 +1,116 @@
+use chrono::{FixedOffset, Utc};
+use sea_orm::entity::prelude::*;
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbErr, QueryOrder, QuerySelect, Set};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "takeback")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub game_id: Uuid,
+    // the player whose move was taken back
+    pub player_num: i16,
+    // how many plies were removed
+    // (against the computer, its reply goes too)
+    pub plies: i16,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::game::Entity",
+        from = "Column::GameId",
+        to = "super::game::Column::Uuid"
+    )]
+    Game,
+}
+
+impl Related<super::game::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Game.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+pub async fn count_for_game(game_id: Uuid, conn: &DatabaseConnection) -> Result<usize, DbErr> {
+    Entity::find()
+        .filter(Column::GameId.eq(game_id))
+        .count(conn)
+        .await
+}
+
+pub async fn take_back(
+    game_id: Uuid,
+    player_num: usize,
+    plies: usize,
+    latest_board_id: i32,
+    conn: &DatabaseConnection,
+) -> Result<Option<super::board::Model>, DbErr> {
+    // removes the last plies from the game's history and records the takeback
+    // returns the board the game goes back to,
+    // or None if the takeback can no longer be made
+    // (e.g. the game ended, the limit was reached, or someone moved in the meantime)
+    let txn = conn.begin().await?;
+
+    // lock the game, so concurrent takebacks are counted one at a time
+    let game = match super::game::Entity::find_by_id(game_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+    {
+        Some(game) if game.ended_at.is_none() && !game.is_rated => game,
+        _ => {
+            txn.rollback().await?;
+            return Ok(None);
+        }
+    };
+
+    let used = Entity::find()
+        .filter(Column::GameId.eq(game_id))
+        .count(&txn)
+        .await?;
+    if used >= game.takeback_limit.max(0) as usize {
+        txn.rollback().await?;
+        return Ok(None);
+    }
+
+    // the removed plies, and the board before them
+    // (the initial board is never removed)
+    let boards = super::board::Entity::find()
+        .filter(super::board::Column::GameId.eq(game_id))
+        .order_by_desc(super::board::Column::CreatedAt)
+        .order_by_desc(super::board::Column::Id)
+        .limit(plies as u64 + 1)
+        .all(&txn)
+        .await?;
+    if boards.len() != plies + 1 || boards[0].id != latest_board_id {
+        txn.rollback().await?;
+        return Ok(None);
+    }
+
+    let removed_ids = boards[..plies]
+        .iter()
+        .map(|board| board.id)
+        .collect::<Vec<_>>();
+    super::board::Entity::delete_many()
+        .filter(super::board::Column::Id.is_in(removed_ids))
+        .exec(&txn)
+        .await?;
+
+    let takeback = ActiveModel {
+        game_id: Set(game_id),
+        player_num: Set(player_num as i16),
+        plies: Set(plies as i16),
+        created_at: Set(Utc::now().with_timezone(&FixedOffset::east(0))),
+        ..Default::default()
+    };
+    takeback.insert(&txn).await?;
+
+    txn.commit().await?;
+
+    Ok(boards.into_iter().nth(plies))
+}
@@ -0,0 +1,71 @@
+// what's done to a game from more than one place
+//
+// moves and results come in over the websocket, but the schedulers end games too
+// (e.g. when a correspondence move is overdue), so both go through here
+
+use std::time::Instant;
+
+use sea_orm::DatabaseConnection;
+
+use crate::channels::GameChannels;
+use crate::entity;
+use crate::entity::game::ResultReason;
+use crate::handlers::message::LobbyMessage;
+
+pub async fn end_game(
+    game: entity::game::Model,
+    winner_num: usize,
+    reason: ResultReason,
+    conn: &DatabaseConnection,
+    game_channels: &GameChannels,
+) -> Result<(), String> {
+    // records the result and lets everyone know
+    // (unless something else has ended the game in the meantime)
+    let game_id = game.uuid;
+    let ended_game = entity::game::finish(game, winner_num, reason, conn)
+        .await
+        .map_err(|err| format!("Database error: {}", err))?;
+
+    let ended_game = match ended_game {
+        Some(ended_game) => ended_game,
+        None => return Ok(()),
+    };
+
+    let room = game_channels.ensure_room(game_id);
+    let _ = room
+        .channel
+        .send(format!("End {} {}", winner_num, reason.as_str()));
+
+    // the clocks stop with the game
+    let now = Instant::now();
+    let clock_reading = room.clock.lock().unwrap().as_mut().map(|clock| {
+        clock.stop(now);
+        clock.read(now)
+    });
+    if let Some(clock_reading) = clock_reading {
+        entity::game::save_clock(game_id, clock_reading.time_left_ms, conn)
+            .await
+            .map_err(|err| format!("Database error: {}", err))?;
+        let _ = room.channel.send(clock_reading.write());
+    }
+
+    if ended_game.is_public {
+        let message = LobbyMessage::Closed { game_id };
+        let _ = game_channels.lobby_channel().send(message.write());
+    }
+
+    Ok(())
+}
+
+pub async fn get_board_state(
+    game: &entity::game::Model,
+    conn: &DatabaseConnection,
+) -> Result<Vec<Vec<u8>>, String> {
+    let game_board = entity::game::get_most_recent_board(game, conn)
+        .await
+        .map_err(|err| format!("Database error: {}", err))?
+        .ok_or(format!("could not find board for game: {}", game.uuid))?;
+
+    Ok(serde_json::from_value(game_board.state.clone())
+        .unwrap_or_else(|_| panic!("could not deserialize game board:\n{:?}", game_board.state)))
+}
@@ -0,0 +1,207 @@
+use axum::extract::{Extension, Form};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use sea_orm::DatabaseConnection;
+use tera::{Context, Tera};
+
+use super::dto;
+use super::error::{handle_db_error, handle_template_error};
+use super::http::SITE_NAME;
+use crate::cookies::Cookies;
+use crate::entity;
+
+pub async fn register_form(
+    Extension(ref templates): Extension<Tera>,
+    cookies: Cookies,
+) -> Result<Response, (StatusCode, String)> {
+    if cookies.is_logged_in() {
+        return Ok(Redirect::to("/".parse().unwrap()).into_response());
+    }
+
+    render_account_form("account/register.html.tera", None, None, templates)
+        .map(IntoResponse::into_response)
+}
+
+pub async fn register(
+    Extension(ref conn): Extension<DatabaseConnection>,
+    Extension(ref templates): Extension<Tera>,
+    Form(payload): Form<dto::AccountPayload>,
+    mut cookies: Cookies,
+) -> Result<Response, (StatusCode, String)> {
+    let template_name = "account/register.html.tera";
+    let username_taken = "username is already taken";
+
+    if cookies.is_logged_in() {
+        return Ok(Redirect::to("/".parse().unwrap()).into_response());
+    }
+
+    let username = match entity::account::validate_username(&payload.username) {
+        Ok(username) => username,
+        Err(error) => {
+            return render_account_form(template_name, Some(&payload), Some(error), templates)
+                .map(IntoResponse::into_response)
+        }
+    };
+    if let Err(error) = entity::account::validate_password(&payload.password) {
+        return render_account_form(template_name, Some(&payload), Some(error), templates)
+            .map(IntoResponse::into_response);
+    }
+
+    let existing_account = entity::account::find_by_username(&username, conn)
+        .await
+        .map_err(handle_db_error)?;
+    if existing_account.is_some() {
+        return render_account_form(
+            template_name,
+            Some(&payload),
+            Some(username_taken),
+            templates,
+        )
+        .map(IntoResponse::into_response);
+    }
+
+    let password = payload.password.clone();
+    let password_hash =
+        tokio::task::spawn_blocking(move || entity::account::hash_password(&password))
+            .await
+            .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?
+            .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err))?;
+
+    // the account takes over the current session's key
+    // so every game played so far as a guest is kept
+    // (the check above can race with another sign-up, so the insert has the final say)
+    let player_key = cookies.session_id;
+    let is_created = entity::account::create(player_key, username, password_hash, conn)
+        .await
+        .map_err(handle_db_error)?;
+    if !is_created {
+        return render_account_form(
+            template_name,
+            Some(&payload),
+            Some(username_taken),
+            templates,
+        )
+        .map(IntoResponse::into_response);
+    }
+    let session = entity::account_session::create(player_key, conn)
+        .await
+        .map_err(handle_db_error)?;
+    cookies.log_in(&session);
+
+    Ok(Redirect::to("/".parse().unwrap()).into_response())
+}
+
+pub async fn login_form(
+    Extension(ref templates): Extension<Tera>,
+    cookies: Cookies,
+) -> Result<Response, (StatusCode, String)> {
+    if cookies.is_logged_in() {
+        return Ok(Redirect::to("/".parse().unwrap()).into_response());
+    }
+
+    render_account_form("account/login.html.tera", None, None, templates)
+        .map(IntoResponse::into_response)
+}
+
+pub async fn login(
+    Extension(ref conn): Extension<DatabaseConnection>,
+    Extension(ref templates): Extension<Tera>,
+    Form(payload): Form<dto::AccountPayload>,
+    mut cookies: Cookies,
+) -> Result<Response, (StatusCode, String)> {
+    let template_name = "account/login.html.tera";
+    let invalid_credentials = "invalid username or password";
+
+    let account = entity::account::find_by_username(&payload.username, conn)
+        .await
+        .map_err(handle_db_error)?;
+
+    // unknown usernames still have a password checked
+    // so they can't be told apart from wrong passwords by how long they take
+    let password = payload.password.clone();
+    let password_hash = account
+        .as_ref()
+        .map(|account| account.password_hash.clone());
+    let is_verified = tokio::task::spawn_blocking(move || match password_hash {
+        Some(password_hash) => entity::account::verify_password(&password, &password_hash),
+        None => entity::account::reject_password(&password),
+    })
+    .await
+    .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+    let account = match account {
+        Some(account) if is_verified => account,
+        _ => {
+            return render_account_form(
+                template_name,
+                Some(&payload),
+                Some(invalid_credentials),
+                templates,
+            )
+            .map(IntoResponse::into_response)
+        }
+    };
+
+    // merge a guest's games, name and rating into the account (see game::merge_player)
+    // but never merge one account into another
+    let guest_key = cookies.session_id;
+    if guest_key != account.uuid && !cookies.is_logged_in() {
+        entity::game::merge_player(guest_key, account.uuid, conn)
+            .await
+            .map_err(handle_db_error)?;
+
+        let account_name = entity::player::get_name(Some(account.uuid), conn)
+            .await
+            .map_err(handle_db_error)?;
+        let guest_name = entity::player::get_name(Some(guest_key), conn)
+            .await
+            .map_err(handle_db_error)?;
+        if let (None, Some(guest_name)) = (account_name, guest_name) {
+            entity::player::set_name(account.uuid, guest_name, conn)
+                .await
+                .map_err(handle_db_error)?;
+        }
+    }
+
+    // every login gets a session of its own, which logging out revokes
+    let session = entity::account_session::create(account.uuid, conn)
+        .await
+        .map_err(handle_db_error)?;
+    cookies.log_in(&session);
+
+    Ok(Redirect::to("/".parse().unwrap()).into_response())
+}
+
+pub async fn logout(
+    Extension(ref conn): Extension<DatabaseConnection>,
+    mut cookies: Cookies,
+) -> Result<Response, (StatusCode, String)> {
+    if let Some(session_id) = cookies.account_session_id() {
+        entity::account_session::revoke(session_id, conn)
+            .await
+            .map_err(handle_db_error)?;
+    }
+    cookies.log_out();
+
+    Ok(Redirect::to("/".parse().unwrap()).into_response())
+}
+
+fn render_account_form(
+    template_name: &str,
+    payload: Option<&dto::AccountPayload>,
+    error: Option<&str>,
+    templates: &Tera,
+) -> Result<Html<String>, (StatusCode, String)> {
+    let mut context = Context::new();
+    context.insert("site_name", SITE_NAME);
+    context.insert("username", &payload.map(|payload| payload.username.trim()));
+    context.insert("error", &error);
+    context.insert("username_max_len", &entity::account::USERNAME_MAX_LEN);
+    context.insert("password_min_len", &entity::account::PASSWORD_MIN_LEN);
+    context.insert("password_max_len", &entity::account::PASSWORD_MAX_LEN);
+
+    let body = templates
+        .render(template_name, &context)
+        .map_err(handle_template_error)?;
+
+    Ok(Html(body))
+}
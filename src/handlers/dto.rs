@@ -5,4 +5,54 @@ use serde::Deserialize;
 #[derive(Deserialize, Debug)]
 pub struct GameCreationPayload {
     pub is_against_ai: Option<bool>,
+    pub is_rated: Option<bool>,
+    pub is_public: Option<bool>,
+    // 1 or 2 to play in that seat, 3 for either at random, 0 to just watch
+    pub creator_seat: Option<usize>,
+    // ignored in rated games, which allow no takebacks
+    pub takeback_limit: Option<i16>,
+    // minutes+seconds (e.g. 3+2), hours or days per move (e.g. 12h, 3d)
+    // or nothing for an untimed game
+    pub time_control: Option<String>,
+    // the name of the engine the computer plays with (e.g. mcts)
+    // or nothing for the default one
+    pub engine: Option<String>,
+    // when the creator just watches the computer play itself
+    // the engine it plays white with, and how many seconds each engine gets per move
+    pub engine2: Option<String>,
+    pub engine_move_secs: Option<i32>,
+    // the number of rows, and of cells in each, in games against the computer
+    pub board_size: Option<i16>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SeatPayload {
+    pub seat: usize,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AccountPayload {
+    pub username: String,
+    pub password: String,
+}
+
+// page numbers start at 1
+#[derive(Deserialize, Debug)]
+pub struct PageQuery {
+    pub page: Option<usize>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ProfileQuery {
+    pub games_page: Option<usize>,
+    pub opponents_page: Option<usize>,
+}
+
+// players are only matched with players who asked for the same settings
+#[derive(Deserialize, Debug)]
+pub struct MatchmakingQuery {
+    pub is_rated: Option<bool>,
+    // as when creating a game
+    pub time_control: Option<String>,
+    pub takeback_limit: Option<i16>,
 }
@@ -20,6 +20,14 @@ pub fn handle_not_found_error(error: String) -> (StatusCode, String) {
     (StatusCode::NOT_FOUND, error)
 }
 
+pub fn handle_forbidden_error(error: String) -> (StatusCode, String) {
+    (StatusCode::FORBIDDEN, error)
+}
+
+pub fn handle_conflict_error(error: String) -> (StatusCode, String) {
+    (StatusCode::CONFLICT, error)
+}
+
 pub async fn handle_staticfiles_server_error(error: std::io::Error) -> (StatusCode, String) {
     (
         StatusCode::INTERNAL_SERVER_ERROR,
@@ -1,28 +1,68 @@
 use std::sync::Arc;
+use std::time::Instant;
 
-use axum::extract::{Extension, Form, Path};
+use axum::extract::{Extension, Form, Path, Query};
 use axum::http::StatusCode;
 use axum::response::{Html, IntoResponse, Redirect};
-use sea_orm::prelude::*;
-use sea_orm::{DatabaseConnection, DbErr, Set};
+use sea_orm::{DatabaseConnection, DbErr};
 use tera::{Context, Tera};
 use url::Url;
 use uuid::Uuid;
 
 use super::dto;
-use super::error::{handle_db_error, handle_not_found_error, handle_template_error};
+use super::error::{
+    handle_conflict_error, handle_db_error, handle_forbidden_error, handle_not_found_error,
+    handle_template_error,
+};
+use super::message::LobbyMessage;
+use super::ws::start_computer;
 use crate::channels::GameChannels;
+use crate::clock::{get_clock_running_num, with_clock, TimeControl};
 use crate::cookies::Cookies;
+use crate::correspondence;
+use crate::engine::tablebase::Tablebases;
+use crate::engine::Engines;
 use crate::entity;
+use crate::matchmaking;
 
-const SITE_NAME: &str = "Stacky Sides";
+pub const SITE_NAME: &str = "Stacky Sides";
+const INVALID_CLAIM: &str = "This link is invalid, has expired or was already used";
 
 pub async fn index(
+    Extension(ref conn): Extension<DatabaseConnection>,
     Extension(ref templates): Extension<Tera>,
-    _cookies: Cookies,
+    Extension(ref engines): Extension<Arc<Engines>>,
+    Extension(ref tablebases): Extension<Arc<Tablebases>>,
+    cookies: Cookies,
 ) -> Result<Html<String>, (StatusCode, String)> {
+    let account = entity::account::find_by_id(cookies.session_id, conn)
+        .await
+        .map_err(handle_db_error)?;
+
     let mut context = Context::new();
     context.insert("site_name", SITE_NAME);
+    context.insert("account", &account);
+    context.insert(
+        "default_takeback_limit",
+        &entity::game::DEFAULT_TAKEBACK_LIMIT,
+    );
+    context.insert("max_takeback_limit", &entity::game::MAX_TAKEBACK_LIMIT);
+    context.insert("engines", &engines.choices());
+    context.insert(
+        "default_engine_move_secs",
+        &entity::game::DEFAULT_ENGINE_MOVE_SECS,
+    );
+    // the boards the computer will play on, largest first
+    // (noting the ones it has a tablebase for)
+    let board_sizes = (entity::game::MIN_BOARD_SIZE..=entity::game::DEFAULT_BOARD_SIZE)
+        .rev()
+        .map(|size| {
+            let is_solved = tablebases.find(size as usize, size as usize).is_some();
+            serde_json::json!({ "size": size, "is_solved": is_solved })
+        })
+        .collect::<Vec<_>>();
+    context.insert("board_sizes", &board_sizes);
+    context.insert("default_board_size", &entity::game::DEFAULT_BOARD_SIZE);
 
     let body = templates
         .render("game/index.html.tera", &context)
@@ -34,23 +74,60 @@ pub async fn index(
 pub async fn create_game(
     Extension(ref conn): Extension<DatabaseConnection>,
     Extension(game_channels): Extension<Arc<GameChannels>>,
+    Extension(engines): Extension<Arc<Engines>>,
     Form(payload): Form<dto::GameCreationPayload>,
     cookies: Cookies,
 ) -> impl IntoResponse {
-    let game = entity::game::create(
-        cookies.session_id,
-        conn,
+    let settings = entity::game::GameSettings::new(
         payload.is_against_ai.unwrap_or(false),
+        payload.is_rated.unwrap_or(false),
+        payload.is_public.unwrap_or(false),
+        payload
+            .takeback_limit
+            .unwrap_or(entity::game::DEFAULT_TAKEBACK_LIMIT),
+        payload.time_control.as_deref().and_then(TimeControl::parse),
+        payload
+            .time_control
+            .as_deref()
+            .and_then(correspondence::parse_move_deadline),
     )
-    .await;
+    .with_board_size(payload.board_size);
+    // against a person, the computer plays with the same engine whichever seat it takes
+    // and a creator who just watches sets two computers playing each other
+    let creator_seat = payload.creator_seat.unwrap_or(1);
+    let engine = payload
+        .engine
+        .filter(|engine| engines.find(engine).is_some());
+    let settings = match creator_seat {
+        0 => settings
+            .with_engines([
+                engine,
+                payload
+                    .engine2
+                    .filter(|engine| engines.find(engine).is_some()),
+            ])
+            .with_engine_move_secs(Some(
+                payload
+                    .engine_move_secs
+                    .unwrap_or(entity::game::DEFAULT_ENGINE_MOVE_SECS),
+            )),
+        _ => settings.with_engines([engine.clone(), engine]),
+    };
+    let game = entity::game::create(cookies.session_id, creator_seat, conn, settings).await;
 
-    if let Err(_) = &game {
+    if game.is_err() {
         return Redirect::temporary("/".parse().unwrap());
     }
 
     let game = game.unwrap();
     let path = format!("/game/{}/share", game.uuid);
     game_channels.insert_channel(game.uuid);
+    // the computer doesn't wait to be asked when it moves first
+    start_computer(&game, &engines, conn, &game_channels);
+
+    if game.is_public {
+        let _ = notify_lobby(game.uuid, conn, &game_channels).await;
+    }
 
     Redirect::to(path.parse().unwrap())
 }
@@ -74,6 +151,8 @@ pub async fn share_game(
     let mut context = Context::new();
     context.insert("game_url", &game_url);
     context.insert("is_against_ai", &game.is_against_ai);
+    context.insert("is_exhibition", &game.is_exhibition());
+    context.insert("is_public", &game.is_public);
     context.insert("site_name", SITE_NAME);
     let body = templates
         .render("game/share.html.tera", &context)
@@ -82,11 +161,72 @@ pub async fn share_game(
     Ok(Html(body))
 }
 
+pub async fn lobby(
+    Extension(ref conn): Extension<DatabaseConnection>,
+    Extension(ref base_url): Extension<Url>,
+    Extension(ref templates): Extension<Tera>,
+) -> Result<Html<String>, (StatusCode, String)> {
+    let games = entity::game::find_open_public(None, conn)
+        .await
+        .map_err(handle_db_error)?;
+
+    let lobby_ws_url = get_ws_url_for_path(String::from("/ws/lobby"), base_url.clone());
+
+    let mut context = Context::new();
+    context.insert("site_name", SITE_NAME);
+    context.insert("games", &games);
+    context.insert("lobby_ws_url", &lobby_ws_url);
+    let body = templates
+        .render("game/lobby.html.tera", &context)
+        .map_err(handle_template_error)?;
+
+    Ok(Html(body))
+}
+
+pub async fn waiting_room(
+    Query(query): Query<dto::MatchmakingQuery>,
+    Extension(ref base_url): Extension<Url>,
+    Extension(ref templates): Extension<Tera>,
+) -> Result<Html<String>, (StatusCode, String)> {
+    // the waiting room asks for the settings it was opened with
+    let settings = matchmaking::get_settings(
+        query.is_rated.unwrap_or(false),
+        query.time_control.as_deref(),
+        query.takeback_limit,
+    );
+    let mut ws_query = url::form_urlencoded::Serializer::new(String::new());
+    ws_query.append_pair("is_rated", &settings.is_rated.to_string());
+    ws_query.append_pair("takeback_limit", &settings.takeback_limit.to_string());
+    let time_control = describe_time_control(settings.time_control, settings.move_deadline_hours);
+    if let Some(text) = query
+        .time_control
+        .as_deref()
+        .filter(|_| time_control.is_some())
+    {
+        ws_query.append_pair("time_control", text);
+    }
+    let path = format!("/ws/matchmaking?{}", ws_query.finish());
+    let matchmaking_ws_url = get_ws_url_for_path(path, base_url.clone());
+
+    let mut context = Context::new();
+    context.insert("site_name", SITE_NAME);
+    context.insert("is_rated", &settings.is_rated);
+    context.insert("time_control", &time_control);
+    context.insert("matchmaking_ws_url", &matchmaking_ws_url);
+    let body = templates
+        .render("game/waiting.html.tera", &context)
+        .map_err(handle_template_error)?;
+
+    Ok(Html(body))
+}
+
 pub async fn play_game(
     Path(game_id): Path<Uuid>,
     Extension(ref conn): Extension<DatabaseConnection>,
     Extension(ref base_url): Extension<Url>,
+    Extension(game_channels): Extension<Arc<GameChannels>>,
     Extension(ref templates): Extension<Tera>,
+    Extension(ref engines): Extension<Arc<Engines>>,
     cookies: Cookies,
 ) -> Result<Html<String>, (StatusCode, String)> {
     let game = entity::game::find_by_id(game_id, conn)
@@ -101,18 +241,90 @@ pub async fn play_game(
         .map_err(handle_not_found_error)?;
 
     let is_against_ai = game.is_against_ai;
-    let is_game_over = match game.ended_at {
-        None => false,
-        _ => true,
+    let computer_names = get_computer_names(&game, engines);
+    let is_game_over = game.ended_at.is_some();
+    let is_rated = game.is_rated;
+
+    let player_names = [
+        entity::player::get_name(game.player1_key, conn)
+            .await
+            .map_err(handle_db_error)?,
+        entity::player::get_name(game.player2_key, conn)
+            .await
+            .map_err(handle_db_error)?,
+    ];
+    let own_name = entity::player::get_name(Some(cookies.session_id), conn)
+        .await
+        .map_err(handle_db_error)?;
+
+    let player_ratings = match is_rated {
+        true => [
+            entity::player::get_rating(game.player1_key, conn)
+                .await
+                .map_err(handle_db_error)?,
+            entity::player::get_rating(game.player2_key, conn)
+                .await
+                .map_err(handle_db_error)?,
+        ],
+        _ => [None, None],
     };
 
-    // assign player number
     // 1 -- player 1, black
     // 2 -- player 2, white
     // 0 -- observer
-    let player_num = get_assigned_player_number(game, cookies.session_id, conn)
+    // opening the link doesn't take a seat -- observers choose to, see take_seat
+    let player_num = entity::game::get_player_num(&game, cookies.session_id);
+    let free_seats = match player_num == 0 && !is_against_ai && !is_game_over {
+        true => [(1, game.player1_key), (2, game.player2_key)]
+            .into_iter()
+            .filter(|(_, key)| key.is_none())
+            .map(|(num, _)| num)
+            .collect(),
+        _ => vec![],
+    };
+
+    let next_game_id = match is_game_over {
+        true => entity::game::find_rematch(game_id, conn)
+            .await
+            .map_err(handle_db_error)?
+            .map(|next_game| next_game.uuid),
+        _ => None,
+    };
+
+    // offers still waiting on an answer, for whoever (re)loads the page
+    let offers = {
+        let room = game_channels.ensure_room(game_id);
+        let offers = room.offers.lock().unwrap();
+        serde_json::json!({
+            "rematch": offers.rematch,
+            "draw": offers.draw,
+            "takeback": offers.takeback,
+        })
+    };
+
+    // a player who left and hasn't come back, so their opponent may settle the game
+    let abandoned_by = game_channels
+        .ensure_room(game_id)
+        .presence
+        .lock()
+        .unwrap()
+        .abandoned_by;
+
+    let takebacks_used = entity::takeback::count_for_game(game_id, conn)
         .await
         .map_err(handle_db_error)?;
+    let takebacks_left = (game.takeback_limit.max(0) as usize).saturating_sub(takebacks_used);
+
+    // the clocks as they stand, for timed games
+    let board_state: Vec<Vec<u8>> = serde_json::from_value(game_board.state.clone())
+        .unwrap_or_else(|_| panic!("could not deserialize game board:\n{:?}", game_board.state));
+    let clock = with_clock(
+        &game,
+        get_clock_running_num(&board_state),
+        &game_channels.ensure_room(game_id),
+        |clock| clock.read(Instant::now()),
+    );
+    let time_control = describe_time_control(game.time_control(), game.move_deadline_hours);
 
     let path = format!("/ws/game/{}/play", game_id);
     let game_ws_url = get_ws_url_for_path(path, base_url.clone());
@@ -120,11 +332,34 @@ pub async fn play_game(
     let mut context = Context::new();
     context.insert("site_name", SITE_NAME);
     context.insert("is_against_ai", &is_against_ai);
+    context.insert("computer_names", &computer_names);
+    context.insert("hints_used", &[game.player1_hints, game.player2_hints]);
+    context.insert("game_id", &game_id);
     context.insert("player_num", &player_num);
+    context.insert("free_seats", &free_seats);
+    context.insert("player_names", &player_names);
+    context.insert("player_ratings", &player_ratings);
+    context.insert("is_rated", &is_rated);
+    context.insert("own_name", &own_name);
+    context.insert("name_max_len", &entity::player::NAME_MAX_LEN);
     context.insert("game_board_data", &game_board.state);
     context.insert("is_game_over", &is_game_over);
-    context.insert("game_board_width", &7);
-    context.insert("game_board_height", &7);
+    context.insert(
+        "game_result",
+        &game.winner_num.map(|winner_num| {
+            serde_json::json!({ "winner_num": winner_num, "reason": game.result_reason })
+        }),
+    );
+    context.insert("offers", &offers);
+    context.insert("takebacks_left", &takebacks_left);
+    context.insert("abandoned_by", &abandoned_by);
+    context.insert("clock", &clock);
+    context.insert("time_control", &time_control);
+    context.insert("move_deadline_at", &game.move_deadline_at);
+    context.insert("previous_game_id", &game.previous_game_id);
+    context.insert("next_game_id", &next_game_id);
+    context.insert("game_board_width", &board_state.first().map_or(0, Vec::len));
+    context.insert("game_board_height", &board_state.len());
     context.insert("game_ws_url", &game_ws_url);
     let body = templates
         .render("game/play.html.tera", &context)
@@ -133,57 +368,318 @@ pub async fn play_game(
     Ok(Html(body))
 }
 
-async fn get_assigned_player_number(
-    game: entity::game::Model,
-    session_id: Uuid,
+pub async fn replay_game(
+    Path(game_id): Path<Uuid>,
+    Extension(ref conn): Extension<DatabaseConnection>,
+    Extension(ref templates): Extension<Tera>,
+    Extension(ref engines): Extension<Arc<Engines>>,
+    Extension(ref tablebases): Extension<Arc<Tablebases>>,
+) -> Result<Html<String>, (StatusCode, String)> {
+    // the game ply by ply, with what the post-game analysis made of each
+    let game = entity::game::find_by_id(game_id, conn)
+        .await
+        .map_err(handle_db_error)?
+        .ok_or(format!("Game not found: {}", game_id))
+        .map_err(handle_not_found_error)?;
+    let boards = entity::board::find_for_game(game_id, conn)
+        .await
+        .map_err(handle_db_error)?
+        .into_iter()
+        .map(|board| board.state)
+        .collect::<Vec<_>>();
+    let annotations = entity::annotation::find_for_game(game_id, conn)
+        .await
+        .map_err(handle_db_error)?;
+
+    let player_names = [
+        entity::player::get_name(game.player1_key, conn)
+            .await
+            .map_err(handle_db_error)?,
+        entity::player::get_name(game.player2_key, conn)
+            .await
+            .map_err(handle_db_error)?,
+    ];
+
+    let mut context = Context::new();
+    context.insert("site_name", SITE_NAME);
+    context.insert("game_id", &game_id);
+    context.insert("is_game_over", &game.ended_at.is_some());
+    context.insert("is_analyzed", &game.analyzed_at.is_some());
+    // games on boards with a tablebase are analyzed exactly
+    let size = game.board_size as usize;
+    context.insert("is_solved", &tablebases.find(size, size).is_some());
+    context.insert("player_names", &player_names);
+    context.insert("computer_names", &get_computer_names(&game, engines));
+    context.insert(
+        "game_result",
+        &game.winner_num.map(|winner_num| {
+            serde_json::json!({ "winner_num": winner_num, "reason": game.result_reason })
+        }),
+    );
+    context.insert("boards", &boards);
+    context.insert("annotations", &annotations);
+    let body = templates
+        .render("game/replay.html.tera", &context)
+        .map_err(handle_template_error)?;
+
+    Ok(Html(body))
+}
+
+fn get_computer_names(game: &entity::game::Model, engines: &Engines) -> [Option<String>; 2] {
+    // what the computer goes by in its seats
+    // (by its engine's name, when it plays itself)
+    [1, 2].map(|num| match game.is_computer(num) {
+        true if game.is_exhibition() => {
+            Some(engines.find_or_default(game.engine(num)).name().to_string())
+        }
+        true => Some(String::from("Computer")),
+        _ => None,
+    })
+}
+
+pub async fn take_seat(
+    Path(game_id): Path<Uuid>,
+    Extension(ref conn): Extension<DatabaseConnection>,
+    Extension(game_channels): Extension<Arc<GameChannels>>,
+    Form(payload): Form<dto::SeatPayload>,
+    cookies: Cookies,
+) -> Result<Redirect, (StatusCode, String)> {
+    let game = entity::game::find_by_id(game_id, conn)
+        .await
+        .map_err(handle_db_error)?
+        .ok_or(format!("Game not found: {}", game_id))
+        .map_err(handle_not_found_error)?;
+    let path = format!("/game/{}/play", game_id);
+
+    // already seated? (e.g. a resubmitted form)
+    if entity::game::get_player_num(&game, cookies.session_id) != 0 {
+        return Ok(Redirect::to(path.parse().unwrap()));
+    }
+
+    let is_seated = entity::game::take_seat(game_id, payload.seat, cookies.session_id, conn)
+        .await
+        .map_err(handle_db_error)?;
+    if !is_seated {
+        return Err(handle_conflict_error(String::from(
+            "This seat has already been taken, or the game has ended",
+        )));
+    }
+
+    let channel_tx = game_channels.ensure_channel(game_id);
+    let _ = channel_tx.send(format!("Seated {}", payload.seat));
+
+    // with both seats taken, a correspondence game's deadline starts running
+    let game = entity::game::find_by_id(game_id, conn)
+        .await
+        .map_err(handle_db_error)?
+        .ok_or(format!("Game not found: {}", game_id))
+        .map_err(handle_not_found_error)?;
+    correspondence::restart_move_deadline(&game, conn, &channel_tx)
+        .await
+        .map_err(handle_db_error)?;
+    let name = entity::player::get_name(Some(cookies.session_id), conn)
+        .await
+        .map_err(handle_db_error)?;
+    if let Some(name) = name {
+        let _ = channel_tx.send(format!("Name {} {}", payload.seat, name));
+    }
+
+    if game.is_public {
+        notify_lobby(game_id, conn, &game_channels)
+            .await
+            .map_err(handle_db_error)?;
+    }
+
+    Ok(Redirect::to(path.parse().unwrap()))
+}
+
+async fn notify_lobby(
+    game_id: Uuid,
     conn: &DatabaseConnection,
-) -> Result<usize, DbErr> {
-    // find next unassigned `player key` in game
-    // set it to session_id
-    // and return whether this makes owner of session_id player 1 or 2
-    // (or 0, which is what all non-playing observers are)
-    let res = match (game.player1_key, game.player2_key) {
-        (None, None) => assign_player(game, conn, session_id, 1).await?,
-        (None, Some(key2)) => match key2 == session_id {
-            true => 2,
-            _ => match game.is_against_ai {
-                true => 0,
-                _ => assign_player(game, conn, session_id, 1).await?,
-            },
-        },
-        (Some(key1), None) => match key1 == session_id {
-            true => 1,
-            _ => match game.is_against_ai {
-                true => 0,
-                _ => assign_player(game, conn, session_id, 2).await?,
-            },
-        },
-        (Some(key1), Some(key2)) => match key1 == session_id {
-            true => 1,
-            _ => match key2 == session_id {
-                true => 2,
-                _ => 0,
-            },
-        },
+    game_channels: &GameChannels,
+) -> Result<(), DbErr> {
+    // (re)lists a public game in the lobby while it has a free seat
+    // and takes it off once it doesn't
+    let lobby_game = entity::game::find_open_public(Some(game_id), conn)
+        .await?
+        .into_iter()
+        .next();
+
+    let message = match lobby_game {
+        Some(game) => LobbyMessage::Open { game },
+        None => LobbyMessage::Closed { game_id },
     };
+    let _ = game_channels.lobby_channel().send(message.write());
+
+    Ok(())
+}
+
+pub async fn create_seat_claim(
+    Path(game_id): Path<Uuid>,
+    Extension(ref conn): Extension<DatabaseConnection>,
+    cookies: Cookies,
+) -> Result<Redirect, (StatusCode, String)> {
+    let game = entity::game::find_by_id(game_id, conn)
+        .await
+        .map_err(handle_db_error)?
+        .ok_or(format!("Game not found: {}", game_id))
+        .map_err(handle_not_found_error)?;
+
+    let player_num = entity::game::get_player_num(&game, cookies.session_id);
+    if player_num == 0 || game.ended_at.is_some() {
+        return Err(handle_forbidden_error(String::from(
+            "Only a player in an ongoing game can move their seat",
+        )));
+    }
+
+    let claim = entity::seat_claim::create(game.uuid, player_num, conn)
+        .await
+        .map_err(handle_db_error)?;
 
-    Ok(res)
+    // the link is shown on a page of its own, so refreshing it doesn't make another one
+    let path = format!("/game/{}/claim/{}", game.uuid, claim.token);
+    Ok(Redirect::to(path.parse().unwrap()))
 }
 
-async fn assign_player(
-    game: entity::game::Model,
+pub async fn show_seat_claim(
+    Path((game_id, token)): Path<(Uuid, String)>,
+    Extension(ref base_url): Extension<Url>,
+    Extension(ref conn): Extension<DatabaseConnection>,
+    Extension(ref templates): Extension<Tera>,
+    cookies: Cookies,
+) -> Result<Html<String>, (StatusCode, String)> {
+    // only the player whose seat the link moves gets to see it
+    let claim = entity::seat_claim::find_usable(&token, conn)
+        .await
+        .map_err(handle_db_error)?
+        .filter(|claim| claim.game_id == game_id)
+        .ok_or_else(|| String::from(INVALID_CLAIM))
+        .map_err(handle_not_found_error)?;
+    let game = entity::game::find_by_id(game_id, conn)
+        .await
+        .map_err(handle_db_error)?
+        .ok_or(format!("Game not found: {}", game_id))
+        .map_err(handle_not_found_error)?;
+    if entity::game::get_player_num(&game, cookies.session_id) != claim.player_num as usize {
+        return Err(handle_not_found_error(String::from(INVALID_CLAIM)));
+    }
+
+    let path = format!("claim/{}", claim.token);
+    let claim_url = base_url.join(&path).unwrap();
+
+    let mut context = Context::new();
+    context.insert("site_name", SITE_NAME);
+    context.insert("claim_url", &claim_url);
+    context.insert("claim_ttl_minutes", &entity::seat_claim::CLAIM_TTL_MINUTES);
+    context.insert("game_id", &game.uuid);
+    let body = templates
+        .render("game/claim.html.tera", &context)
+        .map_err(handle_template_error)?;
+
+    Ok(Html(body))
+}
+
+async fn find_seat_claim(
+    token: &str,
     conn: &DatabaseConnection,
     session_id: Uuid,
-    player_num: usize,
-) -> Result<usize, DbErr> {
-    let mut game: entity::game::ActiveModel = game.into();
-    match player_num {
-        1 => game.player1_key = Set(Some(session_id)),
-        2 => game.player2_key = Set(Some(session_id)),
-        _ => panic!("cannot assign player with key greater than 2 or less than 1"),
+) -> Result<(entity::seat_claim::Model, entity::game::Model), (StatusCode, String)> {
+    // the claim behind a link, and its game, as long as the link could move a seat to session_id
+    let claim = entity::seat_claim::find_usable(token, conn)
+        .await
+        .map_err(handle_db_error)?
+        .ok_or_else(|| String::from(INVALID_CLAIM))
+        .map_err(handle_not_found_error)?;
+
+    let game = entity::game::find_by_id(claim.game_id, conn)
+        .await
+        .map_err(handle_db_error)?
+        .ok_or(format!("Game not found: {}", claim.game_id))
+        .map_err(handle_not_found_error)?;
+
+    let player_num = entity::game::get_player_num(&game, session_id);
+    if player_num != 0 && player_num != claim.player_num as usize {
+        return Err(handle_forbidden_error(String::from(
+            "You are already playing the other seat in this game",
+        )));
+    }
+
+    if game.ended_at.is_some() {
+        return Err(handle_forbidden_error(String::from(
+            "This game has already ended",
+        )));
+    }
+
+    Ok((claim, game))
+}
+
+pub async fn confirm_seat_claim(
+    Path(token): Path<String>,
+    Extension(ref conn): Extension<DatabaseConnection>,
+    Extension(ref templates): Extension<Tera>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    // opening the link only asks to confirm
+    // (so link previews and prefetching don't use it up)
+    let (claim, game) = find_seat_claim(&token, conn, cookies.session_id).await?;
+
+    // opening the link on the same browser changes nothing
+    let player_num = claim.player_num as usize;
+    if entity::game::get_player_num(&game, cookies.session_id) == player_num {
+        let path = format!("/game/{}/play", game.uuid);
+        return Ok(Redirect::to(path.parse().unwrap()).into_response());
+    }
+
+    let mut context = Context::new();
+    context.insert("site_name", SITE_NAME);
+    context.insert("token", &claim.token);
+    context.insert("player_num", &player_num);
+    let body = templates
+        .render("game/claim_seat.html.tera", &context)
+        .map_err(handle_template_error)?;
+
+    Ok(Html(body).into_response())
+}
+
+pub async fn claim_seat(
+    Path(token): Path<String>,
+    Extension(ref conn): Extension<DatabaseConnection>,
+    Extension(game_channels): Extension<Arc<GameChannels>>,
+    cookies: Cookies,
+) -> Result<Redirect, (StatusCode, String)> {
+    let (claim, game) = find_seat_claim(&token, conn, cookies.session_id).await?;
+    let game_id = game.uuid;
+    let path = format!("/game/{}/play", game_id);
+
+    // confirming on the same browser changes nothing
+    let player_num = claim.player_num as usize;
+    if entity::game::get_player_num(&game, cookies.session_id) == player_num {
+        return Ok(Redirect::to(path.parse().unwrap()));
+    }
+
+    let is_redeemed = entity::seat_claim::redeem(claim, &game, cookies.session_id, conn)
+        .await
+        .map_err(handle_db_error)?;
+    if !is_redeemed {
+        return Err(handle_not_found_error(String::from(INVALID_CLAIM)));
+    }
+
+    // let the seat's previous device know it no longer holds the seat
+    let channel_tx = game_channels.ensure_channel(game_id);
+    let _ = channel_tx.send(format!("Claimed {}", player_num));
+
+    Ok(Redirect::to(path.parse().unwrap()))
+}
+
+fn describe_time_control(
+    time_control: Option<TimeControl>,
+    move_deadline_hours: Option<i32>,
+) -> Option<String> {
+    match (time_control, move_deadline_hours) {
+        (Some(time_control), _) => Some(time_control.to_string()),
+        (_, Some(hours)) => Some(correspondence::describe_move_deadline(hours)),
+        _ => None,
     }
-    game.update(conn).await?;
-    Ok(player_num)
 }
 
 fn get_ws_url_for_path(path: String, mut base_url: Url) -> String {
@@ -1,41 +1,322 @@
+use uuid::Uuid;
+
+use crate::entity::game::LobbyGame;
+
 #[derive(Debug)]
 pub enum GameMessage {
     // -- OUTGOING MESSAGES
     // state_str is in the form -- state [[...], [...], ..., [...]]
-    Board { state_str: String },
-    // ending_str is in the form -- end x
+    Board {
+        state_str: String,
+    },
+    // ending_str is in the form -- end x reason
     // where x is either 1 or 2, representing which player won
     // x is 0 in the case of a draw
-    End { ending_str: String },
+    // and reason is how the game ended (e.g. connect, resignation)
+    End {
+        ending_str: String,
+    },
+    // name_str is in the form -- name x some name
+    // where x is either 1 or 2, representing the renamed player
+    Name {
+        name_str: String,
+    },
+    // claimed_str is in the form -- claimed x
+    // where x is either 1 or 2, representing the seat that moved to another device
+    Claimed {
+        player_num: u8,
+        claimed_str: String,
+    },
+    // seated_str is in the form -- seated x
+    // where x is either 1 or 2, representing the seat an observer just took
+    Seated {
+        seated_str: String,
+    },
+    // offer_str is in the form -- offer y x
+    // where y is what is on offer (e.g. rematch, draw)
+    // and x is either 1 or 2, representing the player who offered it
+    Offer {
+        offer_str: String,
+    },
+    // withdrawn_str is in the form -- withdrawn y x
+    // where x is the player whose offer of y no longer stands
+    // (e.g. they made a move instead of waiting for an answer)
+    Withdrawn {
+        withdrawn_str: String,
+    },
+    // declined_str is in the form -- declined y x
+    // where x is the player who turned down the offer of y
+    Declined {
+        declined_str: String,
+    },
+    // next_game_str is in the form -- nextgame uuid
+    // where uuid is the id of the rematch everyone should move on to
+    NextGame {
+        next_game_str: String,
+    },
+    // taken_back_str is in the form -- takenback x n
+    // where x is the player whose last move was taken back
+    // and n is how many takebacks are left in the game
+    // (the corrected board follows in a board message)
+    TakenBack {
+        taken_back_str: String,
+    },
+    // clock_str is in the form -- clock t1 t2 x
+    // where t1 and t2 are the milliseconds left on each player's clock
+    // and x is the player whose clock is running (0 if neither is)
+    Clock {
+        clock_str: String,
+    },
+    // deadline_str is in the form -- deadline time
+    // where time (RFC 3339) is when the player to move forfeits a correspondence game
+    Deadline {
+        deadline_str: String,
+    },
+    // abandoned_str is in the form -- abandoned x
+    // where x is the player who left during their turn and didn't come back in time
+    // (their opponent may now claim the win or abort the game)
+    Abandoned {
+        abandoned_str: String,
+    },
+    // returned_str is in the form -- returned x
+    // where x is the player who came back after abandoning the game
+    Returned {
+        returned_str: String,
+    },
+    // thinking_str is in the form -- thinking x
+    // where x is the seat of the computer, which is about to play
+    Thinking {
+        thinking_str: String,
+    },
+    // suggested_str is in the form -- suggested x row col reason n
+    // where x is the player who asked for a hint (and the only one told about it),
+    // row and col the suggested cell, reason what playing it does (win, block, threat, build)
+    // and n how many hints the player has had in the game
+    Suggested {
+        player_num: u8,
+        suggested_str: String,
+    },
     // -- INCOMING MESSAGES
-    Selection { row: u8, col: u8 },
+    Selection {
+        row: u8,
+        col: u8,
+    },
+    Rename {
+        name: String,
+    },
+    // offers a rematch, or accepts the one the opponent offered
+    Rematch,
+    Resign,
+    OfferDraw,
+    AcceptDraw,
+    DeclineDraw,
+    // asks the opponent to let the player take back their last move
+    RequestTakeback,
+    AcceptTakeback,
+    DeclineTakeback,
+    // settle a game the opponent has abandoned
+    ClaimWin,
+    Abort,
+    // asks the engine for a play, in unrated games
+    Hint,
 }
 
 impl GameMessage {
     pub fn read(text: String) -> Result<Self, &'static str> {
-        let text = text.to_lowercase();
+        // only the keyword is case-insensitive
+        // the rest of the message (e.g. a player's name) is kept as is
+        let (keyword, rest) = match text.split_once(' ') {
+            Some((keyword, rest)) => (keyword.to_lowercase(), rest),
+            None => (text.to_lowercase(), ""),
+        };
 
-        if text.starts_with("board") {
+        if keyword == "board" {
             return Ok(Self::Board { state_str: text });
         }
 
-        if text.starts_with("end") {
+        if keyword == "end" {
             return Ok(Self::End { ending_str: text });
         }
 
+        if keyword == "name" {
+            return Ok(Self::Name { name_str: text });
+        }
+
+        if keyword == "offer" {
+            return Ok(Self::Offer { offer_str: text });
+        }
+
+        if keyword == "withdrawn" {
+            return Ok(Self::Withdrawn {
+                withdrawn_str: text,
+            });
+        }
+
+        if keyword == "declined" {
+            return Ok(Self::Declined { declined_str: text });
+        }
+
+        if keyword == "nextgame" {
+            return Ok(Self::NextGame {
+                next_game_str: text,
+            });
+        }
+
+        if keyword == "takenback" {
+            return Ok(Self::TakenBack {
+                taken_back_str: text,
+            });
+        }
+
+        if keyword == "clock" {
+            return Ok(Self::Clock { clock_str: text });
+        }
+
+        if keyword == "deadline" {
+            return Ok(Self::Deadline { deadline_str: text });
+        }
+
+        if keyword == "abandoned" {
+            return Ok(Self::Abandoned {
+                abandoned_str: text,
+            });
+        }
+
+        if keyword == "returned" {
+            return Ok(Self::Returned { returned_str: text });
+        }
+
+        if keyword == "thinking" {
+            return Ok(Self::Thinking { thinking_str: text });
+        }
+
+        if keyword == "suggested" {
+            if let Some(Ok(player_num)) = rest.split(' ').next().map(str::parse) {
+                return Ok(Self::Suggested {
+                    player_num,
+                    suggested_str: text,
+                });
+            }
+            return Err("could not parse suggested message");
+        }
+
+        if keyword == "seated" {
+            return Ok(Self::Seated { seated_str: text });
+        }
+
+        if keyword == "claimed" {
+            if let Ok(player_num) = rest.parse() {
+                return Ok(Self::Claimed {
+                    player_num,
+                    claimed_str: text,
+                });
+            }
+            return Err("could not parse claimed message");
+        }
+
         // validate and process incoming messages
-        let parts = text.split(" ").collect::<Vec<&str>>();
-        if parts.len() == 0 {
+        if keyword.is_empty() {
             return Err("empty message!");
         }
 
-        if parts[0] == "selection" && parts.len() == 3 {
-            if let (Ok(row), Ok(col)) = (parts[1].parse(), parts[2].parse()) {
-                return Ok(GameMessage::Selection { row, col });
+        if keyword == "selection" {
+            let parts = rest.split(' ').collect::<Vec<&str>>();
+            if parts.len() == 2 {
+                if let (Ok(row), Ok(col)) = (parts[0].parse(), parts[1].parse()) {
+                    return Ok(GameMessage::Selection { row, col });
+                }
             }
             return Err("could not parse selection message");
         }
 
+        if keyword == "offerdraw" {
+            return Ok(GameMessage::OfferDraw);
+        }
+
+        if keyword == "acceptdraw" {
+            return Ok(GameMessage::AcceptDraw);
+        }
+
+        if keyword == "declinedraw" {
+            return Ok(GameMessage::DeclineDraw);
+        }
+
+        if keyword == "takeback" {
+            return Ok(GameMessage::RequestTakeback);
+        }
+
+        if keyword == "accepttakeback" {
+            return Ok(GameMessage::AcceptTakeback);
+        }
+
+        if keyword == "declinetakeback" {
+            return Ok(GameMessage::DeclineTakeback);
+        }
+
+        if keyword == "claimwin" {
+            return Ok(GameMessage::ClaimWin);
+        }
+
+        if keyword == "abort" {
+            return Ok(GameMessage::Abort);
+        }
+
+        if keyword == "hint" {
+            return Ok(GameMessage::Hint);
+        }
+
+        if keyword == "resign" {
+            return Ok(GameMessage::Resign);
+        }
+
+        if keyword == "rematch" {
+            return Ok(GameMessage::Rematch);
+        }
+
+        if keyword == "rename" {
+            return Ok(GameMessage::Rename {
+                name: rest.to_string(),
+            });
+        }
+
         Err("could not parse message")
     }
 }
+
+#[derive(Debug)]
+pub enum LobbyMessage {
+    // the lobby only ever talks to its clients
+    // open -- open {json for the game}
+    // a public game is waiting for an opponent
+    Open { game: LobbyGame },
+    // closed -- closed uuid
+    // a public game was filled or ended, so it leaves the lobby
+    Closed { game_id: Uuid },
+}
+
+impl LobbyMessage {
+    pub fn write(&self) -> String {
+        match self {
+            Self::Open { game } => format!(
+                "Open {}",
+                serde_json::to_string(game).expect("cannot serialize lobby game")
+            ),
+            Self::Closed { game_id } => format!("Closed {}", game_id),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum WaitingRoomMessage {
+    // matched -- matched /game/uuid/play
+    // the path of the game the player was matched into
+    Matched { game_id: Uuid },
+}
+
+impl WaitingRoomMessage {
+    pub fn write(&self) -> String {
+        match self {
+            Self::Matched { game_id } => format!("Matched /game/{}/play", game_id),
+        }
+    }
+}
@@ -1,6 +1,7 @@
+pub mod account;
 pub mod dto;
 pub mod error;
 pub mod http;
 pub mod message;
-pub mod utils;
+pub mod player;
 pub mod ws;
@@ -0,0 +1,238 @@
+use axum::extract::{Extension, Path, Query};
+use axum::http::StatusCode;
+use axum::response::Html;
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use tera::{Context, Tera};
+use uuid::Uuid;
+
+use super::dto;
+use super::error::{handle_db_error, handle_not_found_error, handle_template_error};
+use super::http::SITE_NAME;
+use crate::board::get_next_player_num;
+use crate::cookies::Cookies;
+use crate::correspondence;
+use crate::entity;
+use crate::rating::{self, Rating};
+
+const LEADERBOARD_PAGE_SIZE: usize = 25;
+const GAMES_PAGE_SIZE: usize = 10;
+const OPPONENTS_PAGE_SIZE: usize = 10;
+
+#[derive(Serialize)]
+struct Page {
+    number: usize,
+    has_previous: bool,
+    has_next: bool,
+}
+
+pub async fn leaderboard(
+    Query(query): Query<dto::PageQuery>,
+    Extension(ref conn): Extension<DatabaseConnection>,
+    Extension(ref templates): Extension<Tera>,
+) -> Result<Html<String>, (StatusCode, String)> {
+    let page_num = query.page.unwrap_or(1).max(1);
+    let offset =
+        get_page_offset(page_num, LEADERBOARD_PAGE_SIZE).map_err(handle_not_found_error)?;
+
+    let players = entity::player::find_ranked(LEADERBOARD_PAGE_SIZE + 1, offset, conn)
+        .await
+        .map_err(handle_db_error)?;
+    let (players, page) = take_page(players, page_num, LEADERBOARD_PAGE_SIZE);
+
+    let mut context = Context::new();
+    context.insert("site_name", SITE_NAME);
+    context.insert("players", &players);
+    context.insert("first_rank", &(offset + 1));
+    context.insert("provisional_games", &rating::PROVISIONAL_GAMES);
+    context.insert("page", &page);
+
+    let body = templates
+        .render("player/leaderboard.html.tera", &context)
+        .map_err(handle_template_error)?;
+
+    Ok(Html(body))
+}
+
+pub async fn player_profile(
+    Path(player_key): Path<Uuid>,
+    Query(query): Query<dto::ProfileQuery>,
+    Extension(ref conn): Extension<DatabaseConnection>,
+    Extension(ref templates): Extension<Tera>,
+) -> Result<Html<String>, (StatusCode, String)> {
+    let games_page_num = query.games_page.unwrap_or(1).max(1);
+    let opponents_page_num = query.opponents_page.unwrap_or(1).max(1);
+    let games_offset =
+        get_page_offset(games_page_num, GAMES_PAGE_SIZE).map_err(handle_not_found_error)?;
+    let opponents_offset =
+        get_page_offset(opponents_page_num, OPPONENTS_PAGE_SIZE).map_err(handle_not_found_error)?;
+
+    let games = entity::game::find_by_player(player_key, GAMES_PAGE_SIZE + 1, games_offset, conn)
+        .await
+        .map_err(handle_db_error)?;
+    let (games, games_page) = take_page(games, games_page_num, GAMES_PAGE_SIZE);
+
+    let player = entity::player::find_by_id(player_key, conn)
+        .await
+        .map_err(handle_db_error)?;
+
+    // guests who never named themselves only leave a trace in their games
+    if player.is_none() && games.is_empty() && games_page_num == 1 {
+        return Err(handle_not_found_error(format!(
+            "Player not found: {}",
+            player_key
+        )));
+    }
+
+    let name = entity::player::get_display_name(player_key, conn)
+        .await
+        .map_err(handle_db_error)?;
+
+    let player_rating = match player {
+        Some(player) => Rating {
+            rating: player.rating,
+            rated_games: player.rated_games,
+        },
+        None => Rating {
+            rating: rating::DEFAULT_RATING,
+            rated_games: 0,
+        },
+    };
+
+    let record = entity::game::get_record_for_player(player_key, conn)
+        .await
+        .map_err(handle_db_error)?;
+
+    let opponents = entity::game::get_head_to_heads(
+        player_key,
+        OPPONENTS_PAGE_SIZE + 1,
+        opponents_offset,
+        conn,
+    )
+    .await
+    .map_err(handle_db_error)?;
+    let (opponents, opponents_page) = take_page(opponents, opponents_page_num, OPPONENTS_PAGE_SIZE);
+
+    let mut context = Context::new();
+    context.insert("site_name", SITE_NAME);
+    context.insert("player_key", &player_key);
+    context.insert("name", &name);
+    context.insert("rating", &(player_rating.rating.round() as i64));
+    context.insert("rated_games", &player_rating.rated_games);
+    context.insert("is_provisional", &player_rating.is_provisional());
+    context.insert("record", &record);
+    context.insert("games", &games);
+    context.insert("games_page", &games_page);
+    context.insert("opponents", &opponents);
+    context.insert("opponents_page", &opponents_page);
+
+    let body = templates
+        .render("player/profile.html.tera", &context)
+        .map_err(handle_template_error)?;
+
+    Ok(Html(body))
+}
+
+fn get_page_offset(page_num: usize, page_size: usize) -> Result<usize, String> {
+    // how many rows come before the page
+    // (pages too far along for the database to count to can't exist)
+    page_num
+        .checked_sub(1)
+        .and_then(|pages| pages.checked_mul(page_size))
+        .filter(|&offset| i64::try_from(offset).is_ok())
+        .ok_or(format!("Page not found: {}", page_num))
+}
+
+#[derive(Serialize)]
+struct AwaitingGame {
+    #[serde(flatten)]
+    game: entity::game::OngoingGame,
+    // how long the player has to move, e.g. 3+2 or 1 day per move
+    pace: Option<String>,
+}
+
+pub async fn your_turn(
+    cookies: Cookies,
+    Extension(ref conn): Extension<DatabaseConnection>,
+    Extension(ref templates): Extension<Tera>,
+) -> Result<Html<String>, (StatusCode, String)> {
+    // the player's games that are waiting on their move
+    // (not counting games still waiting for an opponent to sit down)
+    let games = entity::game::find_ongoing_by_player(cookies.session_id, conn)
+        .await
+        .map_err(handle_db_error)?
+        .into_iter()
+        .filter(|game| game.is_against_ai || game.opponent_key.is_some())
+        .filter(|game| {
+            let board_state: Vec<Vec<u8>> =
+                serde_json::from_value(game.state.clone()).unwrap_or_default();
+            get_next_player_num(&board_state) as i32 == game.player_num
+        })
+        .map(|game| {
+            let pace = match (
+                game.clock_initial_secs,
+                game.clock_increment_secs,
+                game.move_deadline_hours,
+            ) {
+                (Some(initial_secs), Some(increment_secs), _) => {
+                    Some(format!("{}+{}", initial_secs / 60, increment_secs))
+                }
+                (_, _, Some(hours)) => Some(correspondence::describe_move_deadline(hours)),
+                _ => None,
+            };
+            AwaitingGame { game, pace }
+        })
+        .collect::<Vec<_>>();
+
+    let mut context = Context::new();
+    context.insert("site_name", SITE_NAME);
+    context.insert("games", &games);
+
+    let body = templates
+        .render("player/your_turn.html.tera", &context)
+        .map_err(handle_template_error)?;
+
+    Ok(Html(body))
+}
+
+fn take_page<T>(mut rows: Vec<T>, page_num: usize, page_size: usize) -> (Vec<T>, Page) {
+    // rows are fetched one past the page size
+    // so the extra row tells whether there is a next page
+    let has_next = rows.len() > page_size;
+    rows.truncate(page_size);
+
+    let page = Page {
+        number: page_num,
+        has_previous: page_num > 1,
+        has_next,
+    };
+
+    (rows, page)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn extra_row_means_next_page() {
+        let (rows, page) = take_page(vec![1, 2, 3], 1, 2);
+        assert_eq!(rows, vec![1, 2]);
+        assert!(!page.has_previous);
+        assert!(page.has_next);
+
+        let (rows, page) = take_page(vec![3], 2, 2);
+        assert_eq!(rows, vec![3]);
+        assert!(page.has_previous);
+        assert!(!page.has_next);
+    }
+
+    #[test]
+    fn pages_past_counting_are_not_found() {
+        assert_eq!(get_page_offset(1, LEADERBOARD_PAGE_SIZE), Ok(0));
+        assert_eq!(get_page_offset(3, GAMES_PAGE_SIZE), Ok(20));
+        assert!(get_page_offset(0, GAMES_PAGE_SIZE).is_err());
+        assert!(get_page_offset(usize::MAX, LEADERBOARD_PAGE_SIZE).is_err());
+        assert!(get_page_offset(usize::MAX / OPPONENTS_PAGE_SIZE, OPPONENTS_PAGE_SIZE).is_err());
+    }
+}
@@ -1,22 +1,149 @@
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
-use axum::extract::{Extension, Path};
+use axum::extract::{Extension, Path, Query};
 use axum::response::IntoResponse;
-use chrono::{FixedOffset, Utc};
 use futures::sink::SinkExt;
 use futures::stream::StreamExt;
-use sea_orm::prelude::*;
-use sea_orm::{DatabaseConnection, Set};
+use sea_orm::DatabaseConnection;
 use serde_json;
 use tokio::sync::broadcast;
 use uuid::Uuid;
 
-use super::message::GameMessage;
-use super::utils::is_winning_move;
-use crate::channels::GameChannels;
+use super::dto;
+use super::message::{GameMessage, LobbyMessage, WaitingRoomMessage};
+use crate::board::{
+    count_plies, get_next_player_num, get_opponent_num, is_board_full, is_valid_move,
+    is_winning_move,
+};
+use crate::channels::{GameChannels, GameRoom, Presence};
+use crate::clock::{get_clock_running_num, with_clock};
 use crate::cookies::Cookies;
+use crate::correspondence;
+use crate::engine::{self, hint, Computer, Engines, Position};
 use crate::entity;
+use crate::entity::game::{GameSettings, ResultReason};
+use crate::games::{end_game, get_board_state};
+use crate::matchmaking::{self, Matchmaker};
+use crate::rating;
+
+// how long a player who left during their turn has to come back
+// before their opponent may claim the win
+const ABANDON_GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+pub async fn ws_lobby(
+    ws: WebSocketUpgrade,
+    Extension(conn): Extension<DatabaseConnection>,
+    Extension(game_channels): Extension<Arc<GameChannels>>,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| ws_lobby_handler(socket, conn, game_channels))
+}
+
+async fn ws_lobby_handler(
+    stream: WebSocket,
+    conn: DatabaseConnection,
+    game_channels: Arc<GameChannels>,
+) {
+    let (mut own_tx, mut own_rx) = stream.split();
+
+    // subscribe before listing the open games
+    // so nothing that happens in between is missed
+    let mut lobby_rx = game_channels.lobby_channel().subscribe();
+
+    let games = match entity::game::find_open_public(None, &conn).await {
+        Ok(games) => games,
+        Err(_) => return,
+    };
+
+    // Task for sending the open games and then every change to them
+    // (the lobby is read-only, so the own client only ever listens)
+    let mut send_task = tokio::spawn(async move {
+        // oldest first, as clients put each newly opened game on top
+        for game in games.into_iter().rev() {
+            let message = LobbyMessage::Open { game };
+            if own_tx.send(Message::Text(message.write())).await.is_err() {
+                return;
+            }
+        }
+
+        loop {
+            let text = match lobby_rx.recv().await {
+                Ok(text) => text,
+                // a slow client just misses some updates
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            if own_tx.send(Message::Text(text)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    // Task for noticing when the own client goes away
+    let mut recv_task = tokio::spawn(async move {
+        while let Some(Ok(msg)) = own_rx.next().await {
+            if let Message::Close(_) = msg {
+                break;
+            }
+        }
+    });
+
+    tokio::select! {
+        _ = (&mut send_task) => recv_task.abort(),
+        _ = (&mut recv_task) => send_task.abort(),
+    };
+}
+
+pub async fn ws_matchmaking(
+    ws: WebSocketUpgrade,
+    cookies: Cookies,
+    Query(query): Query<dto::MatchmakingQuery>,
+    Extension(conn): Extension<DatabaseConnection>,
+    Extension(matchmaker): Extension<Arc<Matchmaker>>,
+) -> impl IntoResponse {
+    let settings = matchmaking::get_settings(
+        query.is_rated.unwrap_or(false),
+        query.time_control.as_deref(),
+        query.takeback_limit,
+    );
+    ws.on_upgrade(move |socket| ws_matchmaking_handler(socket, conn, matchmaker, settings, cookies))
+}
+
+async fn ws_matchmaking_handler(
+    mut socket: WebSocket,
+    conn: DatabaseConnection,
+    matchmaker: Arc<Matchmaker>,
+    settings: GameSettings,
+    cookies: Cookies,
+) {
+    let rating = match entity::player::find_by_id(cookies.session_id, &conn).await {
+        Ok(player) => player
+            .map(|player| player.rating)
+            .unwrap_or(rating::DEFAULT_RATING),
+        Err(_) => return,
+    };
+
+    // the player waits in the queue for as long as the waiting room is open
+    let (ticket_id, mut match_rx) = matchmaker.join(cookies.session_id, rating, settings);
+
+    let game_id = loop {
+        tokio::select! {
+            game_id = &mut match_rx => break game_id.ok(),
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break None,
+                _ => continue,
+            },
+        }
+    };
+
+    matchmaker.leave(ticket_id);
+
+    if let Some(game_id) = game_id {
+        let message = WaitingRoomMessage::Matched { game_id };
+        let _ = socket.send(Message::Text(message.write())).await;
+    }
+}
 
 pub async fn ws_play_game(
     ws: WebSocketUpgrade,
@@ -24,8 +151,11 @@ pub async fn ws_play_game(
     Path(game_id): Path<Uuid>,
     Extension(conn): Extension<DatabaseConnection>,
     Extension(game_channels): Extension<Arc<GameChannels>>,
+    Extension(engines): Extension<Arc<Engines>>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| ws_game_play_handler(socket, conn, game_id, game_channels, cookies))
+    ws.on_upgrade(move |socket| {
+        ws_game_play_handler(socket, conn, game_id, game_channels, engines, cookies)
+    })
 }
 
 async fn ws_game_play_handler(
@@ -33,6 +163,7 @@ async fn ws_game_play_handler(
     conn: DatabaseConnection,
     game_id: Uuid,
     game_channels: Arc<GameChannels>,
+    engines: Arc<Engines>,
     cookies: Cookies,
 ) {
     let (mut own_tx, mut own_rx) = stream.split();
@@ -40,7 +171,10 @@ async fn ws_game_play_handler(
     let game = entity::game::find_by_id(game_id, &conn)
         .await
         .expect("database error in finding game")
-        .expect(&format!("could not find game: {}", game_id));
+        .unwrap_or_else(|| panic!("could not find game: {}", game_id));
+
+    // the computer, in the seats it takes
+    let computers = find_computers(&game, &engines);
 
     // get player number
     // 1 -- player 1, black
@@ -48,49 +182,74 @@ async fn ws_game_play_handler(
     // 0 -- observer
     let mut player_num = 0;
     // check whether they are player 1
-    if let Some(key1) = game.player1_key {
-        if key1 == cookies.session_id {
-            player_num = 1;
-        }
+    if game.player1_key == Some(cookies.session_id) {
+        player_num = 1;
     }
     // if they're still an observer
     // check whether they are player 2
-    if player_num == 0 {
-        if let Some(key2) = game.player2_key {
-            if key2 == cookies.session_id {
-                player_num = 2;
-            }
-        }
+    if player_num == 0 && game.player2_key == Some(cookies.session_id) {
+        player_num = 2;
     }
 
     // subscribe to receive messages in gaming channel
     let channel_tx = game_channels.ensure_channel(game.uuid);
     let mut channel_rx = channel_tx.subscribe();
 
+    if player_num != 0 {
+        join_game(player_num, &game_channels.ensure_room(game_id));
+    }
+    // the computer may have been left to move (e.g. by a server restart)
+    start_computer(&game, &engines, &conn, &game_channels);
+    // kept for when the connection closes
+    let (leave_conn, leave_game_channels) = (conn.clone(), game_channels.clone());
+
     // Task for receiving broadcast messages from the channel
     // and possibly sending them back to own client
     let mut send_task = tokio::spawn(async move {
         while let Ok(msg) = channel_rx.recv().await {
             if let Ok(msg) = GameMessage::read(msg) {
-                match msg {
-                    GameMessage::Board { state_str } => {
-                        if own_tx.send(Message::Text(state_str)).await.is_err() {
-                            break;
-                        }
-                    }
-                    GameMessage::End { ending_str } => {
-                        if own_tx.send(Message::Text(ending_str)).await.is_err() {
-                            break;
-                        }
+                // a claimed seat now belongs to another device
+                // so hang up on this one after letting it know
+                let mut is_seat_lost = false;
+
+                let text = match msg {
+                    GameMessage::Board { state_str } => state_str,
+                    GameMessage::End { ending_str } => ending_str,
+                    GameMessage::Name { name_str } => name_str,
+                    GameMessage::Seated { seated_str } => seated_str,
+                    GameMessage::Offer { offer_str } => offer_str,
+                    GameMessage::Withdrawn { withdrawn_str } => withdrawn_str,
+                    GameMessage::Declined { declined_str } => declined_str,
+                    GameMessage::NextGame { next_game_str } => next_game_str,
+                    GameMessage::TakenBack { taken_back_str } => taken_back_str,
+                    GameMessage::Clock { clock_str } => clock_str,
+                    GameMessage::Deadline { deadline_str } => deadline_str,
+                    GameMessage::Abandoned { abandoned_str } => abandoned_str,
+                    GameMessage::Thinking { thinking_str } => thinking_str,
+                    GameMessage::Returned { returned_str } => returned_str,
+                    GameMessage::Claimed {
+                        player_num: claimed_num,
+                        claimed_str,
+                    } => {
+                        is_seat_lost = claimed_num == player_num;
+                        claimed_str
                     }
-                    _ => {}
+                    // hints are only for the player who asked
+                    GameMessage::Suggested {
+                        player_num: suggested_num,
+                        suggested_str,
+                    } if suggested_num == player_num => suggested_str,
+                    _ => continue,
+                };
+
+                if own_tx.send(Message::Text(text)).await.is_err() || is_seat_lost {
+                    break;
                 }
             }
         }
     });
 
     let channel_tx = channel_tx.clone();
-    let player_num = player_num.clone();
 
     // Task for receiving messages from own client
     // and possibly broadcasting to channel
@@ -108,41 +267,167 @@ async fn ws_game_play_handler(
                         }
 
                         // try playing as human
-                        // break out of game loop if it fails
-                        // (it only fails if game is already over)
-                        if let Err(_) = play(
-                            true,
+                        // break out of game loop on database errors
+                        // (selections that can't be played are just ignored)
+                        let has_played = match play(
+                            None,
                             game_id,
                             &conn,
-                            row,
-                            col,
+                            (row, col),
                             player_num,
-                            channel_tx.clone(),
-                            cookies,
+                            &game_channels,
+                            cookies.session_id,
                         )
                         .await
+                        {
+                            Ok(has_played) => has_played,
+                            Err(_) => break,
+                        };
+
+                        // try playing as ai if in game with ai
+                        // (it won't play if the human's move ended the game)
+                        if has_played
+                            && play_computers(&computers, game_id, &conn, &game_channels)
+                                .await
+                                .is_err()
                         {
                             break;
                         }
+                    }
+                    GameMessage::Rename { name } => {
+                        let name = match entity::player::validate_name(&name) {
+                            Ok(name) => name,
+                            Err(_) => continue,
+                        };
 
-                        // try playing as ai if in game with ai
-                        // break out of game loop if it fails
-                        // (it only fails if game is already over)
-                        if game.is_against_ai {
-                            if let Err(_) = play(
-                                false,
-                                game_id,
-                                &conn,
-                                row,
-                                col,
-                                player_num,
-                                channel_tx.clone(),
-                                cookies,
-                            )
+                        if entity::player::set_name(cookies.session_id, name.clone(), &conn)
                             .await
-                            {
-                                break;
-                            }
+                            .is_err()
+                        {
+                            continue;
+                        }
+
+                        // observers' names are saved for later
+                        // but only players' names are shown on the board
+                        if player_num != 0 {
+                            let _ = channel_tx.send(format!("Name {} {}", player_num, name));
+                        }
+                    }
+                    GameMessage::Resign => {
+                        if player_num == 0 {
+                            continue;
+                        }
+
+                        if resign(
+                            game_id,
+                            player_num,
+                            cookies.session_id,
+                            &conn,
+                            &game_channels,
+                        )
+                        .await
+                        .is_err()
+                        {
+                            break;
+                        }
+                    }
+                    draw_msg @ (GameMessage::OfferDraw
+                    | GameMessage::AcceptDraw
+                    | GameMessage::DeclineDraw) => {
+                        if player_num == 0 {
+                            continue;
+                        }
+
+                        if answer_draw(
+                            &draw_msg,
+                            game_id,
+                            player_num,
+                            cookies.session_id,
+                            &conn,
+                            &game_channels,
+                        )
+                        .await
+                        .is_err()
+                        {
+                            break;
+                        }
+                    }
+                    takeback_msg @ (GameMessage::RequestTakeback
+                    | GameMessage::AcceptTakeback
+                    | GameMessage::DeclineTakeback) => {
+                        if player_num == 0 {
+                            continue;
+                        }
+
+                        if answer_takeback(
+                            &takeback_msg,
+                            game_id,
+                            player_num,
+                            cookies.session_id,
+                            &conn,
+                            &game_channels,
+                        )
+                        .await
+                        .is_err()
+                        {
+                            break;
+                        }
+                    }
+                    abandoned_msg @ (GameMessage::ClaimWin | GameMessage::Abort) => {
+                        if player_num == 0 {
+                            continue;
+                        }
+
+                        if settle_abandoned(
+                            &abandoned_msg,
+                            game_id,
+                            player_num,
+                            cookies.session_id,
+                            &conn,
+                            &game_channels,
+                        )
+                        .await
+                        .is_err()
+                        {
+                            break;
+                        }
+                    }
+                    GameMessage::Hint => {
+                        if player_num == 0 {
+                            continue;
+                        }
+
+                        if give_hint(
+                            game_id,
+                            player_num,
+                            cookies.session_id,
+                            &conn,
+                            &game_channels,
+                            &engines,
+                        )
+                        .await
+                        .is_err()
+                        {
+                            break;
+                        }
+                    }
+                    GameMessage::Rematch => {
+                        if player_num == 0 {
+                            continue;
+                        }
+
+                        if offer_rematch(
+                            game_id,
+                            player_num,
+                            cookies.session_id,
+                            &conn,
+                            &game_channels,
+                            &engines,
+                        )
+                        .await
+                        .is_err()
+                        {
+                            break;
                         }
                     }
                     _ => {}
@@ -156,92 +441,961 @@ async fn ws_game_play_handler(
         _ = (&mut send_task) => recv_task.abort(),
         _ = (&mut recv_task) => send_task.abort(),
     };
+
+    if player_num != 0 {
+        leave_game(game_id, player_num, leave_conn, leave_game_channels);
+    }
 }
 
 async fn play(
-    is_human: bool,
+    computer: Option<Computer>,
     game_id: Uuid,
     conn: &DatabaseConnection,
-    row: usize,
-    col: usize,
+    (row, col): (usize, usize),
     player_num: u8,
-    channel_tx: broadcast::Sender<String>,
-    cookies: Cookies,
-) -> Result<(), String> {
+    game_channels: &Arc<GameChannels>,
+    session_id: Uuid,
+) -> Result<bool, String> {
+    // returns whether a move was actually made
+    // (by the computer, if there is one, otherwise by the human player)
+    // player_num is the seat of whoever is playing, computer or human
+    let is_human = computer.is_none();
     // refresh game from db
-    let game = entity::game::find_by_id(game_id, &conn)
+    let game = entity::game::find_by_id(game_id, conn)
         .await
-        .unwrap()
-        .unwrap();
+        .map_err(|err| format!("Database error: {}", err))?
+        .ok_or(format!("could not find game: {}", game_id))?;
 
     // has game already ended?
-    if let Some(_) = game.ended_at {
-        return Err(String::from("game already ended"));
+    if game.ended_at.is_some() {
+        return Ok(false);
+    }
+
+    // has seat been claimed by another device since this one connected?
+    if is_human && entity::game::get_player_num(&game, session_id) != player_num as usize {
+        return Ok(false);
+    }
+
+    // a player who has run out of time can't move anymore
+    if is_human && flag(game_id, None, conn, game_channels).await? {
+        return Ok(false);
+    }
+    if is_human && entity::game::is_overdue(&game) {
+        correspondence::forfeit(game, conn, game_channels).await?;
+        return Ok(false);
     }
 
     let game_board = entity::game::get_most_recent_board(&game, conn)
         .await
-        .unwrap()
-        .unwrap();
-    let board_state: Vec<Vec<u8>> = serde_json::from_value(game_board.state.clone()).expect(
-        &format!("could not deserialize game board:\n{:?}", game_board.state),
-    );
+        .map_err(|err| format!("Database error: {}", err))?
+        .ok_or(format!("could not find board for game: {}", game_id))?;
+    let board_state: Vec<Vec<u8>> = serde_json::from_value(game_board.state.clone())
+        .unwrap_or_else(|_| panic!("could not deserialize game board:\n{:?}", game_board.state));
 
-    let player_num = match is_human {
-        true => player_num,
-        _ => match player_num {
-            1 => 2,
-            2 => 1,
-            _ => panic!("This shouldn't be happening!"),
+    let (row, col) = match computer {
+        None => (row, col),
+        // (the computer only thinks on its own turn)
+        Some(_) if get_next_player_num(&board_state) != player_num => return Ok(false),
+        Some(computer) => match get_computer_play(
+            computer,
+            &game,
+            &board_state,
+            &game_channels.ensure_room(game_id),
+        )
+        .await?
+        {
+            Some(play) => play,
+            None => return Ok(false),
         },
     };
 
-    let (row, col) = match is_human {
-        true => (row, col),
-        _ => get_ai_play(&board_state, row, col),
-    };
-
     // invalid selection?
-    // TO-DO
+    // * it's not this player's turn
     // * selection has already been made on this board
-    // * selection goes against board rules)
+    // * selection goes against board rules
+    if get_next_player_num(&board_state) != player_num || !is_valid_move(row, col, &board_state) {
+        return Ok(false);
+    }
 
     // create board for current game play
-    let game_board = entity::board::create_next(game.uuid, board_state, row, col, player_num, conn)
-        .await
-        .map_err(|err| format!("Database error: {}", err))?;
-    let board_state: Vec<Vec<u8>> = serde_json::from_value(game_board.state.clone()).expect(
-        &format!("could not deserialize game board:\n{:?}", game_board.state),
-    );
+    // unless the board has changed since it was read
+    // (the game may have ended or a move been taken back, e.g. while the computer was thinking)
+    let game_board =
+        match entity::board::create_next(&game_board, board_state, row, col, player_num, conn)
+            .await
+            .map_err(|err| format!("Database error: {}", err))?
+        {
+            Some(game_board) => game_board,
+            None => return Ok(false),
+        };
+    let board_state: Vec<Vec<u8>> = serde_json::from_value(game_board.state.clone())
+        .unwrap_or_else(|_| panic!("could not deserialize game board:\n{:?}", game_board.state));
 
     // was it a winning move?
-    if is_winning_move(row, col, &board_state) {
-        let mut game: entity::game::ActiveModel = game.into();
+    // or are there no more moves left on board?
+    let result = if is_winning_move(row, col, &board_state) {
+        Some((player_num as usize, ResultReason::Connect))
+    } else if is_board_full(&board_state) {
+        Some((0, ResultReason::BoardFull))
+    } else {
+        None
+    };
 
-        game.winner_key = Set(Some(cookies.session_id));
-        game.ended_at = Set(Some(Utc::now().with_timezone(&FixedOffset::east(0))));
-        game.update(conn).await.unwrap();
+    let channel_tx = game_channels.ensure_channel(game_id);
 
-        let _ = channel_tx.send(format!("End {:?}", player_num));
+    if let Some((winner_num, reason)) = result {
+        end_game(game.clone(), winner_num, reason, conn, game_channels).await?;
     }
 
-    // are there any more moves left on board?
-    // TO-DO
-
     // notify channel of updated board
     let _ = channel_tx.send(format!("Board {:?}", board_state));
 
+    if result.is_none() {
+        update_clock(&game, &board_state, Some(player_num), conn, game_channels).await?;
+        correspondence::restart_move_deadline(&game, conn, &channel_tx)
+            .await
+            .map_err(|err| format!("Database error: {}", err))?;
+    }
+
+    let room = game_channels.ensure_room(game_id);
+    withdraw_offers(player_num, &room);
+
+    // the turn may have passed to a player who has already left
+    let next_num = get_opponent_num(player_num);
+    if result.is_none() && !game.is_against_ai && is_away(next_num, &room) {
+        start_grace_timer(game_id, next_num, conn.clone(), game_channels.clone());
+    }
+
+    Ok(true)
+}
+
+fn find_computers(game: &entity::game::Model, engines: &Engines) -> [Option<Computer>; 2] {
+    [1, 2].map(|num| {
+        game.is_computer(num)
+            .then(|| engines.find_computer(game.engine(num)))
+    })
+}
+
+pub fn start_computer(
+    game: &entity::game::Model,
+    engines: &Engines,
+    conn: &DatabaseConnection,
+    game_channels: &Arc<GameChannels>,
+) {
+    // lets the computer play whenever it's its turn, without waiting on anyone
+    // (e.g. when it opens the game, or in exhibitions, where it plays both sides)
+    if !game.is_against_ai || game.ended_at.is_some() {
+        return;
+    }
+    let computers = find_computers(game, engines);
+    let (game_id, conn, game_channels) = (game.uuid, conn.clone(), game_channels.clone());
+
+    tokio::spawn(async move {
+        if let Err(err) = play_computers(&computers, game_id, &conn, &game_channels).await {
+            tracing::error!("computer could not play in game {}: {}", game_id, err);
+        }
+    });
+}
+
+async fn play_computers(
+    computers: &[Option<Computer>; 2],
+    game_id: Uuid,
+    conn: &DatabaseConnection,
+    game_channels: &Arc<GameChannels>,
+) -> Result<(), String> {
+    // the computer keeps playing for as long as it's its turn
+    // (once, against a person, and until the game ends in exhibitions)
+    loop {
+        let mut has_played = false;
+        for (computer_num, computer) in (1..=2).zip(computers) {
+            if let Some(computer) = computer {
+                has_played |=
+                    play_computer(computer.clone(), computer_num, game_id, conn, game_channels)
+                        .await?;
+            }
+        }
+
+        if !has_played {
+            return Ok(());
+        }
+    }
+}
+
+async fn play_computer(
+    computer: Computer,
+    computer_num: u8,
+    game_id: Uuid,
+    conn: &DatabaseConnection,
+    game_channels: &Arc<GameChannels>,
+) -> Result<bool, String> {
+    // the computer's move, unless it's already busy with one
+    let room = game_channels.ensure_room(game_id);
+    {
+        let mut is_computer_playing = room.is_computer_playing.lock().unwrap();
+        if *is_computer_playing {
+            return Ok(false);
+        }
+        *is_computer_playing = true;
+    }
+
+    let result = play(
+        Some(computer),
+        game_id,
+        conn,
+        (0, 0),
+        computer_num,
+        game_channels,
+        Uuid::nil(),
+    )
+    .await;
+    *room.is_computer_playing.lock().unwrap() = false;
+
+    result
+}
+
+async fn find_own_game(
+    game_id: Uuid,
+    player_num: u8,
+    session_id: Uuid,
+    conn: &DatabaseConnection,
+) -> Result<Option<entity::game::Model>, String> {
+    // the game, as long as this connection's player still holds their seat in it
+    // (it may have been claimed by another device since they connected)
+    let game = entity::game::find_by_id(game_id, conn)
+        .await
+        .map_err(|err| format!("Database error: {}", err))?
+        .ok_or(format!("could not find game: {}", game_id))?;
+
+    match entity::game::get_player_num(&game, session_id) == player_num as usize {
+        true => Ok(Some(game)),
+        _ => Ok(None),
+    }
+}
+
+async fn resign(
+    game_id: Uuid,
+    player_num: u8,
+    session_id: Uuid,
+    conn: &DatabaseConnection,
+    game_channels: &GameChannels,
+) -> Result<(), String> {
+    // only ongoing games can be resigned
+    let game = match find_own_game(game_id, player_num, session_id, conn).await? {
+        Some(game) if game.ended_at.is_none() => game,
+        _ => return Ok(()),
+    };
+
+    let winner_num = get_opponent_num(player_num) as usize;
+    end_game(
+        game,
+        winner_num,
+        ResultReason::Resignation,
+        conn,
+        game_channels,
+    )
+    .await
+}
+
+async fn offer_rematch(
+    game_id: Uuid,
+    player_num: u8,
+    session_id: Uuid,
+    conn: &DatabaseConnection,
+    game_channels: &Arc<GameChannels>,
+    engines: &Engines,
+) -> Result<(), String> {
+    // the first player to ask offers the rematch, the second accepts it
+    // (only finished games can be rematched)
+    let game = match find_own_game(game_id, player_num, session_id, conn).await? {
+        Some(game) if game.ended_at.is_some() => game,
+        _ => return Ok(()),
+    };
+
+    let room = game_channels.ensure_room(game_id);
+
+    // the rematch might have already started
+    if let Some(next_game) = entity::game::find_rematch(game_id, conn)
+        .await
+        .map_err(|err| format!("Database error: {}", err))?
+    {
+        let _ = room.channel.send(format!("NextGame {}", next_game.uuid));
+        return Ok(());
+    }
+
+    let is_accepted = {
+        let mut offers = room.offers.lock().unwrap();
+        match offers.rematch {
+            Some(offered_by) if offered_by != player_num => {
+                offers.rematch = None;
+                true
+            }
+            // the computer never says no
+            _ if game.is_against_ai => true,
+            _ => {
+                offers.rematch = Some(player_num);
+                false
+            }
+        }
+    };
+
+    if !is_accepted {
+        let _ = room.channel.send(format!("Offer rematch {}", player_num));
+        return Ok(());
+    }
+
+    let next_game = entity::game::create_rematch(&game, conn)
+        .await
+        .map_err(|err| format!("Database error: {}", err))?;
+    game_channels.insert_channel(next_game.uuid);
+    // the computer opens the rematch if it's its turn to play first
+    start_computer(&next_game, engines, conn, game_channels);
+
+    // send everyone in the room, spectators included, over to the new game
+    let _ = room.channel.send(format!("NextGame {}", next_game.uuid));
+
+    Ok(())
+}
+
+// what an offer (e.g. of a draw) has come to
+enum Outcome {
+    Offered,
+    Agreed,
+    Declined { declined_by: u8 },
+    Unchanged,
+}
+
+async fn answer_draw(
+    msg: &GameMessage,
+    game_id: Uuid,
+    player_num: u8,
+    session_id: Uuid,
+    conn: &DatabaseConnection,
+    game_channels: &GameChannels,
+) -> Result<(), String> {
+    // draws can only be agreed in ongoing games
+    let game = match find_own_game(game_id, player_num, session_id, conn).await? {
+        Some(game) if game.ended_at.is_none() => game,
+        _ => return Ok(()),
+    };
+
+    let room = game_channels.ensure_room(game_id);
+    let opponent_num = get_opponent_num(player_num);
+
+    let outcome = {
+        let mut offers = room.offers.lock().unwrap();
+        match (msg, offers.draw) {
+            // the computer plays on
+            (GameMessage::OfferDraw, _) if game.is_against_ai => Outcome::Declined {
+                declined_by: opponent_num,
+            },
+            // offering a draw to an opponent who has just offered one is agreeing to it
+            (GameMessage::OfferDraw | GameMessage::AcceptDraw, Some(offered_by))
+                if offered_by == opponent_num =>
+            {
+                offers.draw = None;
+                Outcome::Agreed
+            }
+            (GameMessage::OfferDraw, None) => {
+                offers.draw = Some(player_num);
+                Outcome::Offered
+            }
+            (GameMessage::DeclineDraw, Some(offered_by)) if offered_by == opponent_num => {
+                offers.draw = None;
+                Outcome::Declined {
+                    declined_by: player_num,
+                }
+            }
+            _ => Outcome::Unchanged,
+        }
+    };
+
+    match outcome {
+        Outcome::Offered => {
+            let _ = room.channel.send(format!("Offer draw {}", player_num));
+        }
+        Outcome::Agreed => {
+            end_game(game, 0, ResultReason::Agreement, conn, game_channels).await?;
+        }
+        Outcome::Declined { declined_by } => {
+            let _ = room.channel.send(format!("Declined draw {}", declined_by));
+        }
+        Outcome::Unchanged => {}
+    }
+
     Ok(())
 }
 
-fn get_ai_play(board: &Vec<Vec<u8>>, _row: usize, _col: usize) -> (usize, usize) {
-    // _row and _col identify the last cell that was played by human opponent
-    // may use this information in a future version to make ai smarter
-    for i in 0..board.len() {
-        if board[i][board[i].len() - 1] == 0 {
-            return (i, board[i].len() - 1);
+async fn answer_takeback(
+    msg: &GameMessage,
+    game_id: Uuid,
+    player_num: u8,
+    session_id: Uuid,
+    conn: &DatabaseConnection,
+    game_channels: &Arc<GameChannels>,
+) -> Result<(), String> {
+    // takebacks can only be made in ongoing games that allow them
+    // (rated games never do)
+    let game = match find_own_game(game_id, player_num, session_id, conn).await? {
+        Some(game) if game.ended_at.is_none() && game.takeback_limit > 0 => game,
+        _ => return Ok(()),
+    };
+
+    // there has to be a move of the player's own to take back
+    if let GameMessage::RequestTakeback = msg {
+        if find_takeback(&game, player_num, conn).await?.is_none() {
+            return Ok(());
         }
     }
 
-    (0, 0)
+    let room = game_channels.ensure_room(game_id);
+    let opponent_num = get_opponent_num(player_num);
+
+    let outcome = {
+        let mut offers = room.offers.lock().unwrap();
+        match (msg, offers.takeback) {
+            // the computer always lets its opponent have another go
+            (GameMessage::RequestTakeback, _) if game.is_against_ai => Outcome::Agreed,
+            (GameMessage::RequestTakeback, None) => {
+                offers.takeback = Some(player_num);
+                Outcome::Offered
+            }
+            (GameMessage::AcceptTakeback, Some(requested_by)) if requested_by == opponent_num => {
+                offers.takeback = None;
+                Outcome::Agreed
+            }
+            (GameMessage::DeclineTakeback, Some(requested_by)) if requested_by == opponent_num => {
+                offers.takeback = None;
+                Outcome::Declined {
+                    declined_by: player_num,
+                }
+            }
+            _ => Outcome::Unchanged,
+        }
+    };
+
+    match outcome {
+        Outcome::Offered => {
+            let _ = room.channel.send(format!("Offer takeback {}", player_num));
+        }
+        Outcome::Agreed => {
+            let requested_by = match game.is_against_ai {
+                true => player_num,
+                _ => opponent_num,
+            };
+            take_back(&game, requested_by, conn, game_channels).await?;
+        }
+        Outcome::Declined { declined_by } => {
+            let _ = room
+                .channel
+                .send(format!("Declined takeback {}", declined_by));
+        }
+        Outcome::Unchanged => {}
+    }
+
+    Ok(())
+}
+
+async fn find_takeback(
+    game: &entity::game::Model,
+    player_num: u8,
+    conn: &DatabaseConnection,
+) -> Result<Option<(usize, i32)>, String> {
+    // how many plies taking back the player's last move removes,
+    // and the id of the latest board they are counted from
+    let game_board = entity::game::get_most_recent_board(game, conn)
+        .await
+        .map_err(|err| format!("Database error: {}", err))?
+        .ok_or(format!("could not find board for game: {}", game.uuid))?;
+    let board_state: Vec<Vec<u8>> = serde_json::from_value(game_board.state.clone())
+        .unwrap_or_else(|_| panic!("could not deserialize game board:\n{:?}", game_board.state));
+
+    // whoever isn't next to play made the last move
+    let last_mover = get_opponent_num(get_next_player_num(&board_state));
+    let plies = match (last_mover == player_num, game.is_against_ai) {
+        (true, _) => 1,
+        // the computer's reply goes along with the player's move
+        (false, true) => 2,
+        _ => return Ok(None),
+    };
+
+    match count_plies(&board_state) >= plies {
+        true => Ok(Some((plies, game_board.id))),
+        _ => Ok(None),
+    }
+}
+
+async fn take_back(
+    game: &entity::game::Model,
+    player_num: u8,
+    conn: &DatabaseConnection,
+    game_channels: &Arc<GameChannels>,
+) -> Result<(), String> {
+    // removes the player's last move and shows everyone the board from before it
+    let room = game_channels.ensure_room(game.uuid);
+    let taken_back_board = match find_takeback(game, player_num, conn).await? {
+        Some((plies, latest_board_id)) => entity::takeback::take_back(
+            game.uuid,
+            player_num as usize,
+            plies,
+            latest_board_id,
+            conn,
+        )
+        .await
+        .map_err(|err| format!("Database error: {}", err))?,
+        None => None,
+    };
+
+    // the board moved on (or the game ended) before the takeback could be made
+    let game_board = match taken_back_board {
+        Some(game_board) => game_board,
+        None => {
+            let _ = room
+                .channel
+                .send(format!("Withdrawn takeback {}", player_num));
+            return Ok(());
+        }
+    };
+    let board_state: Vec<Vec<u8>> = serde_json::from_value(game_board.state.clone())
+        .unwrap_or_else(|_| panic!("could not deserialize game board:\n{:?}", game_board.state));
+
+    let takebacks_used = entity::takeback::count_for_game(game.uuid, conn)
+        .await
+        .map_err(|err| format!("Database error: {}", err))?;
+    let takebacks_left = (game.takeback_limit.max(0) as usize).saturating_sub(takebacks_used);
+
+    let _ = room
+        .channel
+        .send(format!("TakenBack {} {}", player_num, takebacks_left));
+    let _ = room.channel.send(format!("Board {:?}", board_state));
+
+    // nobody gets their time back, the clock just changes sides
+    // (but a correspondence player gets a full deadline, as with any other move)
+    update_clock(game, &board_state, None, conn, game_channels).await?;
+    correspondence::restart_move_deadline(game, conn, &room.channel)
+        .await
+        .map_err(|err| format!("Database error: {}", err))
+}
+
+async fn update_clock(
+    game: &entity::game::Model,
+    board_state: &[Vec<u8>],
+    moved_num: Option<u8>,
+    conn: &DatabaseConnection,
+    game_channels: &Arc<GameChannels>,
+) -> Result<(), String> {
+    // hands the clock over to whoever plays next (moved_num is None if nobody moved),
+    // lets everyone know, and watches for them running out of time
+    let room = game_channels.ensure_room(game.uuid);
+    let next_num = get_clock_running_num(board_state);
+    let now = Instant::now();
+
+    let clock_update = with_clock(game, next_num, &room, |clock| {
+        match moved_num {
+            Some(moved_num) => clock.press(moved_num, next_num, now),
+            None => clock.switch(next_num, now),
+        }
+        (clock.read(now), clock.turn(), clock.deadline())
+    });
+    let (clock_reading, turn, deadline) = match clock_update {
+        Some(clock_update) => clock_update,
+        None => return Ok(()),
+    };
+
+    entity::game::save_clock(game.uuid, clock_reading.time_left_ms, conn)
+        .await
+        .map_err(|err| format!("Database error: {}", err))?;
+    let _ = room.channel.send(clock_reading.write());
+
+    if let Some(deadline) = deadline {
+        arm_flag_timer(
+            game.uuid,
+            turn,
+            deadline,
+            conn.clone(),
+            game_channels.clone(),
+        );
+    }
+
+    Ok(())
+}
+
+fn arm_flag_timer(
+    game_id: Uuid,
+    turn: u64,
+    deadline: Instant,
+    conn: DatabaseConnection,
+    game_channels: Arc<GameChannels>,
+) {
+    // flags the player when their time runs out, even if nobody says anything
+    tokio::spawn(async move {
+        tokio::time::sleep_until(deadline.into()).await;
+        if let Err(err) = flag(game_id, Some(turn), &conn, &game_channels).await {
+            tracing::error!("could not flag game {}: {}", game_id, err);
+        }
+    });
+}
+
+async fn flag(
+    game_id: Uuid,
+    turn: Option<u64>,
+    conn: &DatabaseConnection,
+    game_channels: &GameChannels,
+) -> Result<bool, String> {
+    // ends the game if the player to move has run out of time
+    // returns whether they had
+    // (flag timers pass the turn they were set for, and stand down once it's over)
+    let game = entity::game::find_by_id(game_id, conn)
+        .await
+        .map_err(|err| format!("Database error: {}", err))?
+        .ok_or(format!("could not find game: {}", game_id))?;
+
+    if game.ended_at.is_some() || game.time_control().is_none() {
+        return Ok(false);
+    }
+
+    let board_state = get_board_state(&game, conn).await?;
+    let room = game_channels.ensure_room(game_id);
+    let now = Instant::now();
+
+    let flagged_num = with_clock(&game, get_clock_running_num(&board_state), &room, |clock| {
+        let is_current_turn = turn.is_none_or(|turn| turn == clock.turn());
+        match is_current_turn && clock.is_flagged(now) {
+            true => Some(clock.running_num()),
+            _ => None,
+        }
+    })
+    .flatten();
+
+    match flagged_num {
+        Some(flagged_num) => {
+            let winner_num = get_opponent_num(flagged_num) as usize;
+            end_game(game, winner_num, ResultReason::Timeout, conn, game_channels).await?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+pub async fn resume_clocks(
+    conn: &DatabaseConnection,
+    game_channels: &Arc<GameChannels>,
+) -> Result<(), String> {
+    // clocks kept running while the server was down,
+    // so pick them up again (flagging anyone who ran out of time in the meantime)
+    let games = entity::game::find_running_clocks(conn)
+        .await
+        .map_err(|err| format!("Database error: {}", err))?;
+
+    for game in games {
+        let board_state = get_board_state(&game, conn).await?;
+        let room = game_channels.ensure_room(game.uuid);
+
+        let timer = with_clock(&game, get_clock_running_num(&board_state), &room, |clock| {
+            clock.deadline().map(|deadline| (clock.turn(), deadline))
+        })
+        .flatten();
+
+        if let Some((turn, deadline)) = timer {
+            arm_flag_timer(
+                game.uuid,
+                turn,
+                deadline,
+                conn.clone(),
+                game_channels.clone(),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn withdraw_offers(player_num: u8, room: &GameRoom) {
+    // a player who moves instead of waiting for an answer
+    // no longer stands by their draw offer,
+    // and no takeback request survives a move by either player
+    let (is_draw_withdrawn, takeback_requested_by) = {
+        let mut offers = room.offers.lock().unwrap();
+        let is_draw_withdrawn = match offers.draw == Some(player_num) {
+            true => {
+                offers.draw = None;
+                true
+            }
+            _ => false,
+        };
+        (is_draw_withdrawn, offers.takeback.take())
+    };
+
+    if is_draw_withdrawn {
+        let _ = room.channel.send(format!("Withdrawn draw {}", player_num));
+    }
+
+    if let Some(requested_by) = takeback_requested_by {
+        let _ = room
+            .channel
+            .send(format!("Withdrawn takeback {}", requested_by));
+    }
+}
+
+fn join_game(player_num: u8, room: &GameRoom) {
+    // a player coming back to a game they abandoned picks it up again
+    let has_returned = {
+        let mut presence = room.presence.lock().unwrap();
+        let i = player_num as usize - 1;
+        presence.connections[i] += 1;
+        presence.grace_timers[i] += 1;
+        match presence.abandoned_by == Some(player_num) {
+            true => presence.abandoned_by.take().is_some(),
+            _ => false,
+        }
+    };
+
+    if has_returned {
+        let _ = room.channel.send(format!("Returned {}", player_num));
+    }
+}
+
+fn leave_game(
+    game_id: Uuid,
+    player_num: u8,
+    conn: DatabaseConnection,
+    game_channels: Arc<GameChannels>,
+) {
+    // once a player's last connection closes, they have a while to come back
+    // (the grace timer only counts if it's their turn when it runs out)
+    let room = game_channels.ensure_room(game_id);
+    let is_last = {
+        let mut presence = room.presence.lock().unwrap();
+        let i = player_num as usize - 1;
+        presence.connections[i] = presence.connections[i].saturating_sub(1);
+        presence.connections[i] == 0
+    };
+
+    if is_last {
+        start_grace_timer(game_id, player_num, conn, game_channels);
+    }
+}
+
+fn is_away(player_num: u8, room: &GameRoom) -> bool {
+    room.presence.lock().unwrap().connections[player_num as usize - 1] == 0
+}
+
+fn start_grace_timer(
+    game_id: Uuid,
+    player_num: u8,
+    conn: DatabaseConnection,
+    game_channels: Arc<GameChannels>,
+) {
+    let room = game_channels.ensure_room(game_id);
+    let timer = {
+        let mut presence = room.presence.lock().unwrap();
+        presence.grace_timers[player_num as usize - 1] += 1;
+        presence.grace_timers[player_num as usize - 1]
+    };
+
+    tokio::spawn(async move {
+        tokio::time::sleep(ABANDON_GRACE_PERIOD).await;
+        if let Err(err) = mark_abandoned(game_id, player_num, timer, &conn, &room).await {
+            tracing::error!("could not check game {} for abandonment: {}", game_id, err);
+        }
+    });
+}
+
+async fn mark_abandoned(
+    game_id: Uuid,
+    player_num: u8,
+    timer: u64,
+    conn: &DatabaseConnection,
+    room: &GameRoom,
+) -> Result<(), String> {
+    // lets the opponent settle the game, if the player still hasn't come back
+    // and it's their turn in an ongoing game between two people
+    // (correspondence games are expected to be left, and have deadlines instead)
+    let i = player_num as usize - 1;
+    let is_still_away = |presence: &Presence| {
+        presence.grace_timers[i] == timer
+            && presence.connections[i] == 0
+            && presence.abandoned_by.is_none()
+    };
+    if !is_still_away(&room.presence.lock().unwrap()) {
+        return Ok(());
+    }
+
+    let game = entity::game::find_by_id(game_id, conn)
+        .await
+        .map_err(|err| format!("Database error: {}", err))?
+        .ok_or(format!("could not find game: {}", game_id))?;
+    if game.ended_at.is_some()
+        || game.is_against_ai
+        || game.move_deadline_hours.is_some()
+        || game.player1_key.is_none()
+        || game.player2_key.is_none()
+    {
+        return Ok(());
+    }
+
+    let board_state = get_board_state(&game, conn).await?;
+    if get_next_player_num(&board_state) != player_num {
+        return Ok(());
+    }
+
+    let is_abandoned = {
+        let mut presence = room.presence.lock().unwrap();
+        match is_still_away(&presence) {
+            true => {
+                presence.abandoned_by = Some(player_num);
+                true
+            }
+            _ => false,
+        }
+    };
+
+    if is_abandoned {
+        let _ = room.channel.send(format!("Abandoned {}", player_num));
+    }
+
+    Ok(())
+}
+
+async fn settle_abandoned(
+    msg: &GameMessage,
+    game_id: Uuid,
+    player_num: u8,
+    session_id: Uuid,
+    conn: &DatabaseConnection,
+    game_channels: &GameChannels,
+) -> Result<(), String> {
+    // the opponent of a player who abandoned the game
+    // may claim the win, or call the game off
+    let game = match find_own_game(game_id, player_num, session_id, conn).await? {
+        Some(game) if game.ended_at.is_none() => game,
+        _ => return Ok(()),
+    };
+
+    let opponent_num = get_opponent_num(player_num);
+    let is_abandoned = {
+        let room = game_channels.ensure_room(game_id);
+        let presence = room.presence.lock().unwrap();
+        presence.abandoned_by == Some(opponent_num)
+            && presence.connections[opponent_num as usize - 1] == 0
+    };
+    if !is_abandoned {
+        return Ok(());
+    }
+
+    match msg {
+        GameMessage::ClaimWin => {
+            let winner_num = player_num as usize;
+            end_game(
+                game,
+                winner_num,
+                ResultReason::Abandonment,
+                conn,
+                game_channels,
+            )
+            .await
+        }
+        GameMessage::Abort => end_game(game, 0, ResultReason::Aborted, conn, game_channels).await,
+        _ => Ok(()),
+    }
+}
+
+async fn give_hint(
+    game_id: Uuid,
+    player_num: u8,
+    session_id: Uuid,
+    conn: &DatabaseConnection,
+    game_channels: &GameChannels,
+    engines: &Engines,
+) -> Result<(), String> {
+    // suggests a play to the player, if it's their turn in an unrated game
+    let game = match find_own_game(game_id, player_num, session_id, conn).await? {
+        Some(game) if game.ended_at.is_none() && !game.is_rated => game,
+        _ => return Ok(()),
+    };
+
+    let game_board = entity::game::get_most_recent_board(&game, conn)
+        .await
+        .map_err(|err| format!("Database error: {}", err))?
+        .ok_or(format!("could not find board for game: {}", game_id))?;
+    let board_state: Vec<Vec<u8>> = serde_json::from_value(game_board.state.clone())
+        .unwrap_or_else(|_| panic!("could not deserialize game board:\n{:?}", game_board.state));
+    if get_next_player_num(&board_state) != player_num {
+        return Ok(());
+    }
+
+    let engine = engines.find_or_default(Some(hint::HINT_ENGINE));
+    let seed = engine::get_seed(game_id, count_plies(&board_state));
+    let suggested =
+        tokio::task::spawn_blocking(move || hint::suggest(engine.as_ref(), &board_state, seed))
+            .await
+            .map_err(|err| format!("Engine error: {}", err))?;
+    let hint = match suggested {
+        Some(hint) => hint,
+        None => return Ok(()),
+    };
+
+    // the hint only counts once it's given
+    // (and isn't given if the game ended, or was rated, in the meantime)
+    let hints_used = match entity::game::use_hint(game_id, player_num, conn)
+        .await
+        .map_err(|err| format!("Database error: {}", err))?
+    {
+        Some(hints_used) => hints_used,
+        None => return Ok(()),
+    };
+
+    let _ = game_channels.ensure_channel(game_id).send(format!(
+        "Suggested {} {} {} {} {}",
+        player_num,
+        hint.play.0,
+        hint.play.1,
+        hint.reason.as_str(),
+        hints_used
+    ));
+
+    Ok(())
+}
+
+async fn get_computer_play(
+    computer: Computer,
+    game: &entity::game::Model,
+    board: &[Vec<u8>],
+    room: &GameRoom,
+) -> Result<Option<(usize, usize)>, String> {
+    // engines can think for a while, so they're kept off the async workers
+    // and the computer takes its time even when they don't
+    // (though never so long that it runs out of time)
+    let position = Position {
+        time_left_ms: with_clock(game, get_clock_running_num(board), room, |clock| {
+            clock.read(Instant::now()).time_left_ms
+        }),
+        ..Position::new(board.to_vec())
+    };
+    let seed = engine::get_seed(game.uuid, count_plies(board));
+    // exhibitions go at their own pace, with every move taking the time it's given
+    let (budget, think_delay) = match game.engine_move_time() {
+        Some(move_time) => {
+            let budget = position.get_budget(move_time);
+            (budget, budget)
+        }
+        None => {
+            let budget = position.get_budget(engine::TIME_BUDGET);
+            let think_delay = computer
+                .think_delay
+                .pick(board, &mut rand::thread_rng())
+                .min(budget);
+            (budget, think_delay)
+        }
+    };
+
+    let _ = room
+        .channel
+        .send(format!("Thinking {}", get_next_player_num(board)));
+
+    let engine = computer.engine;
+    let (play, _) = tokio::join!(
+        tokio::task::spawn_blocking(move || engine.choose_play(&position, budget, seed)),
+        tokio::time::sleep(think_delay),
+    );
+    play.map_err(|err| format!("Engine error: {}", err))
 }
@@ -0,0 +1,7 @@
+// the rules of the board and the engines that play on it
+//
+// they don't need a database or a server, so the offline solver (see bin/solve.rs)
+// is built from them as well as the server
+
+pub mod board;
+pub mod engine;
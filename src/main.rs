@@ -1,7 +1,8 @@
 use std::env;
 use std::net::SocketAddr;
+use std::sync::Arc;
 
-use axum::routing::{get, get_service};
+use axum::routing::{get, get_service, post};
 use axum::{AddExtensionLayer, Router, Server};
 use dotenv::dotenv;
 use sea_orm::Database;
@@ -10,16 +11,34 @@ use tower_cookies::CookieManagerLayer;
 use tower_http::services::ServeDir;
 use url::Url;
 
+use stacky_sides::{board, engine};
+
+mod analysis;
 mod channels;
+mod clock;
 mod cookies;
+mod correspondence;
 mod entity;
+mod games;
 mod handlers;
+mod matchmaking;
+mod rating;
 
 use channels::GameChannels;
+use cookies::SessionConfig;
+use engine::perfect::Perfect;
+use engine::tablebase::Tablebases;
+use engine::{Engine, Engines};
 use entity::setup as entity_setup;
+use handlers::account::{login, login_form, logout, register, register_form};
 use handlers::error::handle_staticfiles_server_error;
-use handlers::http::{create_game, index, play_game, share_game};
-use handlers::ws::ws_play_game;
+use handlers::http::{
+    claim_seat, confirm_seat_claim, create_game, create_seat_claim, index, lobby, play_game,
+    replay_game, share_game, show_seat_claim, take_seat, waiting_room,
+};
+use handlers::player::{leaderboard, player_profile, your_turn};
+use handlers::ws::{ws_lobby, ws_matchmaking, ws_play_game};
+use matchmaking::Matchmaker;
 
 #[tokio::main]
 async fn main() {
@@ -44,6 +63,8 @@ async fn main() {
     let base_url = env::var("BASE_URL").expect("BASE_URL is not set in environment");
     let base_url = Url::parse(&base_url).expect("Error parsing BASE_URL");
 
+    let session_config = SessionConfig::from_env(&base_url);
+
     let db_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in environment");
     let conn = Database::connect(db_url)
         .await
@@ -53,20 +74,113 @@ async fn main() {
     entity_setup::create_game_table(&conn)
         .await
         .expect("Cannot create game table");
+    entity_setup::upgrade_game_table(&conn)
+        .await
+        .expect("Cannot upgrade game table");
     entity_setup::create_board_table(&conn)
         .await
         .expect("Cannot create board table");
+    entity_setup::create_player_table(&conn)
+        .await
+        .expect("Cannot create player table");
+    entity_setup::upgrade_player_table(&conn)
+        .await
+        .expect("Cannot upgrade player table");
+    entity_setup::create_rating_history_table(&conn)
+        .await
+        .expect("Cannot create rating history table");
+    entity_setup::create_account_table(&conn)
+        .await
+        .expect("Cannot create account table");
+    entity_setup::create_account_session_table(&conn)
+        .await
+        .expect("Cannot create account session table");
+    entity_setup::create_seat_claim_table(&conn)
+        .await
+        .expect("Cannot create seat claim table");
+    entity_setup::create_takeback_table(&conn)
+        .await
+        .expect("Cannot create takeback table");
+    entity_setup::create_annotation_table(&conn)
+        .await
+        .expect("Cannot create annotation table");
+    entity_setup::create_lookup_indexes(&conn)
+        .await
+        .expect("Cannot create lookup indexes");
+
+    let game_channels = GameChannels::new_in_arc();
+
+    // engines run as separate programs, on top of the built-in ones
+    let mut engines = Engines::new();
+    if let Ok(external_engines) = env::var("EXTERNAL_ENGINES") {
+        for engine in engine::external::parse_engines(&external_engines) {
+            tracing::info!("registering external engine: {}", engine.name());
+            engines.register(Arc::new(engine));
+        }
+    }
+    // tablebases worked out by the solver let the computer play some boards perfectly
+    let tablebases = Arc::new(
+        env::var("TABLEBASES")
+            .map(|paths| Tablebases::load(&paths))
+            .unwrap_or_default(),
+    );
+    if !tablebases.is_empty() {
+        engines.register(Arc::new(Perfect::new(tablebases.clone())));
+    }
+    if let Ok(think_delay) = env::var("ENGINE_THINK_MS") {
+        match engine::ThinkDelay::parse(&think_delay) {
+            Some(think_delay) => engines.set_think_delay(think_delay),
+            None => tracing::warn!("ignoring invalid ENGINE_THINK_MS: {}", think_delay),
+        }
+    }
+    let engines = Arc::new(engines);
+    handlers::ws::resume_clocks(&conn, &game_channels)
+        .await
+        .expect("Cannot resume game clocks");
+
+    // pair up waiting players in the background
+    let matchmaker = Matchmaker::new_in_arc();
+    tokio::spawn(matchmaking::run(
+        matchmaker.clone(),
+        conn.clone(),
+        game_channels.clone(),
+    ));
+
+    // forfeit correspondence games whose moves are overdue
+    tokio::spawn(correspondence::run(conn.clone(), game_channels.clone()));
+
+    // annotate games once they've ended
+    tokio::spawn(analysis::run(conn.clone(), tablebases.clone()));
 
     let app = Router::new()
         .route("/", get(index).post(create_game))
+        .route("/register", get(register_form).post(register))
+        .route("/login", get(login_form).post(login))
+        .route("/logout", post(logout))
         .route("/game/:uuid/share", get(share_game))
         .route("/game/:uuid/play", get(play_game))
+        .route("/game/:uuid/replay", get(replay_game))
+        .route("/game/:uuid/seat", post(take_seat))
+        .route("/game/:uuid/claim", post(create_seat_claim))
+        .route("/game/:uuid/claim/:token", get(show_seat_claim))
+        .route("/claim/:token", get(confirm_seat_claim).post(claim_seat))
+        .route("/lobby", get(lobby))
+        .route("/matchmaking", get(waiting_room))
+        .route("/leaderboard", get(leaderboard))
+        .route("/player/:uuid", get(player_profile))
+        .route("/your-turn", get(your_turn))
         .route("/ws/game/:uuid/play", get(ws_play_game))
+        .route("/ws/lobby", get(ws_lobby))
+        .route("/ws/matchmaking", get(ws_matchmaking))
         .nest("/static", staticfiles_service)
         .layer(AddExtensionLayer::new(base_url))
         .layer(AddExtensionLayer::new(conn))
-        .layer(AddExtensionLayer::new(GameChannels::new_in_arc()))
+        .layer(AddExtensionLayer::new(game_channels))
+        .layer(AddExtensionLayer::new(matchmaker))
+        .layer(AddExtensionLayer::new(engines))
+        .layer(AddExtensionLayer::new(tablebases))
         .layer(AddExtensionLayer::new(templates))
+        .layer(AddExtensionLayer::new(session_config.new_in_arc()))
         .layer(CookieManagerLayer::new());
 
     let address = SocketAddr::from(([127, 0, 0, 1], 8000));
@@ -0,0 +1,299 @@
+// pairs up players who asked for an opponent
+//
+// players wait in an in-memory queue, and are only ever paired
+// with players who want the same game settings, and whose rating is close enough
+// the accepted rating gap starts narrow, and widens the longer they wait
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use sea_orm::DatabaseConnection;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::channels::GameChannels;
+use crate::clock::TimeControl;
+use crate::correspondence;
+use crate::entity;
+use crate::entity::game::{GameSettings, ResultReason};
+use crate::games::end_game;
+
+const INITIAL_RATING_RANGE: f64 = 100.0;
+const RATING_RANGE_GROWTH_PER_SEC: f64 = 10.0;
+const MAX_RATING_RANGE: f64 = 800.0;
+const PAIRING_INTERVAL: Duration = Duration::from_secs(1);
+
+pub fn get_settings(
+    is_rated: bool,
+    time_control: Option<&str>,
+    takeback_limit: Option<i16>,
+) -> GameSettings {
+    // the settings a player can ask for, read the same way as when creating a game
+    // (the rest are the same for every game made here)
+    GameSettings::new(
+        false,
+        is_rated,
+        false,
+        takeback_limit.unwrap_or(entity::game::DEFAULT_TAKEBACK_LIMIT),
+        time_control.and_then(TimeControl::parse),
+        time_control.and_then(correspondence::parse_move_deadline),
+    )
+}
+
+pub fn get_rating_range(waited: Duration) -> f64 {
+    (INITIAL_RATING_RANGE + waited.as_secs_f64() * RATING_RANGE_GROWTH_PER_SEC)
+        .min(MAX_RATING_RANGE)
+}
+
+pub struct Ticket {
+    id: Uuid,
+    player_key: Uuid,
+    rating: f64,
+    settings: GameSettings,
+    joined_at: Instant,
+    // receives the id of the game once a match is made
+    match_tx: oneshot::Sender<Uuid>,
+}
+
+impl Ticket {
+    fn accepts(&self, other: &Ticket, now: Instant) -> bool {
+        self.settings == other.settings
+            && self.player_key != other.player_key
+            && (self.rating - other.rating).abs() <= get_rating_range(now - self.joined_at)
+    }
+}
+
+pub struct Matchmaker {
+    tickets: Mutex<Vec<Ticket>>,
+}
+
+impl Matchmaker {
+    fn new() -> Self {
+        Self {
+            tickets: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn new_in_arc() -> Arc<Self> {
+        Arc::new(Self::new())
+    }
+
+    pub fn join(
+        &self,
+        player_key: Uuid,
+        rating: f64,
+        settings: GameSettings,
+    ) -> (Uuid, oneshot::Receiver<Uuid>) {
+        // a player waits in the queue at most once
+        // so a newer waiting room (e.g. in another tab) replaces the older one
+        let (match_tx, match_rx) = oneshot::channel();
+        let ticket = Ticket {
+            id: Uuid::new_v4(),
+            player_key,
+            rating,
+            settings,
+            joined_at: Instant::now(),
+            match_tx,
+        };
+        let ticket_id = ticket.id;
+
+        let mut tickets = self.tickets.lock().unwrap();
+        tickets.retain(|ticket| ticket.player_key != player_key);
+        tickets.push(ticket);
+
+        (ticket_id, match_rx)
+    }
+
+    pub fn leave(&self, ticket_id: Uuid) {
+        self.tickets
+            .lock()
+            .unwrap()
+            .retain(|ticket| ticket.id != ticket_id);
+    }
+
+    fn take_pairs(&self, now: Instant) -> Vec<(Ticket, Ticket)> {
+        let mut tickets = self.tickets.lock().unwrap();
+
+        // players who have left without saying so can't be matched
+        tickets.retain(|ticket| !ticket.match_tx.is_closed());
+
+        let pairs = find_pairs(&tickets, now);
+
+        let mut slots = tickets.drain(..).map(Some).collect::<Vec<_>>();
+        let matched = pairs
+            .into_iter()
+            .map(|(i, j)| (slots[i].take().unwrap(), slots[j].take().unwrap()))
+            .collect();
+        *tickets = slots.into_iter().flatten().collect();
+
+        matched
+    }
+
+    fn put_back(&self, ticket: Ticket) {
+        self.tickets.lock().unwrap().push(ticket);
+    }
+}
+
+fn find_pairs(tickets: &[Ticket], now: Instant) -> Vec<(usize, usize)> {
+    // whoever has waited longest picks first,
+    // and picks the closest rated player that would accept them too
+    let mut order = (0..tickets.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| tickets[i].joined_at);
+
+    let mut is_paired = vec![false; tickets.len()];
+    let mut pairs = Vec::new();
+
+    for &i in order.iter() {
+        if is_paired[i] {
+            continue;
+        }
+
+        let best = order
+            .iter()
+            .copied()
+            .filter(|&j| j != i && !is_paired[j])
+            .filter(|&j| {
+                tickets[i].accepts(&tickets[j], now) && tickets[j].accepts(&tickets[i], now)
+            })
+            .min_by(|&a, &b| {
+                let gap_a = (tickets[i].rating - tickets[a].rating).abs();
+                let gap_b = (tickets[i].rating - tickets[b].rating).abs();
+                gap_a.partial_cmp(&gap_b).unwrap()
+            });
+
+        if let Some(j) = best {
+            is_paired[i] = true;
+            is_paired[j] = true;
+            pairs.push((i, j));
+        }
+    }
+
+    pairs
+}
+
+pub async fn run(
+    matchmaker: Arc<Matchmaker>,
+    conn: DatabaseConnection,
+    game_channels: Arc<GameChannels>,
+) {
+    let mut interval = tokio::time::interval(PAIRING_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        for (ticket1, ticket2) in matchmaker.take_pairs(Instant::now()) {
+            // neither player chose a side, so toss a coin for who plays first
+            let (ticket1, ticket2) = match rand::thread_rng().gen_bool(0.5) {
+                true => (ticket1, ticket2),
+                _ => (ticket2, ticket1),
+            };
+
+            // either of them may have left since the pairs were taken
+            // (the one still waiting is matched again in the next round)
+            if ticket1.match_tx.is_closed() || ticket2.match_tx.is_closed() {
+                matchmaker.put_back(ticket1);
+                matchmaker.put_back(ticket2);
+                continue;
+            }
+
+            let game = entity::game::create_with_players(
+                Some(ticket1.player_key),
+                Some(ticket2.player_key),
+                &conn,
+                ticket1.settings.clone(),
+            )
+            .await;
+
+            let game = match game {
+                Ok(game) => game,
+                Err(err) => {
+                    // let them wait for the next round
+                    tracing::error!("could not create matched game: {}", err);
+                    matchmaker.put_back(ticket1);
+                    matchmaker.put_back(ticket2);
+                    continue;
+                }
+            };
+
+            // or leave while the game is being made
+            // in which case it's aborted, rather than left with an absent opponent
+            let game_id = game.uuid;
+            game_channels.insert_channel(game_id);
+            if ticket1.match_tx.send(game_id).is_err() {
+                abort_game(game, &conn, &game_channels).await;
+                matchmaker.put_back(ticket2);
+                continue;
+            }
+            if ticket2.match_tx.send(game_id).is_err() {
+                // player 1 has already been sent to the game, and finds it aborted there
+                abort_game(game, &conn, &game_channels).await;
+            }
+        }
+    }
+}
+
+async fn abort_game(
+    game: entity::game::Model,
+    conn: &DatabaseConnection,
+    game_channels: &GameChannels,
+) {
+    let game_id = game.uuid;
+    if let Err(err) = end_game(game, 0, ResultReason::Aborted, conn, game_channels).await {
+        tracing::error!("could not abort matched game {}: {}", game_id, err);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ticket(rating: f64, is_rated: bool, waited_secs: u64, now: Instant) -> Ticket {
+        let (match_tx, _match_rx) = oneshot::channel();
+        Ticket {
+            id: Uuid::new_v4(),
+            player_key: Uuid::new_v4(),
+            rating,
+            settings: GameSettings::new(false, is_rated, false, 0, None, None),
+            joined_at: now - Duration::from_secs(waited_secs),
+            match_tx,
+        }
+    }
+
+    #[test]
+    fn rating_range_widens_up_to_a_limit() {
+        assert_eq!(
+            get_rating_range(Duration::from_secs(0)),
+            INITIAL_RATING_RANGE
+        );
+        assert!(get_rating_range(Duration::from_secs(10)) > INITIAL_RATING_RANGE);
+        assert_eq!(
+            get_rating_range(Duration::from_secs(3600)),
+            MAX_RATING_RANGE
+        );
+    }
+
+    #[test]
+    fn pairs_closest_compatible_players() {
+        let now = Instant::now();
+        let tickets = vec![
+            ticket(1500.0, true, 5, now),
+            ticket(1580.0, true, 0, now),
+            ticket(1510.0, false, 0, now),
+            ticket(1530.0, true, 0, now),
+        ];
+
+        // the casual player can't be matched with anyone rated
+        assert_eq!(find_pairs(&tickets, now), vec![(0, 3)]);
+    }
+
+    #[test]
+    fn distant_ratings_pair_only_after_waiting() {
+        let now = Instant::now();
+        let tickets = vec![ticket(1500.0, true, 0, now), ticket(1800.0, true, 0, now)];
+        assert!(find_pairs(&tickets, now).is_empty());
+
+        let tickets = vec![ticket(1500.0, true, 30, now), ticket(1800.0, true, 30, now)];
+        assert_eq!(find_pairs(&tickets, now), vec![(0, 1)]);
+    }
+}
@@ -1,4 +1,12 @@
+pub mod analysis;
+pub mod board;
 pub mod channels;
+pub mod clock;
 pub mod cookies;
+pub mod correspondence;
+pub mod engine;
 pub mod entity;
+pub mod games;
 pub mod handlers;
+pub mod matchmaking;
+pub mod rating;
@@ -0,0 +1,119 @@
+// Elo ratings for human vs human games
+//
+// a player's first PROVISIONAL_GAMES rated games are provisional:
+// their rating moves faster while it settles,
+// and opponents risk less against them, since it isn't reliable yet
+
+pub const DEFAULT_RATING: f64 = 1500.0;
+pub const PROVISIONAL_GAMES: i32 = 20;
+
+const K_FACTOR: f64 = 20.0;
+const PROVISIONAL_K_FACTOR: f64 = 40.0;
+const MIN_RATING: f64 = 100.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rating {
+    pub rating: f64,
+    pub rated_games: i32,
+}
+
+impl Rating {
+    pub fn is_provisional(&self) -> bool {
+        self.rated_games < PROVISIONAL_GAMES
+    }
+
+    fn k_factor(&self, opponent: &Rating) -> f64 {
+        if self.is_provisional() {
+            PROVISIONAL_K_FACTOR
+        } else if opponent.is_provisional() {
+            K_FACTOR / 2.0
+        } else {
+            K_FACTOR
+        }
+    }
+}
+
+pub fn expected_score(rating: f64, opponent_rating: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent_rating - rating) / 400.0))
+}
+
+// score is from player 1's point of view
+// 1 -- player 1 won, 0.5 -- draw, 0 -- player 2 won
+pub fn rate(player1: Rating, player2: Rating, score: f64) -> (Rating, Rating) {
+    let expected = expected_score(player1.rating, player2.rating);
+
+    let player1_rating = player1.rating + player1.k_factor(&player2) * (score - expected);
+    let player2_rating =
+        player2.rating + player2.k_factor(&player1) * ((1.0 - score) - (1.0 - expected));
+
+    (
+        Rating {
+            rating: player1_rating.max(MIN_RATING),
+            rated_games: player1.rated_games + 1,
+        },
+        Rating {
+            rating: player2_rating.max(MIN_RATING),
+            rated_games: player2.rated_games + 1,
+        },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn established(rating: f64) -> Rating {
+        Rating {
+            rating,
+            rated_games: PROVISIONAL_GAMES,
+        }
+    }
+
+    fn provisional(rating: f64) -> Rating {
+        Rating {
+            rating,
+            rated_games: 0,
+        }
+    }
+
+    #[test]
+    fn expected_scores() {
+        assert_eq!(expected_score(1500.0, 1500.0), 0.5);
+        assert!((expected_score(1900.0, 1500.0) - 0.909).abs() < 0.001);
+        assert!((expected_score(1500.0, 1900.0) - 0.091).abs() < 0.001);
+    }
+
+    #[test]
+    fn win_between_equals() {
+        let (player1, player2) = rate(established(1500.0), established(1500.0), 1.0);
+
+        assert_eq!(player1.rating, 1510.0);
+        assert_eq!(player2.rating, 1490.0);
+        assert_eq!(player1.rated_games, PROVISIONAL_GAMES + 1);
+        assert_eq!(player2.rated_games, PROVISIONAL_GAMES + 1);
+    }
+
+    #[test]
+    fn draw_moves_towards_each_other() {
+        let (player1, player2) = rate(established(1700.0), established(1500.0), 0.5);
+
+        assert!(player1.rating < 1700.0);
+        assert!(player2.rating > 1500.0);
+        assert!(((1700.0 - player1.rating) - (player2.rating - 1500.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn provisional_ratings_move_faster() {
+        let (player1, player2) = rate(provisional(1500.0), established(1500.0), 1.0);
+
+        assert_eq!(player1.rating, 1520.0);
+        assert_eq!(player2.rating, 1495.0);
+    }
+
+    #[test]
+    fn ratings_have_a_floor() {
+        let (_player1, player2) = rate(provisional(MIN_RATING), provisional(MIN_RATING), 1.0);
+
+        assert_eq!(player2.rating, MIN_RATING);
+    }
+}
@@ -1,18 +1,20 @@
 class GameUI {
-  constructor(playerNum, gameBoardData, isAgainstAI, isGameOver) {
+  constructor(playerNum, playerNames, gameBoardData, computerNames, isGameOver) {
     this.canPlayNext = false;
     this.playerNum = playerNum;
+    this.playerNames = playerNames;
     this.gameBoardData = gameBoardData;
-    this.isAgainstAI = isAgainstAI;
+    // what the computer goes by in each seat it takes (null in seats for people)
+    this.computerNames = computerNames;
     this.isGameOver = isGameOver;
     this.showPlayerStatus();
   }
 
   showPlayerStatus() {
-    if (this.isAgainstAI) {
-      // in this version, AI is always player 2
-      document.querySelector(".white .description").innerText = "Computer";
-    }
+    document.querySelector(".black .description").innerText =
+      this.getPlayerName(1);
+    document.querySelector(".white .description").innerText =
+      this.getPlayerName(2);
 
     if (this.playerNum === 0) {
       return;
@@ -22,12 +24,133 @@ class GameUI {
       this.playerNum === 1 ? "black" : this.playerNum === 2 ? "white" : "oops";
     document
       .querySelector(`.${className} .description`)
-      .appendChild(document.createTextNode("(You)"));
+      .appendChild(document.createTextNode(" (You)"));
+  }
+
+  getPlayerName(num) {
+    return (
+      this.computerNames[num - 1] ||
+      this.playerNames[num - 1] ||
+      `Player ${num}`
+    );
+  }
+
+  renamePlayer(num, name) {
+    this.playerNames[num - 1] = name;
+    this.showPlayerStatus();
+
+    if (this.isGameOver) {
+      this.displayResult();
+    }
+  }
+
+  notifySeatClaimed(claimedNum) {
+    if (claimedNum !== this.playerNum) {
+      return;
+    }
+
+    // this device is now only watching
+    this.playerNum = 0;
+    this.canPlayNext = false;
+    this.showPlayerStatus();
+
+    let claimCardElt = document.querySelector(".claim-card");
+    if (claimCardElt) {
+      claimCardElt.remove();
+    }
+
+    let noticeElt = document.createElement("p");
+    noticeElt.classList.add("small", "text-muted", "pt-2");
+    noticeElt.textContent = "Your seat was claimed on another device.";
+    document.querySelector(".result-card").appendChild(noticeElt);
+  }
+
+  notifySeatTaken(seatNum) {
+    // someone else took this seat, so it can't be offered here anymore
+    let seatCardElt = document.querySelector(".seat-card");
+    if (!seatCardElt) {
+      return;
+    }
+
+    let seatFormElt = seatCardElt.querySelector(`form[data-seat="${seatNum}"]`);
+    if (seatFormElt) {
+      seatFormElt.remove();
+    }
+    if (!seatCardElt.querySelector("form")) {
+      seatCardElt.remove();
+    }
+  }
+
+  attachWatchListener() {
+    let seatCardElt = document.querySelector(".seat-card");
+    if (!seatCardElt) {
+      return;
+    }
+
+    seatCardElt.querySelector(".watch-button").addEventListener("click", () => {
+      seatCardElt.remove();
+    });
+  }
+
+  notifyDrawOffer(offeredByNum) {
+    if (offeredByNum === this.playerNum) {
+      this.showDrawButtons(true, "Draw offered, waiting for your opponent...");
+      document.querySelector(".offer-draw-button").disabled = true;
+    } else {
+      this.showDrawButtons(
+        false,
+        `${this.getPlayerName(offeredByNum)} offers a draw.`
+      );
+    }
+  }
+
+  notifyDrawOfferClosed(isDeclined, byNum) {
+    let status = "";
+    if (isDeclined && byNum !== this.playerNum) {
+      status = `${this.getPlayerName(byNum)} declined the draw.`;
+    } else if (!isDeclined && byNum !== this.playerNum) {
+      status = `${this.getPlayerName(byNum)} withdrew their draw offer.`;
+    }
+
+    this.showDrawButtons(true, status);
+    let offerButtonElt = document.querySelector(".offer-draw-button");
+    if (offerButtonElt) {
+      offerButtonElt.disabled = false;
+    }
+  }
+
+  attachRenameListener(websocket) {
+    let nameCardElt = document.querySelector(".name-card");
+    nameCardElt.addEventListener("submit", (event) => {
+      event.preventDefault();
+
+      let name = nameCardElt.elements["name"].value.trim();
+      if (name) {
+        websocket.send(`Rename ${name}`);
+      }
+    });
   }
 
   replaceGameBoardData(gameBoardData) {
     this.gameBoardData = gameBoardData;
     this.refreshGameBoard();
+    this.showThinking(false);
+    this.showHintStatus("");
+  }
+
+  notifyThinking(num) {
+    let thinkingCardElt = document.querySelector(".thinking-card");
+    if (thinkingCardElt) {
+      thinkingCardElt.textContent = `${this.getPlayerName(num)} is thinking…`;
+    }
+    this.showThinking(true);
+  }
+
+  showThinking(isShown) {
+    let thinkingCardElt = document.querySelector(".thinking-card");
+    if (thinkingCardElt) {
+      thinkingCardElt.classList.toggle("d-none", !isShown);
+    }
   }
 
   refreshGameBoard() {
@@ -66,6 +189,9 @@ class GameUI {
       "flex-wrap"
     );
 
+    cardElt.style.setProperty("--board-rows", this.gameBoardData.length);
+    cardElt.style.setProperty("--board-cols", this.gameBoardData[0].length);
+
     this.gameBoardData.forEach((row, i) => {
       row.forEach((_cellData, j) => {
         cardElt.appendChild(this.createGameCell(i, j));
@@ -132,23 +258,353 @@ class GameUI {
       });
   }
 
-  notifyGameEnd(winnerNum) {
+  setResult(winnerNum, resultReason) {
     this.winnerNum = winnerNum;
     this.loserNum = this.winnerNum === 1 ? 2 : 1;
+    this.resultReason = resultReason;
     this.isGameOver = true;
   }
 
+  notifyGameEnd(winnerNum, resultReason) {
+    this.setResult(winnerNum, resultReason);
+
+    // nothing left to do in this game, except maybe play another
+    for (let selector of [
+      ".deadline-card",
+      ".resign-card",
+      ".draw-card",
+      ".takeback-card",
+      ".abandon-card",
+      ".thinking-card",
+      ".hint-card",
+      ".claim-card",
+    ]) {
+      let elt = document.querySelector(selector);
+      if (elt) {
+        elt.remove();
+      }
+    }
+
+    for (let selector of [".rematch-card", ".replay-card"]) {
+      let elt = document.querySelector(selector);
+      if (elt) {
+        elt.classList.remove("d-none");
+      }
+    }
+
+    // not every ending comes with a new board
+    this.refreshGameBoard();
+  }
+
+  attachResignListener(websocket) {
+    let resignCardElt = document.querySelector(".resign-card");
+    if (!resignCardElt) {
+      return;
+    }
+
+    resignCardElt
+      .querySelector(".resign-button")
+      .addEventListener("click", (event) => {
+        if (window.confirm("Are you sure you want to resign?")) {
+          websocket.send("Resign");
+        }
+      });
+  }
+
+  attachRematchListener(websocket) {
+    let rematchCardElt = document.querySelector(".rematch-card");
+    if (!rematchCardElt) {
+      return;
+    }
+
+    rematchCardElt
+      .querySelector(".rematch-button")
+      .addEventListener("click", (event) => {
+        event.target.disabled = true;
+        websocket.send("Rematch");
+      });
+  }
+
+  attachDrawListener(websocket) {
+    let drawCardElt = document.querySelector(".draw-card");
+    if (!drawCardElt) {
+      return;
+    }
+
+    for (let [selector, command] of [
+      [".offer-draw-button", "OfferDraw"],
+      [".accept-draw-button", "AcceptDraw"],
+      [".decline-draw-button", "DeclineDraw"],
+    ]) {
+      drawCardElt.querySelector(selector).addEventListener("click", () => {
+        websocket.send(command);
+      });
+    }
+  }
+
+  showDrawButtons(isOfferButtonShown, status) {
+    let drawCardElt = document.querySelector(".draw-card");
+    if (!drawCardElt) {
+      return;
+    }
+
+    let offerButtonElt = drawCardElt.querySelector(".offer-draw-button");
+    let answerButtonElts = drawCardElt.querySelectorAll(
+      ".accept-draw-button, .decline-draw-button"
+    );
+    offerButtonElt.classList.toggle("d-none", !isOfferButtonShown);
+    answerButtonElts.forEach((elt) =>
+      elt.classList.toggle("d-none", isOfferButtonShown)
+    );
+    drawCardElt.querySelector(".draw-status").textContent = status;
+  }
+
+  attachHintListener(websocket) {
+    let hintCardElt = document.querySelector(".hint-card");
+    if (!hintCardElt) {
+      return;
+    }
+
+    hintCardElt.querySelector(".hint-button").addEventListener("click", () => {
+      if (this.canPlayNext) {
+        websocket.send("Hint");
+      } else {
+        this.showHintStatus("Hints are for when it's your turn.");
+      }
+    });
+  }
+
+  notifySuggested(num, row, col, reason, hintsUsed) {
+    let reasons = {
+      win: "it connects four",
+      block: "it stops your opponent from connecting four",
+      threat: "it sets up four to connect on your next move",
+      build: "it builds towards lines of four",
+    };
+    let cellElt = document.querySelector(
+      `.game-card .cell[data-row="${row}"][data-col="${col}"]`
+    );
+    if (cellElt) {
+      cellElt.classList.add("hinted");
+    }
+    this.showHintStatus(`Try the highlighted cell: ${reasons[reason]}.`);
+
+    let className = num === 1 ? "black" : "white";
+    let hintsUsedElt = document.querySelector(`.${className} .hints-used`);
+    if (hintsUsedElt) {
+      hintsUsedElt.textContent = `${hintsUsed} hint${hintsUsed === 1 ? "" : "s"}`;
+      hintsUsedElt.classList.remove("d-none");
+    }
+  }
+
+  showHintStatus(status) {
+    let hintStatusElt = document.querySelector(".hint-status");
+    if (hintStatusElt) {
+      hintStatusElt.textContent = status;
+    }
+  }
+
+  attachTakebackListener(websocket) {
+    let takebackCardElt = document.querySelector(".takeback-card");
+    if (!takebackCardElt) {
+      return;
+    }
+
+    for (let [selector, command] of [
+      [".request-takeback-button", "Takeback"],
+      [".accept-takeback-button", "AcceptTakeback"],
+      [".decline-takeback-button", "DeclineTakeback"],
+    ]) {
+      takebackCardElt.querySelector(selector).addEventListener("click", () => {
+        websocket.send(command);
+      });
+    }
+  }
+
+  showTakebackButtons(isRequestButtonShown, status) {
+    let takebackCardElt = document.querySelector(".takeback-card");
+    if (!takebackCardElt) {
+      return;
+    }
+
+    let requestButtonElt = takebackCardElt.querySelector(
+      ".request-takeback-button"
+    );
+    let answerButtonElts = takebackCardElt.querySelectorAll(
+      ".accept-takeback-button, .decline-takeback-button"
+    );
+    requestButtonElt.classList.toggle("d-none", !isRequestButtonShown);
+    requestButtonElt.disabled = false;
+    answerButtonElts.forEach((elt) =>
+      elt.classList.toggle("d-none", isRequestButtonShown)
+    );
+    takebackCardElt.querySelector(".takeback-status").textContent = status;
+  }
+
+  notifyTakebackRequest(requestedByNum) {
+    if (requestedByNum === this.playerNum) {
+      this.showTakebackButtons(
+        true,
+        "Takeback requested, waiting for your opponent..."
+      );
+      document.querySelector(".request-takeback-button").disabled = true;
+    } else {
+      this.showTakebackButtons(
+        false,
+        `${this.getPlayerName(requestedByNum)} wants to take back their move.`
+      );
+    }
+  }
+
+  notifyTakebackRequestClosed(isDeclined, byNum) {
+    let status = "";
+    if (isDeclined && byNum !== this.playerNum) {
+      status = `${this.getPlayerName(byNum)} declined the takeback.`;
+    } else if (!isDeclined && byNum === this.playerNum) {
+      status = "The board changed, so the takeback was cancelled.";
+    }
+
+    this.showTakebackButtons(true, status);
+  }
+
+  notifyTakenBack(takenBackNum, takebacksLeft) {
+    // the corrected board comes in its own message
+    let takebackCardElt = document.querySelector(".takeback-card");
+    if (!takebackCardElt) {
+      return;
+    }
+
+    if (takebacksLeft === 0) {
+      takebackCardElt.remove();
+      return;
+    }
+
+    takebackCardElt.querySelector(".takebacks-left").textContent =
+      takebacksLeft;
+    this.showTakebackButtons(
+      true,
+      `${this.getPlayerName(takenBackNum)} took back their move.`
+    );
+  }
+
+  attachAbandonListener(websocket) {
+    let abandonCardElt = document.querySelector(".abandon-card");
+    if (!abandonCardElt || this.playerNum === 0) {
+      return;
+    }
+
+    for (let [selector, command] of [
+      [".claim-win-button", "ClaimWin"],
+      [".abort-button", "Abort"],
+    ]) {
+      abandonCardElt.querySelector(selector).addEventListener("click", () => {
+        websocket.send(command);
+      });
+    }
+  }
+
+  notifyAbandoned(abandonedByNum) {
+    let abandonCardElt = document.querySelector(".abandon-card");
+    if (!abandonCardElt) {
+      return;
+    }
+
+    // a player only sees this about themselves on a page loaded while away,
+    // and it's cleared as soon as they're back
+    let status = `${this.getPlayerName(abandonedByNum)} left the game.`;
+    if (abandonedByNum !== this.playerNum && this.playerNum !== 0) {
+      status += " You can claim the win, or abort the game.";
+    }
+    abandonCardElt.querySelector(".abandon-status").textContent = status;
+    abandonCardElt.classList.remove("d-none");
+  }
+
+  notifyReturned(returnedNum) {
+    let abandonCardElt = document.querySelector(".abandon-card");
+    if (abandonCardElt) {
+      abandonCardElt.classList.add("d-none");
+    }
+  }
+
+  notifyOffer(offer, offeredByNum) {
+    if (offer === "draw") {
+      this.notifyDrawOffer(offeredByNum);
+      return;
+    }
+
+    if (offer === "takeback") {
+      this.notifyTakebackRequest(offeredByNum);
+      return;
+    }
+
+    let rematchCardElt = document.querySelector(".rematch-card");
+    if (offer !== "rematch" || !rematchCardElt) {
+      return;
+    }
+
+    let buttonElt = rematchCardElt.querySelector(".rematch-button");
+    let statusElt = rematchCardElt.querySelector(".rematch-status");
+    if (offeredByNum === this.playerNum) {
+      buttonElt.disabled = true;
+      statusElt.textContent = "Rematch offered, waiting for your opponent...";
+    } else {
+      buttonElt.disabled = false;
+      buttonElt.textContent = "Accept rematch";
+      statusElt.textContent = `${this.getPlayerName(offeredByNum)} wants a rematch!`;
+    }
+  }
+
+  setClock(timeLeftMs, runningNum) {
+    // the server keeps the real clocks,
+    // this only counts down between its updates
+    this.clock = { timeLeftMs, runningNum, readAt: performance.now() };
+    this.showClock();
+
+    if (!this.clockTimer) {
+      this.clockTimer = setInterval(() => this.showClock(), 100);
+    }
+  }
+
+  showClock() {
+    let elapsed = performance.now() - this.clock.readAt;
+
+    for (let [num, className] of [
+      [1, "black"],
+      [2, "white"],
+    ]) {
+      let clockElt = document.querySelector(`.${className} .clock`);
+      if (!clockElt) {
+        continue;
+      }
+
+      let isRunning = num === this.clock.runningNum;
+      let timeLeft = this.clock.timeLeftMs[num - 1];
+      if (isRunning) {
+        timeLeft = Math.max(0, timeLeft - elapsed);
+      }
+
+      clockElt.textContent = formatClock(timeLeft);
+      clockElt.classList.toggle("fw-bold", isRunning);
+      clockElt.classList.toggle("text-danger", isRunning && timeLeft < 10000);
+    }
+  }
+
+  setMoveDeadline(moveDeadlineAt) {
+    // shown in the player's own time zone
+    let deadlineCardElt = document.querySelector(".deadline-card");
+    deadlineCardElt.querySelector(".deadline").textContent = new Date(
+      moveDeadlineAt
+    ).toLocaleString();
+    deadlineCardElt.classList.toggle("d-none", this.isGameOver);
+  }
+
   displayResult() {
     let resultElt = document.createElement("p");
     resultElt.classList.add("h6", "pt-2");
 
     let player1OutLoud, player2OutLoud;
-    player1OutLoud = this.playerNum === 1 ? "You" : "Player 1";
-    player2OutLoud = this.playerNum === 2 ? "You" : "Player 2";
-    // for now, if AI is playing, they are player 2
-    if (this.isAgainstAI) {
-      player2OutLoud = "Computer";
-    }
+    player1OutLoud = this.playerNum === 1 ? "You" : this.getPlayerName(1);
+    player2OutLoud = this.playerNum === 2 ? "You" : this.getPlayerName(2);
 
     let whoWon, whoLost;
     if (this.winnerNum === 1) {
@@ -159,8 +615,21 @@ class GameUI {
       whoLost = player1OutLoud;
     }
 
-    resultElt.textContent =
-      this.winnerNum === 0 ? `It's a TIE!` : `${whoWon} won, ${whoLost} lost.`;
+    if (this.resultReason === "aborted") {
+      resultElt.textContent = `The game was aborted.`;
+    } else if (this.winnerNum === 0 && this.resultReason === "agreement") {
+      resultElt.textContent = `It's a TIE, by agreement.`;
+    } else if (this.winnerNum === 0) {
+      resultElt.textContent = `It's a TIE!`;
+    } else if (this.resultReason === "resignation") {
+      resultElt.textContent = `${whoLost} resigned, so ${whoWon} won.`;
+    } else if (this.resultReason === "timeout") {
+      resultElt.textContent = `${whoLost} ran out of time, so ${whoWon} won.`;
+    } else if (this.resultReason === "abandonment") {
+      resultElt.textContent = `${whoLost} left the game, so ${whoWon} won.`;
+    } else {
+      resultElt.textContent = `${whoWon} won, ${whoLost} lost.`;
+    }
 
     let resultCardElt = document.querySelector(".result-card");
     while (resultCardElt.firstChild) {
@@ -170,3 +639,13 @@ class GameUI {
     resultCardElt.appendChild(resultElt);
   }
 }
+
+function formatClock(ms) {
+  // m:ss, with tenths of a second once it gets tight
+  let minutes = Math.floor(ms / 60000);
+  let seconds = Math.floor((ms % 60000) / 1000);
+  if (ms < 10000) {
+    return `0:0${seconds}.${Math.floor((ms % 1000) / 100)}`;
+  }
+  return `${minutes}:${String(seconds).padStart(2, "0")}`;
+}
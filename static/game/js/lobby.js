@@ -0,0 +1,103 @@
+window.addEventListener("DOMContentLoaded", (event) => {
+  const lobbySocketUrl = JSON.parse(
+    document.getElementById("lobbyWsUrl").textContent
+  );
+  const lobbyGames = document.getElementById("lobbyGames");
+  const lobbyEmpty = document.getElementById("lobbyEmpty");
+  const lobbyClosed = document.getElementById("lobbyClosed");
+
+  function refreshEmptyNote() {
+    if (lobbyGames.children.length) {
+      lobbyEmpty.classList.add("d-none");
+    } else {
+      lobbyEmpty.classList.remove("d-none");
+    }
+  }
+
+  function findRow(gameId) {
+    return lobbyGames.querySelector(`tr[data-game-id="${gameId}"]`);
+  }
+
+  function createRow(game) {
+    let row = document.createElement("tr");
+    row.dataset.gameId = game.uuid;
+
+    let playerCell = document.createElement("td");
+    let ratingCell = document.createElement("td");
+    ratingCell.classList.add("text-end");
+
+    if (game.player_key) {
+      let playerLink = document.createElement("a");
+      playerLink.href = `/player/${game.player_key}`;
+      playerLink.innerText = game.player_name || "Anonymous";
+      playerCell.appendChild(playerLink);
+      ratingCell.innerText = Math.round(game.player_rating);
+    } else {
+      // the creator is only watching
+      playerCell.classList.add("text-muted");
+      playerCell.innerText = "Both seats free";
+    }
+
+    let kindCell = document.createElement("td");
+    kindCell.innerText = game.is_rated ? "Rated" : "Casual";
+
+    let joinCell = document.createElement("td");
+    joinCell.classList.add("text-end");
+    let joinLink = document.createElement("a");
+    joinLink.classList.add("btn", "btn-sm", "btn-primary");
+    joinLink.href = `/game/${game.uuid}/play`;
+    joinLink.innerText = "Join";
+    joinCell.appendChild(joinLink);
+
+    row.append(playerCell, ratingCell, kindCell, joinCell);
+    return row;
+  }
+
+  function openGame(game) {
+    let row = createRow(game);
+    let existingRow = findRow(game.uuid);
+    if (existingRow) {
+      existingRow.replaceWith(row);
+    } else {
+      // newest games go on top
+      lobbyGames.prepend(row);
+    }
+  }
+
+  function closeGame(gameId) {
+    let row = findRow(gameId);
+    if (row) {
+      row.remove();
+    }
+  }
+
+  const websocket = new WebSocket(lobbySocketUrl);
+  websocket.onopen = function (event) {
+    // the server starts by sending every open game
+    // so drop whatever was rendered with the page
+    while (lobbyGames.firstChild) {
+      lobbyGames.firstChild.remove();
+    }
+  };
+
+  websocket.onclose = function (event) {
+    // the list stops updating, so say so
+    lobbyClosed.classList.remove("d-none");
+  };
+
+  websocket.onmessage = function (event) {
+    const separatorIndex = event.data.indexOf(" ");
+    if (separatorIndex === -1) {
+      return;
+    }
+    const keyword = event.data.slice(0, separatorIndex).toLowerCase();
+    const rest = event.data.slice(separatorIndex + 1);
+
+    if (keyword === "open") {
+      openGame(JSON.parse(rest));
+    } else if (keyword === "closed") {
+      closeGame(rest);
+    }
+    refreshEmptyNote();
+  };
+});
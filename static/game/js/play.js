@@ -5,21 +5,66 @@ window.addEventListener("DOMContentLoaded", (event) => {
   const gameBoardData = JSON.parse(
     document.getElementById("gameBoardData").textContent
   );
-  const isAgainstAI = JSON.parse(
-    document.getElementById("isAgainstAI").textContent
+  const computerNames = JSON.parse(
+    document.getElementById("computerNames").textContent
   );
   const playerNum = JSON.parse(
     document.getElementById("playerNum").textContent
   );
+  const playerNames = JSON.parse(
+    document.getElementById("playerNames").textContent
+  );
   const isGameOver = JSON.parse(
     document.getElementById("isGameOver").textContent
   );
-  let gameUI = new GameUI(playerNum, gameBoardData, isAgainstAI, isGameOver);
+  const gameResult = JSON.parse(
+    document.getElementById("gameResult").textContent
+  );
+  let gameUI = new GameUI(
+    playerNum,
+    playerNames,
+    gameBoardData,
+    computerNames,
+    isGameOver
+  );
+  if (gameResult) {
+    gameUI.setResult(gameResult.winner_num, gameResult.reason);
+  }
+  const clock = JSON.parse(document.getElementById("clock").textContent);
+  if (clock) {
+    gameUI.setClock(clock.time_left_ms, clock.running_num);
+  }
+  const moveDeadlineAt = JSON.parse(
+    document.getElementById("moveDeadlineAt").textContent
+  );
+  if (moveDeadlineAt) {
+    gameUI.setMoveDeadline(moveDeadlineAt);
+  }
+  const abandonedBy = JSON.parse(
+    document.getElementById("abandonedBy").textContent
+  );
+  if (abandonedBy) {
+    gameUI.notifyAbandoned(abandonedBy);
+  }
+  const offers = JSON.parse(document.getElementById("offers").textContent);
+  for (let [offer, offeredByNum] of Object.entries(offers)) {
+    if (offeredByNum) {
+      gameUI.notifyOffer(offer, offeredByNum);
+    }
+  }
 
   const websocket = new WebSocket(gamePlaySocketUrl);
   websocket.onopen = function (event) {
     gameUI.refreshGameBoard();
     gameUI.attachClickListener(websocket);
+    gameUI.attachRenameListener(websocket);
+    gameUI.attachWatchListener();
+    gameUI.attachRematchListener(websocket);
+    gameUI.attachResignListener(websocket);
+    gameUI.attachDrawListener(websocket);
+    gameUI.attachTakebackListener(websocket);
+    gameUI.attachHintListener(websocket);
+    gameUI.attachAbandonListener(websocket);
   };
 
   websocket.onclose = function (event) {
@@ -27,14 +72,61 @@ window.addEventListener("DOMContentLoaded", (event) => {
   };
 
   websocket.onmessage = function (event) {
-    let msg = event.data.toLowerCase();
+    // only the keyword is case-insensitive, names keep their case
+    let msg = event.data;
+    let keyword = msg.split(" ", 1)[0].toLowerCase();
+    msg = msg.slice(keyword.length).trim();
 
-    if (msg.startsWith("board")) {
-      msg = msg.slice("board".length).trim();
+    if (keyword === "board") {
       gameUI.replaceGameBoardData(JSON.parse(msg));
-    } else if (msg.startsWith("end")) {
-      msg = msg.slice("end".length).trim();
-      gameUI.notifyGameEnd(JSON.parse(msg));
+    } else if (keyword === "end") {
+      let [num, reason] = msg.split(" ");
+      gameUI.notifyGameEnd(JSON.parse(num), reason);
+    } else if (keyword === "name") {
+      let [num, ...name] = msg.split(" ");
+      gameUI.renamePlayer(JSON.parse(num), name.join(" "));
+    } else if (keyword === "offer") {
+      let [offer, num] = msg.split(" ");
+      gameUI.notifyOffer(offer, JSON.parse(num));
+    } else if (keyword === "withdrawn" || keyword === "declined") {
+      let [offer, num] = msg.split(" ");
+      if (offer === "draw") {
+        gameUI.notifyDrawOfferClosed(keyword === "declined", JSON.parse(num));
+      } else if (offer === "takeback") {
+        gameUI.notifyTakebackRequestClosed(
+          keyword === "declined",
+          JSON.parse(num)
+        );
+      }
+    } else if (keyword === "takenback") {
+      let [num, takebacksLeft] = msg.split(" ");
+      gameUI.notifyTakenBack(JSON.parse(num), JSON.parse(takebacksLeft));
+    } else if (keyword === "clock") {
+      let [timeLeft1, timeLeft2, runningNum] = msg.split(" ").map(JSON.parse);
+      gameUI.setClock([timeLeft1, timeLeft2], runningNum);
+    } else if (keyword === "deadline") {
+      gameUI.setMoveDeadline(msg);
+    } else if (keyword === "suggested") {
+      let [num, row, col, reason, hintsUsed] = msg.split(" ");
+      gameUI.notifySuggested(
+        JSON.parse(num),
+        JSON.parse(row),
+        JSON.parse(col),
+        reason,
+        JSON.parse(hintsUsed)
+      );
+    } else if (keyword === "thinking") {
+      gameUI.notifyThinking(JSON.parse(msg));
+    } else if (keyword === "abandoned") {
+      gameUI.notifyAbandoned(JSON.parse(msg));
+    } else if (keyword === "returned") {
+      gameUI.notifyReturned(JSON.parse(msg));
+    } else if (keyword === "nextgame") {
+      window.location.assign(`/game/${msg}/play`);
+    } else if (keyword === "seated") {
+      gameUI.notifySeatTaken(JSON.parse(msg));
+    } else if (keyword === "claimed") {
+      gameUI.notifySeatClaimed(JSON.parse(msg));
     }
   };
 });
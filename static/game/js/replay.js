@@ -0,0 +1,201 @@
+window.addEventListener("DOMContentLoaded", (event) => {
+  const playerNames = JSON.parse(
+    document.getElementById("playerNames").textContent
+  );
+  const computerNames = JSON.parse(
+    document.getElementById("computerNames").textContent
+  );
+  const boards = JSON.parse(document.getElementById("boards").textContent);
+  const annotations = JSON.parse(
+    document.getElementById("annotations").textContent
+  );
+
+  const getPlayerName = (num) =>
+    computerNames[num - 1] || playerNames[num - 1] || `Player ${num}`;
+  document.querySelector(".black-name").textContent = getPlayerName(1);
+  document.querySelector(".white-name").textContent = getPlayerName(2);
+
+  // the first board is the empty one, before any plies
+  const lastPly = boards.length - 1;
+  let ply = lastPly;
+
+  const showPly = (newPly) => {
+    ply = Math.max(0, Math.min(lastPly, newPly));
+    showBoard();
+    showComment();
+    showGraphMarker();
+
+    for (let itemElt of document.querySelectorAll(".ply-list li")) {
+      itemElt.classList.toggle(
+        "fw-bold",
+        JSON.parse(itemElt.dataset.ply) === ply
+      );
+    }
+  };
+
+  const showBoard = () => {
+    let cardElt = document.querySelector(".game-card");
+    cardElt.replaceChildren();
+    cardElt.style.setProperty("--board-rows", boards[ply].length);
+    cardElt.style.setProperty("--board-cols", boards[ply][0].length);
+
+    let annotation = annotations[ply - 1];
+    boards[ply].forEach((row, i) => {
+      row.forEach((cellData, j) => {
+        let cellElt = document.createElement("div");
+        cellElt.classList.add("cell");
+        if (cellData === 1) {
+          cellElt.classList.add("colored", "black");
+        } else if (cellData === 2) {
+          cellElt.classList.add("colored", "white");
+        } else {
+          cellElt.classList.add("disabled");
+        }
+
+        // the engine's choice, where it differs from the play made
+        if (
+          annotation &&
+          annotation.judgement &&
+          annotation.best_row === i &&
+          annotation.best_col === j
+        ) {
+          cellElt.classList.add("hinted");
+        }
+        cardElt.appendChild(cellElt);
+      });
+    });
+
+    document.querySelector(".ply-counter").textContent = `${ply} / ${lastPly}`;
+  };
+
+  const describePly = (annotation, isJudged) => {
+    let text = `${getPlayerName(annotation.player_num)} played ${
+      annotation.play_row
+    },${annotation.play_col}`;
+    if (isJudged && annotation.judgement) {
+      text += ` (${annotation.judgement}, ${annotation.best_row},${annotation.best_col} was better)`;
+    }
+    return text;
+  };
+
+  const showComment = () => {
+    let annotation = annotations[ply - 1];
+    let commentElt = document.querySelector(".ply-comment");
+    if (ply === 0) {
+      commentElt.textContent = "The empty board.";
+    } else if (annotation) {
+      commentElt.textContent = describePly(annotation, true);
+    } else {
+      commentElt.textContent = "";
+    }
+  };
+
+  // the graph runs from the empty board (even) to the last ply
+  const graphWidth = 300;
+  const graphHeight = 120;
+  const toPoint = (i, advantage) => [
+    (i / Math.max(lastPly, 1)) * graphWidth,
+    graphHeight / 2 - (advantage / 100) * (graphHeight / 2 - 4),
+  ];
+
+  const drawGraph = () => {
+    let graphElt = document.querySelector(".advantage-graph");
+    if (!graphElt) {
+      return;
+    }
+    const svgNS = "http://www.w3.org/2000/svg";
+
+    let axisElt = document.createElementNS(svgNS, "line");
+    axisElt.setAttribute("x1", 0);
+    axisElt.setAttribute("x2", graphWidth);
+    axisElt.setAttribute("y1", graphHeight / 2);
+    axisElt.setAttribute("y2", graphHeight / 2);
+    axisElt.setAttribute("stroke", "#ccc");
+    graphElt.appendChild(axisElt);
+
+    let points = [toPoint(0, 0)].concat(
+      annotations.map((annotation) =>
+        toPoint(annotation.ply, annotation.advantage)
+      )
+    );
+    let lineElt = document.createElementNS(svgNS, "polyline");
+    lineElt.setAttribute("points", points.map((p) => p.join(",")).join(" "));
+    lineElt.setAttribute("fill", "none");
+    lineElt.setAttribute("stroke", "firebrick");
+    lineElt.setAttribute("stroke-width", 2);
+    lineElt.setAttribute("vector-effect", "non-scaling-stroke");
+    graphElt.appendChild(lineElt);
+
+    let markerElt = document.createElementNS(svgNS, "line");
+    markerElt.classList.add("marker");
+    markerElt.setAttribute("y1", 0);
+    markerElt.setAttribute("y2", graphHeight);
+    markerElt.setAttribute("stroke", "black");
+    markerElt.setAttribute("vector-effect", "non-scaling-stroke");
+    graphElt.appendChild(markerElt);
+
+    graphElt.addEventListener("click", (event) => {
+      let rect = graphElt.getBoundingClientRect();
+      let x = (event.clientX - rect.left) / rect.width;
+      showPly(Math.round(x * lastPly));
+    });
+  };
+
+  const showGraphMarker = () => {
+    let markerElt = document.querySelector(".advantage-graph .marker");
+    if (markerElt) {
+      let [x, _y] = toPoint(ply, 0);
+      markerElt.setAttribute("x1", x);
+      markerElt.setAttribute("x2", x);
+    }
+  };
+
+  const listPlies = () => {
+    let listElt = document.querySelector(".ply-list");
+    if (!listElt) {
+      return;
+    }
+
+    const badges = {
+      inaccuracy: "bg-warning text-dark",
+      mistake: "bg-danger",
+      blunder: "bg-dark",
+    };
+    for (let annotation of annotations) {
+      let itemElt = document.createElement("li");
+      itemElt.dataset.ply = annotation.ply;
+      itemElt.style.cursor = "pointer";
+      itemElt.textContent = describePly(annotation, false);
+      if (annotation.judgement) {
+        let badgeElt = document.createElement("span");
+        badgeElt.className = `badge ms-1 ${badges[annotation.judgement]}`;
+        badgeElt.textContent = annotation.judgement;
+        itemElt.appendChild(badgeElt);
+      }
+      itemElt.addEventListener("click", () => showPly(annotation.ply));
+      listElt.appendChild(itemElt);
+    }
+  };
+
+  for (let [selector, getPly] of [
+    [".first-button", () => 0],
+    [".previous-button", () => ply - 1],
+    [".next-button", () => ply + 1],
+    [".last-button", () => lastPly],
+  ]) {
+    document
+      .querySelector(selector)
+      .addEventListener("click", () => showPly(getPly()));
+  }
+  document.addEventListener("keydown", (event) => {
+    if (event.key === "ArrowLeft") {
+      showPly(ply - 1);
+    } else if (event.key === "ArrowRight") {
+      showPly(ply + 1);
+    }
+  });
+
+  drawGraph();
+  listPlies();
+  showPly(lastPly);
+});
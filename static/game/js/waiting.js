@@ -0,0 +1,30 @@
+window.addEventListener("DOMContentLoaded", (event) => {
+  const matchmakingSocketUrl = JSON.parse(
+    document.getElementById("matchmakingWsUrl").textContent
+  );
+  const waitingStatus = document.getElementById("waitingStatus");
+  let isMatched = false;
+
+  const websocket = new WebSocket(matchmakingSocketUrl);
+
+  websocket.onclose = function (event) {
+    if (!isMatched) {
+      waitingStatus.innerText =
+        "Lost connection to the server. Please refresh the page to keep waiting.";
+    }
+  };
+
+  websocket.onmessage = function (event) {
+    const separatorIndex = event.data.indexOf(" ");
+    if (separatorIndex === -1) {
+      return;
+    }
+    const keyword = event.data.slice(0, separatorIndex).toLowerCase();
+    const rest = event.data.slice(separatorIndex + 1);
+
+    if (keyword === "matched") {
+      isMatched = true;
+      window.location.assign(rest);
+    }
+  };
+});